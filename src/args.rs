@@ -0,0 +1,103 @@
+use crate::light::{Light, MAX_LIGHTS};
+use crate::normals::Weighting;
+use crate::parser::ParseOptions;
+use crate::texture::ImageFormat;
+use std::fs::File;
+use std::io::Read;
+
+const DEFAULT_MODEL: &str = "models/42.obj";
+const DEFAULT_TEXTURE: &str = "textures/sigma_cat.bmp";
+const INVALID_FILES: &str =
+    "Invalid arguments\nModels accepted: .obj, .stl, .ply, .gltf, .glb, .off, Textures accepted: BMP, PNG, TGA, PPM, PGM";
+
+pub struct Args {
+    pub model_path: String,
+    pub texture_path: String,
+    pub lights: Vec<Light>,
+    pub parse_options: ParseOptions,
+    /// Write the loaded model here (.obj, .stl or .ply) instead of viewing it.
+    pub export_path: Option<String>,
+}
+
+impl Args {
+    /// Parses the command line (without the program name): up to one model and
+    /// one texture in any order, plus options. Files are told apart by their
+    /// contents: anything that isn't an image is taken as the model, whose
+    /// format is worked out when loading it.
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+        let mut files = Vec::new();
+        let mut lights = Vec::new();
+        let mut parse_options = ParseOptions::default();
+        let mut export_path = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--light" => {
+                    let spec = args
+                        .next()
+                        .ok_or("Error: --light expects a value like point:x,y,z[:r,g,b]")?;
+                    if lights.len() == MAX_LIGHTS {
+                        return Err(format!("Error: At most {} lights are supported", MAX_LIGHTS));
+                    }
+                    lights.push(Light::from_spec(&spec).map_err(|e| format!("Error: {}", e))?);
+                }
+                "--strict" => parse_options.strict = true,
+                "--crease-angle" => {
+                    let angle = args
+                        .next()
+                        .and_then(|value| value.parse::<f32>().ok())
+                        .filter(|angle| (0.0..=180.0).contains(angle))
+                        .ok_or("Error: --crease-angle expects an angle in degrees (0-180)")?;
+                    parse_options.normals.crease_angle = Some(angle);
+                }
+                "--normal-weighting" => {
+                    let name = args
+                        .next()
+                        .ok_or("Error: --normal-weighting expects uniform, area or angle")?;
+                    parse_options.normals.weighting =
+                        Weighting::from_name(&name).map_err(|e| format!("Error: {}", e))?;
+                }
+                "--export" => {
+                    export_path = Some(
+                        args.next()
+                            .ok_or("Error: --export expects an output file (.obj, .stl or .ply)")?,
+                    );
+                }
+                _ if arg.starts_with("--") => {
+                    return Err(format!("Error: Unknown option {}", arg));
+                }
+                _ => files.push(arg),
+            }
+        }
+
+        let (model_path, texture_path) = match files.as_slice() {
+            [] => (DEFAULT_MODEL.to_string(), DEFAULT_TEXTURE.to_string()),
+            [arg] if is_texture(arg) => (DEFAULT_MODEL.to_string(), arg.clone()),
+            [arg] => (arg.clone(), DEFAULT_TEXTURE.to_string()),
+            [arg1, arg2] => match (is_texture(arg1), is_texture(arg2)) {
+                (false, true) => (arg1.clone(), arg2.clone()),
+                (true, false) => (arg2.clone(), arg1.clone()),
+                _ => return Err(INVALID_FILES.to_string()),
+            },
+            _ => return Err("Error: Too many arguments".to_string()),
+        };
+
+        Ok(Args {
+            model_path,
+            texture_path,
+            lights,
+            parse_options,
+            export_path,
+        })
+    }
+}
+
+/// Whether the file starts like an image. Files that can't be read are taken
+/// as models, so the error is reported when loading them.
+fn is_texture(path: &str) -> bool {
+    let mut header = Vec::new();
+    File::open(path)
+        .and_then(|file| file.take(64).read_to_end(&mut header))
+        .is_ok()
+        && ImageFormat::sniff(&header).is_some()
+}
@@ -0,0 +1,483 @@
+use crate::texture::Image;
+
+const BI_RGB: u32 = 0;
+const BI_RLE8: u32 = 1;
+const BI_RLE4: u32 = 2;
+const BI_BITFIELDS: u32 = 3;
+const BI_ALPHABITFIELDS: u32 = 6;
+
+const FILE_HEADER_SIZE: usize = 14;
+const CORE_HEADER_SIZE: usize = 12;
+const INFO_HEADER_SIZE: usize = 40;
+
+pub fn is_bmp(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"BM")
+}
+
+/// The header fields the pixel decoders need.
+struct Header {
+    width: usize,
+    height: usize,
+    top_down: bool,
+    bits_per_pixel: u16,
+    compression: u32,
+    /// Red, green, blue and alpha masks, for 16 and 32-bit images.
+    masks: [u32; 4],
+    palette: Vec<[u8; 4]>,
+    data_offset: usize,
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Result<u16, String> {
+    bytes
+        .get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| "BMP header is truncated".to_string())
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Result<u32, String> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| "BMP header is truncated".to_string())
+}
+
+/// Decodes a Windows bitmap: uncompressed 1, 4, 8, 16, 24 and 32-bit images,
+/// BITFIELDS masks and RLE8/RLE4 compression, stored bottom-up or top-down.
+pub fn decode(bytes: &[u8]) -> Result<Image, String> {
+    if !is_bmp(bytes) {
+        return Err("not a BMP file".to_string());
+    }
+    let header = read_header(bytes)?;
+    let mut image = Image::new(header.width, header.height)?;
+    match header.compression {
+        BI_RLE8 | BI_RLE4 => decode_rle(bytes, &header, &mut image)?,
+        _ => decode_rows(bytes, &header, &mut image)?,
+    }
+    Ok(image)
+}
+
+fn read_header(bytes: &[u8]) -> Result<Header, String> {
+    let data_offset = u32_at(bytes, 10)? as usize;
+    let header_size = u32_at(bytes, FILE_HEADER_SIZE)? as usize;
+    let info = FILE_HEADER_SIZE;
+
+    let (width, height, planes, bits_per_pixel, compression, colors_used) =
+        if header_size == CORE_HEADER_SIZE {
+            (
+                u16_at(bytes, info + 4)? as i64,
+                u16_at(bytes, info + 6)? as i64,
+                u16_at(bytes, info + 8)?,
+                u16_at(bytes, info + 10)?,
+                BI_RGB,
+                0,
+            )
+        } else if header_size >= INFO_HEADER_SIZE {
+            (
+                u32_at(bytes, info + 4)? as i32 as i64,
+                u32_at(bytes, info + 8)? as i32 as i64,
+                u16_at(bytes, info + 12)?,
+                u16_at(bytes, info + 14)?,
+                u32_at(bytes, info + 16)?,
+                u32_at(bytes, info + 32)? as usize,
+            )
+        } else {
+            return Err(format!("unsupported BMP header size {}", header_size));
+        };
+
+    if planes != 1 {
+        return Err(format!("BMP has {} planes, expected 1", planes));
+    }
+    if width <= 0 || height == 0 {
+        return Err(format!("invalid BMP size {}x{}", width, height));
+    }
+    let top_down = height < 0;
+
+    let valid = match compression {
+        BI_RGB => matches!(bits_per_pixel, 1 | 4 | 8 | 16 | 24 | 32),
+        BI_RLE8 => bits_per_pixel == 8 && !top_down,
+        BI_RLE4 => bits_per_pixel == 4 && !top_down,
+        BI_BITFIELDS | BI_ALPHABITFIELDS => matches!(bits_per_pixel, 16 | 32),
+        _ => return Err(format!("unsupported BMP compression {}", compression)),
+    };
+    if !valid {
+        return Err(format!(
+            "unsupported BMP: {} bits per pixel with compression {}",
+            bits_per_pixel, compression
+        ));
+    }
+
+    // Masks live inside V2+ headers, or right after a plain info header
+    let mut table = info + header_size;
+    let masks = if compression == BI_BITFIELDS || compression == BI_ALPHABITFIELDS {
+        let at = if header_size == INFO_HEADER_SIZE {
+            table += if compression == BI_ALPHABITFIELDS {
+                16
+            } else {
+                12
+            };
+            info + header_size
+        } else {
+            info + INFO_HEADER_SIZE
+        };
+        let has_alpha = compression == BI_ALPHABITFIELDS || header_size >= INFO_HEADER_SIZE + 16;
+        [
+            u32_at(bytes, at)?,
+            u32_at(bytes, at + 4)?,
+            u32_at(bytes, at + 8)?,
+            if has_alpha {
+                u32_at(bytes, at + 12)?
+            } else {
+                0
+            },
+        ]
+    } else if bits_per_pixel == 16 {
+        [0x7c00, 0x03e0, 0x001f, 0]
+    } else {
+        [0x00ff_0000, 0x0000_ff00, 0x0000_00ff, 0]
+    };
+
+    let mut palette = Vec::new();
+    if bits_per_pixel <= 8 {
+        let max_colors = 1 << bits_per_pixel;
+        let count = if colors_used == 0 || colors_used > max_colors {
+            max_colors
+        } else {
+            colors_used
+        };
+        let entry_size = if header_size == CORE_HEADER_SIZE {
+            3
+        } else {
+            4
+        };
+        for i in 0..count {
+            let at = table + i * entry_size;
+            // Some writers store fewer entries than they declare
+            if at + entry_size > data_offset.min(bytes.len()) {
+                break;
+            }
+            palette.push([bytes[at + 2], bytes[at + 1], bytes[at], 255]);
+        }
+        if palette.is_empty() {
+            return Err("BMP palette is missing".to_string());
+        }
+    }
+
+    Ok(Header {
+        width: width as usize,
+        height: height.unsigned_abs() as usize,
+        top_down,
+        bits_per_pixel,
+        compression,
+        masks,
+        palette,
+        data_offset,
+    })
+}
+
+fn palette_color(header: &Header, index: usize) -> Result<[u8; 4], String> {
+    header
+        .palette
+        .get(index)
+        .copied()
+        .ok_or_else(|| format!("BMP palette index {} is out of range", index))
+}
+
+/// Extracts the channel under `mask` and scales it to 0..=255.
+fn channel(value: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    let max = mask >> mask.trailing_zeros();
+    ((((value & mask) >> mask.trailing_zeros()) as u64 * 255 + max as u64 / 2) / max as u64) as u8
+}
+
+fn decode_rows(bytes: &[u8], header: &Header, image: &mut Image) -> Result<(), String> {
+    let bits = header.bits_per_pixel as usize;
+    // Rows are padded to a multiple of 4 bytes
+    let stride = (header.width * bits).div_ceil(32) * 4;
+    let size = stride * header.height;
+    let data = bytes
+        .get(header.data_offset..)
+        .and_then(|data| data.get(..size))
+        .ok_or_else(|| {
+            format!(
+                "BMP pixel data is truncated: expected {} bytes at offset {}",
+                size, header.data_offset
+            )
+        })?;
+
+    let [red, green, blue, alpha] = header.masks;
+    // Plain 32-bit files may carry alpha in their unused byte; treat it as
+    // alpha unless it's zero everywhere
+    let implicit_alpha = header.compression == BI_RGB
+        && bits == 32
+        && data.chunks_exact(4).any(|pixel| pixel[3] != 0);
+
+    for (row_index, row) in data.chunks_exact(stride).enumerate() {
+        let y = if header.top_down {
+            row_index
+        } else {
+            header.height - 1 - row_index
+        };
+        for x in 0..header.width {
+            let color = match bits {
+                1 | 4 | 8 => {
+                    let bit = x * bits;
+                    let shift = 8 - bits - bit % 8;
+                    let index = (row[bit / 8] >> shift) as usize & ((1 << bits) - 1);
+                    palette_color(header, index)?
+                }
+                16 => {
+                    let value = u16::from_le_bytes([row[x * 2], row[x * 2 + 1]]) as u32;
+                    let a = if alpha == 0 {
+                        255
+                    } else {
+                        channel(value, alpha)
+                    };
+                    [
+                        channel(value, red),
+                        channel(value, green),
+                        channel(value, blue),
+                        a,
+                    ]
+                }
+                24 => [row[x * 3 + 2], row[x * 3 + 1], row[x * 3], 255],
+                _ => {
+                    let p = &row[x * 4..x * 4 + 4];
+                    if implicit_alpha {
+                        [p[2], p[1], p[0], p[3]]
+                    } else {
+                        let value = u32::from_le_bytes([p[0], p[1], p[2], p[3]]);
+                        let a = if alpha == 0 {
+                            255
+                        } else {
+                            channel(value, alpha)
+                        };
+                        [
+                            channel(value, red),
+                            channel(value, green),
+                            channel(value, blue),
+                            a,
+                        ]
+                    }
+                }
+            };
+            image.set_pixel(x, y, color);
+        }
+    }
+    Ok(())
+}
+
+/// Pixels an RLE stream skips over with deltas or early line ends stay
+/// transparent black.
+fn decode_rle(bytes: &[u8], header: &Header, image: &mut Image) -> Result<(), String> {
+    let four_bit = header.compression == BI_RLE4;
+    let data = bytes
+        .get(header.data_offset..)
+        .ok_or_else(|| "BMP pixel data offset is past the end of the file".to_string())?;
+    let truncated = || "BMP RLE data is truncated".to_string();
+
+    let (mut x, mut row) = (0usize, 0usize);
+    let mut put = |x: &mut usize, row: usize, index: usize| -> Result<(), String> {
+        // Runs may overshoot the row; the extra pixels are dropped
+        if *x < header.width && row < header.height {
+            let color = palette_color(header, index)?;
+            image.set_pixel(*x, header.height - 1 - row, color);
+        }
+        *x += 1;
+        Ok(())
+    };
+
+    let mut at = 0;
+    loop {
+        let (&count, &value) = data.get(at).zip(data.get(at + 1)).ok_or_else(truncated)?;
+        at += 2;
+        if count > 0 {
+            for i in 0..count as usize {
+                let index = if !four_bit {
+                    value
+                } else if i % 2 == 0 {
+                    value >> 4
+                } else {
+                    value & 0x0f
+                };
+                put(&mut x, row, index as usize)?;
+            }
+            continue;
+        }
+        match value {
+            0 => {
+                x = 0;
+                row += 1;
+            }
+            1 => return Ok(()),
+            2 => {
+                let (&dx, &dy) = data.get(at).zip(data.get(at + 1)).ok_or_else(truncated)?;
+                at += 2;
+                x += dx as usize;
+                row += dy as usize;
+            }
+            length => {
+                let length = length as usize;
+                let byte_count = if four_bit { length.div_ceil(2) } else { length };
+                let run = data.get(at..at + byte_count).ok_or_else(truncated)?;
+                for i in 0..length {
+                    let index = if !four_bit {
+                        run[i]
+                    } else if i % 2 == 0 {
+                        run[i / 2] >> 4
+                    } else {
+                        run[i / 2] & 0x0f
+                    };
+                    put(&mut x, row, index as usize)?;
+                }
+                // Absolute runs are padded to a 16-bit boundary
+                at += byte_count + byte_count % 2;
+            }
+        }
+        if row >= header.height {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a file with a 40-byte info header followed by `extra` (masks
+    /// or palette) and the pixel data.
+    fn bmp(
+        width: i32,
+        height: i32,
+        bits: u16,
+        compression: u32,
+        extra: &[u8],
+        pixels: &[u8],
+    ) -> Vec<u8> {
+        let offset = (FILE_HEADER_SIZE + INFO_HEADER_SIZE + extra.len()) as u32;
+        let mut bytes = b"BM".to_vec();
+        bytes.extend((offset + pixels.len() as u32).to_le_bytes());
+        bytes.extend([0; 4]);
+        bytes.extend(offset.to_le_bytes());
+        bytes.extend((INFO_HEADER_SIZE as u32).to_le_bytes());
+        bytes.extend(width.to_le_bytes());
+        bytes.extend(height.to_le_bytes());
+        bytes.extend(1u16.to_le_bytes());
+        bytes.extend(bits.to_le_bytes());
+        bytes.extend(compression.to_le_bytes());
+        bytes.extend([0; 20]);
+        bytes.extend(extra);
+        bytes.extend(pixels);
+        bytes
+    }
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const GREEN: [u8; 4] = [0, 255, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+    const WHITE: [u8; 4] = [255, 255, 255, 255];
+    const CLEAR: [u8; 4] = [0, 0, 0, 0];
+
+    /// Palette entries in file order (BGRx).
+    fn palette(colors: &[[u8; 4]]) -> Vec<u8> {
+        colors.iter().flat_map(|c| [c[2], c[1], c[0], 0]).collect()
+    }
+
+    #[test]
+    fn padded_rows_are_read_bottom_up_or_top_down() {
+        // 3x2 at 24 bits: 9 bytes per row, padded to 12
+        let pixels = [
+            0, 0, 255, 0, 255, 0, 255, 0, 0, 0, 0, 0, // bottom: red green blue
+            255, 255, 255, 0, 0, 0, 0, 0, 255, 0, 0, 0, // top: white black red
+        ];
+        let image = decode(&bmp(3, 2, 24, BI_RGB, &[], &pixels)).unwrap();
+        assert_eq!((image.width, image.height), (3, 2));
+        assert_eq!(image.pixel(0, 0), WHITE);
+        assert_eq!(image.pixel(2, 0), RED);
+        assert_eq!(image.pixel(0, 1), RED);
+        assert_eq!(image.pixel(2, 1), BLUE);
+
+        let image = decode(&bmp(3, -2, 24, BI_RGB, &[], &pixels)).unwrap();
+        assert_eq!(image.pixel(0, 0), RED);
+        assert_eq!(image.pixel(1, 0), GREEN);
+        assert_eq!(image.pixel(0, 1), WHITE);
+    }
+
+    #[test]
+    fn indexed_and_masked_pixels_are_expanded() {
+        let colors = palette(&[RED, GREEN, BLUE, WHITE]);
+        // 1 bit: 10 pixels, 0b10110000_01000000
+        let image = decode(&bmp(10, 1, 1, BI_RGB, &colors, &[0xb0, 0x40, 0, 0])).unwrap();
+        let row: Vec<_> = (0..10).map(|x| image.pixel(x, 0)).collect();
+        assert_eq!(row[..4], [GREEN, RED, GREEN, GREEN]);
+        assert_eq!(row[9], GREEN);
+        let image = decode(&bmp(3, 1, 4, BI_RGB, &colors, &[0x23, 0x10, 0, 0])).unwrap();
+        assert_eq!(image.pixel(0, 0), BLUE);
+        assert_eq!(image.pixel(1, 0), WHITE);
+        assert_eq!(image.pixel(2, 0), GREEN);
+        let image = decode(&bmp(2, 1, 8, BI_RGB, &colors, &[3, 2, 0, 0])).unwrap();
+        assert_eq!(image.pixel(0, 0), WHITE);
+        assert!(decode(&bmp(1, 1, 8, BI_RGB, &colors, &[9, 0, 0, 0])).is_err());
+
+        // Default 16-bit masks are 5-5-5
+        let image = decode(&bmp(2, 1, 16, BI_RGB, &[], &[0x00, 0x7c, 0x1f, 0x00])).unwrap();
+        assert_eq!(image.pixel(0, 0), RED);
+        assert_eq!(image.pixel(1, 0), BLUE);
+        // 5-6-5 bitfields
+        let masks: Vec<u8> = [0xf800u32, 0x07e0, 0x001f]
+            .iter()
+            .flat_map(|m| m.to_le_bytes())
+            .collect();
+        let image = decode(&bmp(1, 1, 16, BI_BITFIELDS, &masks, &[0xe0, 0x07, 0, 0])).unwrap();
+        assert_eq!(image.pixel(0, 0), GREEN);
+
+        // 32-bit keeps the fourth byte as alpha when it's used
+        let image = decode(&bmp(2, 1, 32, BI_RGB, &[], &[255, 0, 0, 128, 0, 0, 255, 0])).unwrap();
+        assert_eq!(image.pixel(0, 0), [0, 0, 255, 128]);
+        assert_eq!(image.pixel(1, 0), [255, 0, 0, 0]);
+        let image = decode(&bmp(1, 1, 32, BI_RGB, &[], &[0, 255, 0, 0])).unwrap();
+        assert_eq!(image.pixel(0, 0), GREEN);
+        let masks: Vec<u8> = [0xff00_0000u32, 0x00ff_0000, 0x0000_ff00, 0x0000_00ff]
+            .iter()
+            .flat_map(|m| m.to_le_bytes())
+            .collect();
+        let image = decode(&bmp(1, 1, 32, BI_ALPHABITFIELDS, &masks, &[64, 30, 20, 10])).unwrap();
+        assert_eq!(image.pixel(0, 0), [10, 20, 30, 64]);
+    }
+
+    #[test]
+    fn run_length_encoded_pixels_are_expanded() {
+        let colors = palette(&[RED, GREEN, BLUE, WHITE]);
+        // Bottom row: 2 blue, absolute [0 1 3], end of line; top row: delta
+        // right by 2, 1 green, end of bitmap
+        let rle8 = [2, 2, 0, 3, 0, 1, 3, 0, 0, 0, 0, 2, 2, 0, 1, 1, 0, 1];
+        let image = decode(&bmp(5, 2, 8, BI_RLE8, &colors, &rle8)).unwrap();
+        let bottom: Vec<_> = (0..5).map(|x| image.pixel(x, 1)).collect();
+        assert_eq!(bottom, [BLUE, BLUE, RED, GREEN, WHITE]);
+        let top: Vec<_> = (0..5).map(|x| image.pixel(x, 0)).collect();
+        assert_eq!(top, [CLEAR, CLEAR, GREEN, CLEAR, CLEAR]);
+
+        // 3 pixels alternating white and green, then absolute [2 0 1]
+        let rle4 = [3, 0x31, 0, 3, 0x20, 0x10, 0, 1];
+        let image = decode(&bmp(6, 1, 4, BI_RLE4, &colors, &rle4)).unwrap();
+        let row: Vec<_> = (0..6).map(|x| image.pixel(x, 0)).collect();
+        assert_eq!(row, [WHITE, GREEN, WHITE, BLUE, RED, GREEN]);
+
+        assert!(decode(&bmp(2, 1, 8, BI_RLE8, &colors, &[2, 1, 0])).is_err());
+    }
+
+    #[test]
+    fn malformed_files_are_rejected() {
+        let pixels = [0; 8];
+        assert!(decode(b"BM").is_err());
+        assert!(decode(&bmp(1, 1, 24, BI_RGB, &[], &pixels)[2..]).is_err());
+        // Pixel data shorter than the rows it declares
+        assert!(decode(&bmp(4, 4, 24, BI_RGB, &[], &pixels)).is_err());
+        assert!(decode(&bmp(0, 1, 24, BI_RGB, &[], &pixels)).is_err());
+        assert!(decode(&bmp(1, 1, 12, BI_RGB, &[], &pixels)).is_err());
+        assert!(decode(&bmp(1, -1, 8, BI_RLE8, &[0; 4], &pixels)).is_err());
+        assert!(decode(&bmp(1, 1, 24, 4, &[], &pixels)).is_err());
+        assert!(decode(&bmp(100_000, 100_000, 24, BI_RGB, &[], &pixels)).is_err());
+        assert!(decode(&bmp(i32::MAX, 1, 32, BI_RGB, &[], &pixels)).is_err());
+    }
+}
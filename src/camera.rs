@@ -1,57 +1,201 @@
+use crate::matrices::Mat4;
+use crate::quaternion::Quat;
+use crate::vectors::Vec3;
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum DragMode {
+    Orbit,
+    Pan,
+}
+
+struct DragState {
+    mode: DragMode,
+    last: (i32, i32),
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum ProjectionMode {
+    Perspective,
+    Orthographic,
+}
+
 pub struct Camera {
-    pub position: [f32; 3],
-    pub target: [f32; 3],
+    pub position: Vec3,
+    pub target: Vec3,
+    pub up: Vec3,
+    orientation: Quat,
+    distance: f32,
+    drag: Option<DragState>,
     pub width: i32,
     pub height: i32,
     aspect_ratio: f32,
-    pub projection: [[f32; 4]; 4],
+    pub fov: f32,
+    pub near: f32,
+    pub far: f32,
+    pub projection_mode: ProjectionMode,
+    pub projection: Mat4,
+    pub view: Mat4,
 }
 
 impl Camera {
     pub fn new(width: i32, height: i32) -> Self {
-        let aspect_ratio = width as f32 / height as f32;
-        Camera {
-            position: [0.0, 0.0, 10.0],
-            target: [0.0, 0.0, 0.0],
+        let mut camera = Camera {
+            position: Vec3::new(0.0, 0.0, 10.0),
+            target: Vec3::ZERO,
+            up: Vec3::Y,
+            orientation: Quat::IDENTITY,
+            distance: 10.0,
+            drag: None,
             width,
             height,
-            aspect_ratio,
-            projection: Camera::calculate_projection(aspect_ratio),
-        }
+            aspect_ratio: width as f32 / height as f32,
+            fov: 60.0f32.to_radians(),
+            near: 0.1,
+            far: 1000.0,
+            projection_mode: ProjectionMode::Perspective,
+            projection: Mat4::IDENTITY,
+            view: Mat4::IDENTITY,
+        };
+        camera.update_projection();
+        camera.update_view();
+        camera
     }
 
     pub fn update_resolution(&mut self, width: i32, height: i32) {
         self.width = width;
         self.height = height;
         self.aspect_ratio = width as f32 / height as f32;
-        self.projection = Camera::calculate_projection(self.aspect_ratio);
+        self.update_projection();
     }
 
-    fn calculate_projection(aspect_ratio: f32) -> [[f32; 4]; 4] {
-        let fov = 60.0f32.to_radians();
-        let near = 0.1;
-        let far = 1000.0;
-        
-        let scale = (fov / 2.0).tan();
-        let r = aspect_ratio * scale;
-        let l = -r;
-        let t = scale;
-        let b = -t;
+    pub fn update_projection(&mut self) {
+        self.projection = match self.projection_mode {
+            ProjectionMode::Perspective => self.calculate_perspective(),
+            ProjectionMode::Orthographic => self.calculate_orthographic(),
+        };
+    }
+
+    fn calculate_perspective(&self) -> Mat4 {
+        Mat4::perspective(self.fov, self.aspect_ratio, self.near, self.far)
+    }
 
-        [
-            [2.0 / (r - l), 0.0, 0.0, 0.0],
-            [0.0, 2.0 / (t - b), 0.0, 0.0],
-            [0.0, 0.0, -2.0 / (far - near), -(far + near) / (far - near)],
-            [0.0, 0.0, 0.0, 1.0],
-        ]
+    fn calculate_orthographic(&self) -> Mat4 {
+        // Size the view volume so the target plane matches the perspective framing
+        let t = self.distance() * (self.fov / 2.0).tan();
+        let r = t * self.aspect_ratio;
+        Mat4::orthographic(-r, r, -t, t, self.near, self.far)
     }
 
-    pub fn update_target(&mut self, model_center: [f32; 3]) {
+    pub fn toggle_projection(&mut self) {
+        self.projection_mode = match self.projection_mode {
+            ProjectionMode::Perspective => ProjectionMode::Orthographic,
+            ProjectionMode::Orthographic => ProjectionMode::Perspective,
+        };
+        self.update_projection();
+    }
+
+    pub fn set_fov(&mut self, fov_degrees: f32) {
+        self.fov = fov_degrees.clamp(10.0, 120.0).to_radians();
+        self.update_projection();
+    }
+
+    pub fn update_view(&mut self) {
+        // The camera sits on +Z of its own frame, looking back at the target
+        self.position = self.target + self.orientation * Vec3::new(0.0, 0.0, self.distance);
+        self.up = self.orientation * Vec3::Y;
+        self.view = Mat4::look_at(self.position, self.target, self.up);
+    }
+
+    pub fn distance(&self) -> f32 {
+        self.distance
+    }
+
+    pub fn update_target(&mut self, model_center: Vec3) {
         self.target = model_center;
+        self.update_view();
     }
 
     pub fn zoom(&mut self, delta: f32) {
-        let new_z = self.position[2] + delta;
-        self.position[2] = new_z.max(0.0); // minimum distance
+        self.set_distance(self.distance + delta);
+    }
+
+    fn set_distance(&mut self, distance: f32) {
+        self.distance = distance.max(self.near); // minimum distance
+        self.update_view();
+        if self.projection_mode == ProjectionMode::Orthographic {
+            self.update_projection();
+        }
+    }
+
+    /// Rotates the camera around its target by `yaw` (around its up axis) and
+    /// `pitch` (around its right axis), both in radians.
+    pub fn orbit(&mut self, yaw: f32, pitch: f32) {
+        let yaw_rotation = Quat::from_axis_angle(Vec3::Y, yaw);
+        let pitch_rotation = Quat::from_axis_angle(Vec3::X, -pitch);
+        self.rotate_local(yaw_rotation * pitch_rotation);
+    }
+
+    fn rotate_local(&mut self, rotation: Quat) {
+        self.orientation = (self.orientation * rotation).normalize();
+        self.update_view();
+    }
+
+    /// Moves both the camera and its target along the view plane.
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        let right = self.orientation * Vec3::X;
+        let up = self.orientation * Vec3::Y;
+        self.target += right * dx + up * dy;
+        self.update_view();
+    }
+
+    pub fn begin_drag(&mut self, mode: DragMode, x: i32, y: i32) {
+        self.drag = Some(DragState { mode, last: (x, y) });
+    }
+
+    pub fn end_drag(&mut self) {
+        self.drag = None;
     }
-}
\ No newline at end of file
+
+    pub fn drag_to(&mut self, x: i32, y: i32) {
+        let Some(drag) = self.drag.as_mut() else {
+            return;
+        };
+        let (last_x, last_y) = drag.last;
+        drag.last = (x, y);
+
+        match drag.mode {
+            DragMode::Orbit => {
+                let from = self.arcball_vector(last_x, last_y);
+                let to = self.arcball_vector(x, y);
+                // Turning the camera the opposite way makes the scene follow the cursor
+                self.rotate_local(Quat::between(from, to).conjugate());
+            }
+            DragMode::Pan => {
+                // World units covered by one pixel on the target plane
+                let scale = 2.0 * self.distance * (self.fov / 2.0).tan() / self.height as f32;
+                self.pan(-(x - last_x) as f32 * scale, (y - last_y) as f32 * scale);
+            }
+        }
+    }
+
+    /// Zooms by a wheel step count; positive values move towards the target.
+    pub fn scroll(&mut self, amount: f32) {
+        self.set_distance(self.distance * 0.9f32.powf(amount));
+    }
+
+    /// Projects a window point onto a virtual trackball in camera space
+    /// (Holroyd's sphere/hyperbola blend, so dragging outside stays smooth).
+    fn arcball_vector(&self, x: i32, y: i32) -> Vec3 {
+        let size = self.width.min(self.height) as f32;
+        let px = (2.0 * x as f32 - self.width as f32) / size;
+        let py = (self.height as f32 - 2.0 * y as f32) / size;
+        let squared = px * px + py * py;
+
+        let pz = if squared <= 0.5 {
+            (1.0 - squared).sqrt()
+        } else {
+            0.5 / squared.sqrt()
+        };
+        Vec3::new(px, py, pz).normalize()
+    }
+}
@@ -0,0 +1,409 @@
+use crate::material::Material;
+use crate::parser::{ObjData, ObjError};
+use crate::vectors::Vec3;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Formats a loaded mesh can be written in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    /// Text OBJ with positions, texture coordinates, normals, parts and an
+    /// MTL library next to it.
+    Obj,
+    /// Binary STL: triangles with facet normals only.
+    Stl,
+    /// Binary little-endian PLY with one vertex per distinct corner.
+    Ply,
+}
+
+impl ExportFormat {
+    pub fn from_extension(path: &str) -> Option<Self> {
+        let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "obj" => Some(ExportFormat::Obj),
+            "stl" => Some(ExportFormat::Stl),
+            "ply" => Some(ExportFormat::Ply),
+            _ => None,
+        }
+    }
+}
+
+/// Writes `data` to `path` in the format its extension names. OBJ files get
+/// an MTL library with the same name when there are materials.
+pub fn export(data: &ObjData, path: &str) -> Result<(), ObjError> {
+    let format = ExportFormat::from_extension(path).ok_or_else(|| ObjError::InvalidData {
+        file: path.to_string(),
+        message: "can only export .obj, .stl or .ply files".to_string(),
+    })?;
+    let create = |path: &Path| {
+        File::create(path)
+            .map(BufWriter::new)
+            .map_err(|source| io_error(path, source))
+    };
+
+    let path = Path::new(path);
+    match format {
+        ExportFormat::Obj => {
+            let library = ObjLibrary::new(data);
+            let mtl_path = path.with_extension("mtl");
+            let mtl_name = mtl_path.file_name().unwrap().to_string_lossy();
+            if !library.materials.is_empty() {
+                let mut mtl = create(&mtl_path)?;
+                write_mtl(&library.materials, &mut mtl)
+                    .and_then(|_| mtl.flush())
+                    .map_err(|source| io_error(&mtl_path, source))?;
+            }
+            let mut obj = create(path)?;
+            write_obj(data, &library, &mtl_name, &mut obj).and_then(|_| obj.flush())
+        }
+        ExportFormat::Stl => {
+            let mut stl = create(path)?;
+            write_stl(data, &mut stl).and_then(|_| stl.flush())
+        }
+        ExportFormat::Ply => {
+            let mut ply = create(path)?;
+            write_ply(data, &mut ply).and_then(|_| ply.flush())
+        }
+    }
+    .map_err(|source| io_error(path, source))
+}
+
+fn io_error(path: &Path, source: io::Error) -> ObjError {
+    ObjError::Io {
+        file: path.to_string_lossy().into_owned(),
+        source,
+    }
+}
+
+/// The materials an exported OBJ refers to.
+struct ObjLibrary {
+    materials: Vec<Material>,
+    /// The material for ranges without one, when it has to be named because
+    /// `usemtl` can't be undone.
+    default: Option<usize>,
+}
+
+impl ObjLibrary {
+    fn new(data: &ObjData) -> Self {
+        let mut materials = data.materials.clone();
+        let mut default = None;
+        let mut current = None;
+        for range in &data.material_ranges {
+            if range.material.is_none() && current.is_some() && default.is_none() {
+                let mut name = Material::default().name;
+                while materials.iter().any(|m| m.name == name) {
+                    name.push('_');
+                }
+                materials.push(Material::new(&name));
+                default = Some(materials.len() - 1);
+            }
+            current = range.material.or(current);
+        }
+        ObjLibrary { materials, default }
+    }
+}
+
+fn write_obj<W: Write>(
+    data: &ObjData,
+    library: &ObjLibrary,
+    mtl_name: &str,
+    out: &mut W,
+) -> io::Result<()> {
+    writeln!(out, "# Exported by scop")?;
+    if !library.materials.is_empty() {
+        writeln!(out, "mtllib {}", mtl_name)?;
+    }
+    for (index, [x, y, z]) in data.vertices.iter().enumerate() {
+        match data.vertex_colors.get(index) {
+            Some([r, g, b]) => writeln!(out, "v {} {} {} {} {} {}", x, y, z, r, g, b)?,
+            None => writeln!(out, "v {} {} {}", x, y, z)?,
+        }
+    }
+    for [u, v] in &data.tex_coords {
+        writeln!(out, "vt {} {}", u, v)?;
+    }
+    for [x, y, z] in &data.normals {
+        writeln!(out, "vn {} {} {}", x, y, z)?;
+    }
+
+    let mut current_part = None;
+    let mut current_material = None;
+    for range in &data.material_ranges {
+        if current_part != Some(range.part) {
+            writeln!(out, "o {}", data.parts[range.part].name)?;
+            current_part = Some(range.part);
+        }
+        let material = range.material.or(current_material.and(library.default));
+        if material != current_material {
+            if let Some(material) = material {
+                writeln!(out, "usemtl {}", library.materials[material].name)?;
+            }
+            current_material = material;
+        }
+        for face in &data.faces[range.first_face..range.first_face + range.face_count] {
+            write!(out, "f")?;
+            for i in 0..3 {
+                write!(
+                    out,
+                    " {}/{}/{}",
+                    face.vertex_indices[i] + 1,
+                    face.tex_coord_indices[i] + 1,
+                    face.normal_indices[i] + 1
+                )?;
+            }
+            writeln!(out)?;
+        }
+    }
+    for [a, b] in &data.lines {
+        writeln!(out, "l {} {}", a + 1, b + 1)?;
+    }
+    for point in &data.points {
+        writeln!(out, "p {}", point + 1)?;
+    }
+    Ok(())
+}
+
+fn write_mtl<W: Write>(materials: &[Material], out: &mut W) -> io::Result<()> {
+    writeln!(out, "# Exported by scop")?;
+    for material in materials {
+        let [ka, kd, ks] = [material.ambient, material.diffuse, material.specular];
+        writeln!(out, "\nnewmtl {}", material.name)?;
+        writeln!(out, "Ka {} {} {}", ka[0], ka[1], ka[2])?;
+        writeln!(out, "Kd {} {} {}", kd[0], kd[1], kd[2])?;
+        writeln!(out, "Ks {} {} {}", ks[0], ks[1], ks[2])?;
+        writeln!(out, "Ns {}", material.shininess)?;
+        writeln!(out, "d {}", material.opacity)?;
+        writeln!(out, "illum {}", material.illumination)?;
+        // Maps were resolved against the source file's directory, which the
+        // exported library may not share
+        let absolute = |map: &str| {
+            std::path::absolute(map).map_or(map.to_string(), |p| p.to_string_lossy().into_owned())
+        };
+        if let Some(map) = &material.diffuse_map {
+            writeln!(out, "map_Kd {}", absolute(map))?;
+        }
+        if let Some(map) = &material.bump_map {
+            writeln!(
+                out,
+                "map_Bump -bm {} {}",
+                material.bump_multiplier,
+                absolute(map)
+            )?;
+        }
+        if let Some(map) = &material.alpha_map {
+            writeln!(out, "map_d {}", absolute(map))?;
+        }
+    }
+    Ok(())
+}
+
+fn write_stl<W: Write>(data: &ObjData, out: &mut W) -> io::Result<()> {
+    let mut header = [0; 80];
+    header[..16].copy_from_slice(b"Exported by scop");
+    out.write_all(&header)?;
+    out.write_all(&(data.faces.len() as u32).to_le_bytes())?;
+
+    for face in &data.faces {
+        let corners = face
+            .vertex_indices
+            .map(|i| Vec3::from(data.vertices[i as usize]));
+        let normal = (corners[1] - corners[0])
+            .cross(corners[2] - corners[0])
+            .normalize();
+        for value in [normal, corners[0], corners[1], corners[2]]
+            .iter()
+            .flat_map(|v| v.to_array())
+        {
+            out.write_all(&value.to_le_bytes())?;
+        }
+        out.write_all(&[0, 0])?;
+    }
+    Ok(())
+}
+
+fn write_ply<W: Write>(data: &ObjData, out: &mut W) -> io::Result<()> {
+    // PLY has one index per corner, so each distinct combination of
+    // position, texture coordinate and normal becomes a vertex. Without
+    // faces, the positions are written as a point cloud.
+    let mut corners: Vec<[u32; 3]> = Vec::new();
+    let mut unique: HashMap<[u32; 3], u32> = HashMap::new();
+    let faces: Vec<[u32; 3]> = data
+        .faces
+        .iter()
+        .map(|face| {
+            [0, 1, 2].map(|i| {
+                let corner = [
+                    face.vertex_indices[i],
+                    face.tex_coord_indices[i],
+                    face.normal_indices[i],
+                ];
+                *unique.entry(corner).or_insert_with(|| {
+                    corners.push(corner);
+                    (corners.len() - 1) as u32
+                })
+            })
+        })
+        .collect();
+    let has_faces = !faces.is_empty();
+    if !has_faces {
+        corners = (0..data.vertices.len() as u32).map(|i| [i, 0, 0]).collect();
+    }
+    let has_colors = !data.vertex_colors.is_empty();
+
+    writeln!(
+        out,
+        "ply\nformat binary_little_endian 1.0\ncomment Exported by scop"
+    )?;
+    writeln!(out, "element vertex {}", corners.len())?;
+    writeln!(out, "property float x\nproperty float y\nproperty float z")?;
+    if has_faces {
+        writeln!(
+            out,
+            "property float nx\nproperty float ny\nproperty float nz"
+        )?;
+        writeln!(out, "property float s\nproperty float t")?;
+    }
+    if has_colors {
+        writeln!(
+            out,
+            "property uchar red\nproperty uchar green\nproperty uchar blue"
+        )?;
+    }
+    if has_faces {
+        writeln!(out, "element face {}", faces.len())?;
+        writeln!(out, "property list uchar uint vertex_indices")?;
+    }
+    writeln!(out, "end_header")?;
+
+    for &[position, tex_coord, normal] in &corners {
+        let mut values = data.vertices[position as usize].to_vec();
+        if has_faces {
+            values.extend(data.normals[normal as usize]);
+            values.extend(data.tex_coords[tex_coord as usize]);
+        }
+        for value in values {
+            out.write_all(&value.to_le_bytes())?;
+        }
+        if has_colors {
+            let color = data.vertex_colors[position as usize];
+            out.write_all(&color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8))?;
+        }
+    }
+    for face in &faces {
+        out.write_all(&[3])?;
+        for index in face {
+            out.write_all(&index.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ParseOptions;
+    use crate::{ply, stl};
+    use std::fs;
+
+    /// Exports to a scratch directory and parses the result back.
+    fn round_trip(data: &ObjData, file: &str) -> ObjData {
+        let directory =
+            std::env::temp_dir().join(format!("scop_export_{}_{}", std::process::id(), file));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join(file);
+        let path_text = path.to_str().unwrap();
+        export(data, path_text).unwrap();
+        let parsed = match ExportFormat::from_extension(path_text).unwrap() {
+            ExportFormat::Obj => ObjData::parse(path_text, &ParseOptions::strict()),
+            ExportFormat::Stl => stl::parse(path_text, &ParseOptions::strict()),
+            ExportFormat::Ply => ply::parse(path_text, &ParseOptions::strict()),
+        };
+        fs::remove_dir_all(directory).unwrap();
+        parsed.unwrap()
+    }
+
+    fn faces(data: &ObjData) -> Vec<[[u32; 3]; 3]> {
+        data.faces
+            .iter()
+            .map(|f| [f.vertex_indices, f.tex_coord_indices, f.normal_indices])
+            .collect()
+    }
+
+    #[test]
+    fn obj_round_trip_keeps_everything() {
+        // Absolute, so the texture path survives the move
+        let source = ObjData::parse(
+            concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/materials/grouped.obj"
+            ),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        let exported = round_trip(&source, "grouped.obj");
+        assert_eq!(exported.vertices, source.vertices);
+        assert_eq!(exported.tex_coords, source.tex_coords);
+        assert_eq!(exported.normals, source.normals);
+        assert_eq!(faces(&exported), faces(&source));
+        assert_eq!(exported.parts, source.parts);
+        assert_eq!(exported.lines, source.lines);
+        assert_eq!(exported.points, source.points);
+        assert_eq!(
+            exported.materials[..source.materials.len()],
+            source.materials[..]
+        );
+        // Only ranges without a material after one with a material change
+        for (exported, source) in exported.material_ranges.iter().zip(&source.material_ranges) {
+            assert_eq!(exported.first_face, source.first_face);
+            if source.material.is_some() {
+                assert_eq!(exported.material, source.material);
+            }
+        }
+    }
+
+    #[test]
+    fn stl_and_ply_round_trips_keep_geometry() {
+        let source = ObjData::parse("models/42.obj", &ParseOptions::strict()).unwrap();
+        let triangles = |data: &ObjData| {
+            let mut triangles: Vec<[[u32; 3]; 3]> = data
+                .faces
+                .iter()
+                .map(|f| {
+                    f.vertex_indices
+                        .map(|i| data.vertices[i as usize].map(f32::to_bits))
+                })
+                .collect();
+            triangles.sort();
+            triangles
+        };
+
+        let stl = round_trip(&source, "42.stl");
+        assert_eq!(triangles(&stl), triangles(&source));
+
+        let ply = round_trip(&source, "42.ply");
+        assert_eq!(triangles(&ply), triangles(&source));
+        // Vertices were split per normal, but keep their normals
+        let normal =
+            |data: &ObjData, face: usize| data.normals[data.faces[face].normal_indices[0] as usize];
+        assert_eq!(normal(&ply, 0), normal(&source, 0));
+    }
+
+    #[test]
+    fn point_clouds_export_their_vertices() {
+        let source = ObjData::parse_reader(
+            "v 0 0 0 1 0 0\nv 1 0 0 0 1 0\np 1 2\n".as_bytes(),
+            "points.obj",
+            &ParseOptions::strict(),
+        )
+        .unwrap();
+        let mut ply = Vec::new();
+        write_ply(&source, &mut ply).unwrap();
+        let parsed = ply::parse_bytes(&ply, "points.ply", &ParseOptions::strict()).unwrap();
+        assert_eq!(parsed.vertices, source.vertices);
+        assert_eq!(parsed.vertex_colors, source.vertex_colors);
+        assert_eq!(parsed.points, [0, 1]);
+        assert!(export(&source, "points.fbx").is_err());
+    }
+}
@@ -0,0 +1,1005 @@
+use crate::json::Json;
+use crate::material::{AlphaUsage, EmbeddedImage, Material};
+use crate::matrices::{Mat3, Mat4};
+use crate::normals;
+use crate::parser::{self, FaceVertex, MeshBuilder, ObjData, ObjError, ParseOptions};
+use crate::quaternion::Quat;
+use crate::vectors::Vec3;
+use std::path::Path;
+
+const GLB_MAGIC: &[u8] = b"glTF";
+const GLB_JSON_CHUNK: u32 = 0x4E4F534A;
+const GLB_BIN_CHUNK: u32 = 0x004E4942;
+
+/// Primitive modes, which say how indices form points, lines or triangles.
+const POINTS: usize = 0;
+const LINES: usize = 1;
+const LINE_LOOP: usize = 2;
+const LINE_STRIP: usize = 3;
+const TRIANGLES: usize = 4;
+const TRIANGLE_STRIP: usize = 5;
+const TRIANGLE_FAN: usize = 6;
+
+pub fn is_glb(bytes: &[u8]) -> bool {
+    bytes.starts_with(GLB_MAGIC)
+}
+
+pub fn parse(path: &str, options: &ParseOptions) -> Result<ObjData, ObjError> {
+    let bytes = parser::read_file(path)?;
+    parse_bytes(&bytes, path, options)
+}
+
+/// Parses a `.gltf` document or `.glb` container. Every mesh reachable from
+/// the default scene becomes a part, placed by its node's transform; buffers
+/// and base color textures are resolved against the file's directory.
+pub fn parse_bytes(
+    bytes: &[u8],
+    file_name: &str,
+    options: &ParseOptions,
+) -> Result<ObjData, ObjError> {
+    let invalid = |message: String| ObjError::InvalidData {
+        file: file_name.to_string(),
+        message,
+    };
+    let (json, binary_chunk) = match is_glb(bytes) {
+        true => split_glb(bytes).map_err(|message| invalid(message.to_string()))?,
+        false => (bytes, None),
+    };
+    let text = std::str::from_utf8(json).map_err(|_| invalid("JSON is not UTF-8".to_string()))?;
+    let document = Json::parse(text).map_err(|e| invalid(e.to_string()))?;
+
+    if let Some(required) = document.get("extensionsRequired").and_then(Json::as_array) {
+        if let Some(extension) = required.first() {
+            return Err(invalid(format!(
+                "requires unsupported extension {}",
+                extension.as_str().unwrap_or("?")
+            )));
+        }
+    }
+
+    let mut builder = GltfBuilder {
+        file_name,
+        directory: Path::new(file_name).parent().unwrap_or(Path::new("")),
+        options,
+        buffers: Vec::new(),
+        mesh: MeshBuilder::default(),
+        warnings: Vec::new(),
+        document: &document,
+    };
+    builder.load_buffers(binary_chunk)?;
+    builder.load_materials()?;
+    for (node, transform) in builder.scene_nodes()? {
+        builder.add_node(node, transform)?;
+    }
+
+    let GltfBuilder { mesh, warnings, .. } = builder;
+    Ok(mesh.finish(&options.normals, warnings))
+}
+
+/// Returns the JSON chunk and, if present, the binary chunk of a `.glb`.
+fn split_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>), &'static str> {
+    let word = |offset: usize| {
+        bytes
+            .get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    };
+    if word(4) != Some(2) {
+        return Err("only version 2 .glb files are supported");
+    }
+    let length = (word(8).ok_or("truncated header")? as usize).min(bytes.len());
+
+    let mut chunks = Vec::new();
+    let mut offset = 12;
+    while offset + 8 <= length {
+        let (size, kind) = (word(offset).unwrap() as usize, word(offset + 4).unwrap());
+        let data = bytes
+            .get(offset + 8..offset + 8 + size)
+            .ok_or("truncated chunk")?;
+        chunks.push((kind, data));
+        // Chunks are padded to 4 bytes
+        offset += 8 + size.div_ceil(4) * 4;
+    }
+    match chunks.as_slice() {
+        [(GLB_JSON_CHUNK, json), rest @ ..] => Ok((
+            json,
+            rest.iter()
+                .find(|(kind, _)| *kind == GLB_BIN_CHUNK)
+                .map(|(_, data)| *data),
+        )),
+        _ => Err("first chunk is not JSON"),
+    }
+}
+
+/// The contents of a base64 `data:` URI.
+fn decode_data_uri(uri: &str) -> Option<Vec<u8>> {
+    uri.split_once(";base64,")
+        .and_then(|(_, data)| decode_base64(data))
+}
+
+/// Decodes standard base64, with or without padding.
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim_end_matches('=');
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut bits, mut bit_count) = (0u32, 0);
+    for c in text.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        bits = (bits << 6) | value as u32;
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            bytes.push((bits >> bit_count) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+/// Undoes `%XX` escapes in a URI.
+fn decode_uri(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| uri.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// The members of `key` when it's an array, or nothing.
+fn array<'j>(json: &'j Json, key: &str) -> &'j [Json] {
+    json.get(key).and_then(Json::as_array).unwrap_or(&[])
+}
+
+fn get_usize(json: &Json, key: &str) -> Option<usize> {
+    json.get(key).and_then(Json::as_usize)
+}
+
+fn get_usize_or(json: &Json, key: &str, default: usize) -> usize {
+    get_usize(json, key).unwrap_or(default)
+}
+
+/// A node's transform relative to its parent.
+fn local_transform(node: &Json) -> Mat4 {
+    if let Some(m) = node.get("matrix").and_then(Json::as_floats::<16>) {
+        // Stored column by column
+        let mut rows = [[0.0; 4]; 4];
+        for (column, values) in m.chunks(4).enumerate() {
+            for (row, value) in values.iter().enumerate() {
+                rows[row][column] = *value;
+            }
+        }
+        return Mat4::new(rows);
+    }
+    let vector = |key: &str, default: [f32; 3]| {
+        let [x, y, z] = node
+            .get(key)
+            .and_then(Json::as_floats::<3>)
+            .unwrap_or(default);
+        Vec3::new(x, y, z)
+    };
+    let rotation = node
+        .get("rotation")
+        .and_then(Json::as_floats::<4>)
+        .map_or(Quat::IDENTITY, |[x, y, z, w]| Quat::new(x, y, z, w));
+    Mat4::translation(vector("translation", [0.0; 3]))
+        * Mat4::from_quat(rotation)
+        * Mat4::scale(vector("scale", [1.0; 3]))
+}
+
+struct GltfBuilder<'a> {
+    file_name: &'a str,
+    /// Where buffer and image URIs are resolved from.
+    directory: &'a Path,
+    options: &'a ParseOptions,
+    document: &'a Json,
+    buffers: Vec<Vec<u8>>,
+    mesh: MeshBuilder,
+    warnings: Vec<ObjError>,
+}
+
+impl<'a> GltfBuilder<'a> {
+    fn invalid(&self, message: String) -> ObjError {
+        ObjError::InvalidData {
+            file: self.file_name.to_string(),
+            message,
+        }
+    }
+
+    /// Skips what can't be shown with a warning, or fails in strict mode.
+    fn report(&mut self, message: String) -> Result<(), ObjError> {
+        let error = self.invalid(message);
+        parser::report(error, self.options, &mut self.warnings)
+    }
+
+    /// The `index`-th entry of the top-level array `key`.
+    fn element(&self, key: &str, index: usize) -> Result<&'a Json, ObjError> {
+        array(self.document, key)
+            .get(index)
+            .ok_or_else(|| self.invalid(format!("{} {} doesn't exist", key, index)))
+    }
+
+    fn load_buffers(&mut self, binary_chunk: Option<&[u8]>) -> Result<(), ObjError> {
+        for (index, buffer) in array(self.document, "buffers").iter().enumerate() {
+            let data = match buffer.get("uri").and_then(Json::as_str) {
+                Some(uri) if uri.starts_with("data:") => decode_data_uri(uri)
+                    .ok_or_else(|| self.invalid(format!("buffer {} has a bad data URI", index)))?,
+                Some(uri) => {
+                    let path = self.directory.join(decode_uri(uri));
+                    parser::read_file(&path.to_string_lossy())?
+                }
+                None if index == 0 => binary_chunk
+                    .ok_or_else(|| self.invalid("buffer 0 has no data".to_string()))?
+                    .to_vec(),
+                None => return Err(self.invalid(format!("buffer {} has no data", index))),
+            };
+            let length = get_usize_or(buffer, "byteLength", 0);
+            if data.len() < length {
+                return Err(self.invalid(format!(
+                    "buffer {} holds {} of its {} bytes",
+                    index,
+                    data.len(),
+                    length
+                )));
+            }
+            self.buffers.push(data);
+        }
+        Ok(())
+    }
+
+    /// Converts every material up front so primitives can refer to them by
+    /// their glTF index.
+    fn load_materials(&mut self) -> Result<(), ObjError> {
+        for (index, json) in array(self.document, "materials").iter().enumerate() {
+            let name = json.get("name").and_then(Json::as_str);
+            let mut material = Material::new(name.unwrap_or(&format!("material {}", index)));
+            let pbr = json.get("pbrMetallicRoughness");
+            let factor = pbr.and_then(|p| p.get("baseColorFactor"));
+            // Alpha is ignored unless the material asks for it
+            material.alpha = match json.get("alphaMode").and_then(Json::as_str) {
+                Some("BLEND") => AlphaUsage::Blend,
+                Some("MASK") => {
+                    let cutoff = json.get("alphaCutoff").and_then(Json::as_f64);
+                    AlphaUsage::Mask(cutoff.map_or(0.5, |cutoff| cutoff as f32))
+                }
+                _ => AlphaUsage::Opaque,
+            };
+            if let Some([r, g, b, a]) = factor.and_then(Json::as_floats::<4>) {
+                material.diffuse = [r, g, b];
+                material.ambient = [r, g, b];
+                if material.alpha != AlphaUsage::Opaque {
+                    material.opacity = a;
+                }
+            }
+            let texture = pbr
+                .and_then(|p| p.get("baseColorTexture"))
+                .and_then(|t| get_usize(t, "index"));
+            if let Some(texture) = texture {
+                self.load_image(texture, &mut material)?;
+            }
+            self.mesh.materials.push(material);
+        }
+        Ok(())
+    }
+
+    /// Makes a texture's image the material's diffuse map: a file next to
+    /// the model, or bytes from a buffer view or `data:` URI.
+    fn load_image(&mut self, texture: usize, material: &mut Material) -> Result<(), ObjError> {
+        let source = get_usize(self.element("textures", texture)?, "source");
+        let Some(source) = source else {
+            return Ok(());
+        };
+        let image = self.element("images", source)?;
+        let bytes = match image.get("uri").and_then(Json::as_str) {
+            Some(uri) if uri.starts_with("data:") => {
+                decode_data_uri(uri).ok_or_else(|| "has a bad data URI".to_string())
+            }
+            Some(uri) => {
+                let path = self.directory.join(decode_uri(uri));
+                material.diffuse_map = Some(path.to_string_lossy().into_owned());
+                return Ok(());
+            }
+            None => match get_usize(image, "bufferView") {
+                Some(view) => self.buffer_view(view).map(<[u8]>::to_vec),
+                None => Err("has no data".to_string()),
+            },
+        };
+        match bytes {
+            Ok(bytes) => {
+                material.diffuse_image = Some(EmbeddedImage {
+                    name: format!("{} image {}", self.file_name, source),
+                    bytes: bytes.into(),
+                });
+                Ok(())
+            }
+            Err(message) => self.report(format!("image {} {}", source, message)),
+        }
+    }
+
+    /// Every node of the scene to show with its world transform, parents
+    /// first. Without scenes, all nodes that aren't children are shown.
+    fn scene_nodes(&self) -> Result<Vec<(usize, Mat4)>, ObjError> {
+        let node_count = array(self.document, "nodes").len();
+        let roots: Vec<usize> = match array(self.document, "scenes") {
+            [] => {
+                let mut is_child = vec![false; node_count];
+                for node in array(self.document, "nodes") {
+                    for child in array(node, "children").iter().filter_map(Json::as_usize) {
+                        if child < node_count {
+                            is_child[child] = true;
+                        }
+                    }
+                }
+                (0..node_count).filter(|&i| !is_child[i]).collect()
+            }
+            _ => {
+                let scene = self.element("scenes", get_usize_or(self.document, "scene", 0))?;
+                array(scene, "nodes")
+                    .iter()
+                    .filter_map(Json::as_usize)
+                    .collect()
+            }
+        };
+
+        let mut visited = vec![false; node_count];
+        let mut nodes = Vec::new();
+        let mut stack: Vec<(usize, Mat4)> = roots
+            .iter()
+            .rev()
+            .map(|&root| (root, Mat4::IDENTITY))
+            .collect();
+        while let Some((index, parent)) = stack.pop() {
+            let node = self.element("nodes", index)?;
+            if std::mem::replace(&mut visited[index], true) {
+                return Err(self.invalid(format!("node {} appears twice in the hierarchy", index)));
+            }
+            let transform = parent * local_transform(node);
+            nodes.push((index, transform));
+            for child in array(node, "children").iter().rev() {
+                let child = child
+                    .as_usize()
+                    .ok_or_else(|| self.invalid(format!("node {} has a bad child", index)))?;
+                stack.push((child, transform));
+            }
+        }
+        Ok(nodes)
+    }
+
+    fn add_node(&mut self, index: usize, transform: Mat4) -> Result<(), ObjError> {
+        let node = self.element("nodes", index)?;
+        let Some(mesh_index) = get_usize(node, "mesh") else {
+            return Ok(());
+        };
+        let mesh = self.element("meshes", mesh_index)?;
+        let name = [node, mesh]
+            .iter()
+            .find_map(|json| json.get("name").and_then(Json::as_str))
+            .map_or_else(|| format!("node {}", index), str::to_string);
+        let part = self.mesh.part(&name);
+
+        for (primitive_index, primitive) in array(mesh, "primitives").iter().enumerate() {
+            if let Err(message) = self.add_primitive(primitive, part, transform) {
+                self.report(format!(
+                    "mesh {} primitive {}: {}",
+                    mesh_index, primitive_index, message
+                ))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds one primitive; on error nothing has been added.
+    fn add_primitive(
+        &mut self,
+        primitive: &Json,
+        part: usize,
+        transform: Mat4,
+    ) -> Result<(), String> {
+        let attributes = primitive.get("attributes");
+        let attribute = |name: &str| attributes.and_then(|a| get_usize(a, name));
+        let positions =
+            self.read_accessor(attribute("POSITION").ok_or("no POSITION attribute")?, None)?;
+        if positions.iter().any(|position| position.len() != 3) {
+            return Err("POSITION doesn't have 3 components".to_string());
+        }
+        let count = positions.len();
+        let read_optional = |name: &str, min_components: usize| match attribute(name) {
+            Some(accessor) => {
+                let values = self.read_accessor(accessor, Some(count))?;
+                if values.len() != count || values.iter().any(|v| v.len() < min_components) {
+                    return Err(format!("{} doesn't match POSITION", name));
+                }
+                Ok(Some(values))
+            }
+            None => Ok(None),
+        };
+        let normals = read_optional("NORMAL", 3)?;
+        let tex_coords = read_optional("TEXCOORD_0", 2)?;
+        let colors = read_optional("COLOR_0", 3)?;
+        let indices: Vec<u32> = match get_usize(primitive, "indices") {
+            Some(accessor) => self.read_indices(accessor)?,
+            None => (0..count as u32).collect(),
+        };
+        if let Some(index) = indices.iter().find(|&&i| i as usize >= count) {
+            return Err(format!("index {} out of range ({} vertices)", index, count));
+        }
+        let material = match get_usize(primitive, "material") {
+            Some(m) if m >= self.mesh.materials.len() => {
+                return Err(format!("material {} doesn't exist", m))
+            }
+            material => material,
+        };
+        let mode = get_usize_or(primitive, "mode", TRIANGLES);
+        if mode > TRIANGLE_FAN {
+            return Err(format!("unknown mode {}", mode));
+        }
+
+        let vertex_base = self.mesh.vertices.len() as u32;
+        let normal_base = self.mesh.normals.len() as u32;
+        let tex_coord_base = self.mesh.tex_coords.len() as u32;
+        let normal_matrix = Mat3::normal_matrix(&transform);
+        for (i, position) in positions.iter().enumerate() {
+            let position =
+                transform.transform_point(Vec3::new(position[0], position[1], position[2]));
+            let color = colors.as_ref().map(|c| [c[i][0], c[i][1], c[i][2]]);
+            self.mesh.add_vertex(position.to_array(), color);
+        }
+        for normal in normals.iter().flatten() {
+            let normal = normal_matrix * Vec3::new(normal[0], normal[1], normal[2]);
+            self.mesh.normals.push(normal.normalize().to_array());
+        }
+        // glTF puts the texture origin at the top left, OBJ at the bottom left
+        for uv in tex_coords.iter().flatten() {
+            self.mesh.tex_coords.push([uv[0], 1.0 - uv[1]]);
+        }
+
+        let corner = |i: u32| FaceVertex {
+            position: vertex_base + i,
+            tex_coord: tex_coords.is_some().then_some(tex_coord_base + i),
+            normal: normals.is_some().then_some(normal_base + i),
+        };
+        let triangles: Vec<[u32; 3]> = match mode {
+            TRIANGLES => indices
+                .chunks_exact(3)
+                .map(|t| [t[0], t[1], t[2]])
+                .collect(),
+            // Every other strip triangle is flipped to keep the winding
+            TRIANGLE_STRIP => indices
+                .windows(3)
+                .enumerate()
+                .map(|(i, t)| match i % 2 {
+                    0 => [t[0], t[1], t[2]],
+                    _ => [t[1], t[0], t[2]],
+                })
+                .collect(),
+            TRIANGLE_FAN => indices
+                .windows(2)
+                .skip(1)
+                .map(|t| [indices[0], t[0], t[1]])
+                .collect(),
+            _ => Vec::new(),
+        };
+        // A mirroring transform turns faces inside out unless their winding
+        // is reversed. Without normals, glTF asks for flat shading
+        let mirrored = Mat3::from_mat4(&transform).determinant() < 0.0;
+        for [a, b, c] in triangles {
+            let triangle = if mirrored { [a, c, b] } else { [a, b, c] };
+            self.mesh
+                .add_face(&triangle.map(corner), part, material, normals::FLAT);
+        }
+
+        let offset = |i: &u32| vertex_base + i;
+        match mode {
+            POINTS => self.mesh.points.extend(indices.iter().map(offset)),
+            LINES => self.mesh.lines.extend(
+                indices
+                    .chunks_exact(2)
+                    .map(|l| [offset(&l[0]), offset(&l[1])]),
+            ),
+            LINE_LOOP | LINE_STRIP => {
+                let mut strip: Vec<u32> = indices.iter().map(offset).collect();
+                if mode == LINE_LOOP && strip.len() > 2 {
+                    strip.push(strip[0]);
+                }
+                self.mesh
+                    .lines
+                    .extend(strip.windows(2).map(|l| [l[0], l[1]]));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// The bytes of a buffer view.
+    fn buffer_view(&self, index: usize) -> Result<&[u8], String> {
+        let view = array(self.document, "bufferViews")
+            .get(index)
+            .ok_or_else(|| format!("buffer view {} doesn't exist", index))?;
+        let buffer = get_usize(view, "buffer")
+            .and_then(|b| self.buffers.get(b))
+            .ok_or_else(|| format!("buffer view {} has no buffer", index))?;
+        let start = get_usize_or(view, "byteOffset", 0);
+        let end = start + get_usize_or(view, "byteLength", 0);
+        buffer
+            .get(start..end)
+            .ok_or_else(|| format!("buffer view {} is out of range", index))
+    }
+
+    /// Reads every element of an accessor as floats, applying normalization.
+    /// `vertex_count` is the number of elements of a vertex attribute.
+    fn read_accessor(
+        &self,
+        index: usize,
+        vertex_count: Option<usize>,
+    ) -> Result<Vec<Vec<f32>>, String> {
+        let accessor = self.accessor(index, vertex_count)?;
+        Ok((0..accessor.count)
+            .map(|i| {
+                (0..accessor.components)
+                    .map(|c| accessor.float(i, c))
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// Reads an accessor of indices. They stay integers, since floats can't
+    /// hold every 32-bit index.
+    fn read_indices(&self, index: usize) -> Result<Vec<u32>, String> {
+        let accessor = self.accessor(index, None)?;
+        if accessor.components != 1 || !matches!(accessor.component_type, 5121 | 5123 | 5125) {
+            return Err(format!(
+                "accessor {}: indices must be unsigned integer scalars",
+                index
+            ));
+        }
+        Ok((0..accessor.count).map(|i| accessor.integer(i)).collect())
+    }
+
+    /// Checks an accessor's layout against its buffer view. Accessors without
+    /// one are all zeros; their count is only trusted when it matches
+    /// `vertex_count`, so a file can't ask for an arbitrarily large allocation.
+    fn accessor(&self, index: usize, vertex_count: Option<usize>) -> Result<Accessor<'_>, String> {
+        let accessor = array(self.document, "accessors")
+            .get(index)
+            .ok_or_else(|| format!("accessor {} doesn't exist", index))?;
+        let error = |message: &str| format!("accessor {}: {}", index, message);
+        if accessor.get("sparse").is_some() {
+            return Err(error("sparse accessors aren't supported"));
+        }
+        let count = get_usize(accessor, "count").ok_or_else(|| error("no count"))?;
+        let components = match accessor.get("type").and_then(Json::as_str) {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") => 4,
+            _ => return Err(error("unsupported type")),
+        };
+        let component_type = get_usize(accessor, "componentType").unwrap_or(0);
+        let size =
+            component_size(component_type).ok_or_else(|| error("unsupported component type"))?;
+        let mut layout = Accessor {
+            bytes: None,
+            start: get_usize_or(accessor, "byteOffset", 0),
+            stride: size * components,
+            count,
+            components,
+            component_type,
+            normalized: accessor.get("normalized") == Some(&Json::Bool(true)),
+        };
+
+        let Some(view_index) = get_usize(accessor, "bufferView") else {
+            return match vertex_count {
+                Some(vertex_count) if vertex_count == count => Ok(layout),
+                Some(_) => Err(error("doesn't match POSITION")),
+                None => Err(error("has no buffer view")),
+            };
+        };
+        let view_bytes = self.buffer_view(view_index).map_err(|e| error(&e))?;
+        let view = &array(self.document, "bufferViews")[view_index];
+        let element_size = size * components;
+        layout.stride = get_usize_or(view, "byteStride", element_size);
+        if layout.stride < element_size {
+            return Err(error("elements overlap"));
+        }
+        if count > 0 && layout.start + (count - 1) * layout.stride + element_size > view_bytes.len()
+        {
+            return Err(error("reads past its buffer view"));
+        }
+        layout.bytes = Some(view_bytes);
+        Ok(layout)
+    }
+}
+
+/// Bytes per component of an accessor's `componentType`.
+fn component_size(component_type: usize) -> Option<usize> {
+    match component_type {
+        5120 | 5121 => Some(1),
+        5122 | 5123 => Some(2),
+        5125 | 5126 => Some(4),
+        _ => None,
+    }
+}
+
+/// Where an accessor's elements are in their buffer view, and how they are
+/// stored.
+struct Accessor<'b> {
+    /// The buffer view, or `None` when every element is zero.
+    bytes: Option<&'b [u8]>,
+    start: usize,
+    stride: usize,
+    count: usize,
+    components: usize,
+    component_type: usize,
+    normalized: bool,
+}
+
+impl Accessor<'_> {
+    /// The bytes of one component, or `None` for an all-zero accessor.
+    fn component(&self, element: usize, component: usize) -> Option<&[u8]> {
+        let size = component_size(self.component_type)?;
+        let offset = self.start + element * self.stride + component * size;
+        self.bytes.map(|bytes| &bytes[offset..offset + size])
+    }
+
+    fn integer(&self, element: usize) -> u32 {
+        match self.component(element, 0) {
+            Some(&[b]) => b as u32,
+            Some(&[b0, b1]) => u16::from_le_bytes([b0, b1]) as u32,
+            Some(&[b0, b1, b2, b3]) => u32::from_le_bytes([b0, b1, b2, b3]),
+            _ => 0,
+        }
+    }
+
+    fn float(&self, element: usize, component: usize) -> f32 {
+        let Some(b) = self.component(element, component) else {
+            return 0.0;
+        };
+        let value = match self.component_type {
+            5120 => b[0] as i8 as f32,
+            5121 => b[0] as f32,
+            5122 => i16::from_le_bytes([b[0], b[1]]) as f32,
+            5123 => u16::from_le_bytes([b[0], b[1]]) as f32,
+            5125 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32,
+            _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        };
+        match (self.normalized, self.component_type) {
+            (true, 5120) => (value / 127.0).max(-1.0),
+            (true, 5121) => value / 255.0,
+            (true, 5122) => (value / 32767.0).max(-1.0),
+            (true, 5123) => value / 65535.0,
+            _ => value,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vectors::tests::assert_vec3_eq;
+
+    /// A unit quad in the xy plane, as 4 float positions, 4 float uvs and 6
+    /// ubyte indices.
+    fn quad_buffer() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for value in [
+            0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0,
+        ] {
+            bytes.extend(value.to_le_bytes());
+        }
+        for value in [0.0f32, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend([0, 1, 2, 0, 2, 3]);
+        bytes
+    }
+
+    fn encode_base64(bytes: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut text = String::new();
+        for chunk in bytes.chunks(3) {
+            let bits = chunk
+                .iter()
+                .enumerate()
+                .fold(0u32, |bits, (i, &b)| bits | (b as u32) << (16 - 8 * i));
+            for i in 0..=chunk.len() {
+                text.push(ALPHABET[(bits >> (18 - 6 * i) & 63) as usize] as char);
+            }
+        }
+        text
+    }
+
+    /// Two nodes sharing the quad mesh, the second moved and scaled by its
+    /// parent. `buffer` is the buffer object's JSON.
+    fn document(buffer: &str) -> String {
+        format!(
+            r#"{{
+  "asset": {{"version": "2.0"}},
+  "scene": 0,
+  "scenes": [{{"nodes": [0, 1]}}],
+  "nodes": [
+    {{"name": "left", "mesh": 0}},
+    {{"children": [2], "translation": [5, 0, 0]}},
+    {{"name": "right", "mesh": 0, "scale": [2, 2, 2]}}
+  ],
+  "meshes": [{{"primitives": [{{
+    "attributes": {{"POSITION": 0, "TEXCOORD_0": 1}}, "indices": 2, "material": 0
+  }}]}}],
+  "materials": [{{"name": "paint", "pbrMetallicRoughness": {{
+    "baseColorFactor": [1, 0.5, 0, 0.75], "baseColorTexture": {{"index": 0}}
+  }}}}],
+  "textures": [{{"source": 0}}],
+  "images": [{{"uri": "paint%20chips.bmp"}}],
+  "accessors": [
+    {{"bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3"}},
+    {{"bufferView": 0, "byteOffset": 48, "componentType": 5126, "count": 4, "type": "VEC2"}},
+    {{"bufferView": 1, "componentType": 5121, "count": 6, "type": "SCALAR"}}
+  ],
+  "bufferViews": [
+    {{"buffer": 0, "byteLength": 80}},
+    {{"buffer": 0, "byteOffset": 80, "byteLength": 6}}
+  ],
+  "buffers": [{}]
+}}"#,
+            buffer
+        )
+    }
+
+    fn check_quads(obj: &ObjData) {
+        assert_eq!(obj.vertices.len(), 8);
+        assert_eq!(obj.faces.len(), 4);
+        let names: Vec<&str> = obj.parts.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["left", "right"]);
+        // Translated by the parent, scaled by the child
+        assert_vec3_eq(Vec3::from(obj.vertices[6]), Vec3::new(7.0, 2.0, 0.0));
+        assert_eq!(obj.tex_coords[0], [0.0, 0.0]);
+        assert_eq!(obj.tex_coords[3], [0.0, 1.0]);
+
+        let material = &obj.materials[0];
+        assert_eq!(material.diffuse, [1.0, 0.5, 0.0]);
+        assert_eq!(material.opacity, 1.0);
+        assert_eq!(material.alpha, AlphaUsage::Opaque);
+        assert_eq!(
+            material.diffuse_map.as_deref(),
+            Some(
+                Path::new("assets")
+                    .join("paint chips.bmp")
+                    .to_str()
+                    .unwrap()
+            )
+        );
+        assert!(obj.material_ranges.iter().all(|r| r.material == Some(0)));
+    }
+
+    #[test]
+    fn gltf_with_embedded_buffer() {
+        let buffer = quad_buffer();
+        let json = document(&format!(
+            r#"{{"byteLength": {}, "uri": "data:application/octet-stream;base64,{}"}}"#,
+            buffer.len(),
+            encode_base64(&buffer)
+        ));
+        let obj = parse_bytes(
+            json.as_bytes(),
+            "assets/quads.gltf",
+            &ParseOptions::strict(),
+        )
+        .unwrap();
+        check_quads(&obj);
+    }
+
+    #[test]
+    fn glb_with_binary_chunk() {
+        let mut json = document(r#"{"byteLength": 86}"#).into_bytes();
+        json.resize(json.len().div_ceil(4) * 4, b' ');
+        let mut buffer = quad_buffer();
+        buffer.resize(88, 0);
+
+        let mut glb = GLB_MAGIC.to_vec();
+        glb.extend(2u32.to_le_bytes());
+        glb.extend((12 + 8 + json.len() as u32 + 8 + 88).to_le_bytes());
+        for (kind, data) in [(GLB_JSON_CHUNK, &json), (GLB_BIN_CHUNK, &buffer)] {
+            glb.extend((data.len() as u32).to_le_bytes());
+            glb.extend(kind.to_le_bytes());
+            glb.extend(data);
+        }
+        let obj = parse_bytes(&glb, "assets/quads.glb", &ParseOptions::strict()).unwrap();
+        check_quads(&obj);
+        // Flat shading, since the file has no normals
+        assert_eq!(obj.normals, [[0.0, 0.0, 1.0]]);
+    }
+
+    #[test]
+    fn mirrored_nodes_keep_their_winding() {
+        let buffer = format!(
+            r#"{{"byteLength": 86, "uri": "data:;base64,{}"}}"#,
+            encode_base64(&quad_buffer())
+        );
+        let json = document(&buffer).replace(
+            r#"{"name": "left", "mesh": 0}"#,
+            r#"{"name": "left", "mesh": 0, "scale": [-1, 1, 1]}"#,
+        );
+        let obj = parse_bytes(json.as_bytes(), "mirror.gltf", &ParseOptions::strict()).unwrap();
+        assert_eq!(obj.vertices[1], [-1.0, 0.0, 0.0]);
+        assert_eq!(obj.faces[0].vertex_indices, [0, 2, 1]);
+        assert_eq!(obj.faces[2].vertex_indices, [4, 5, 6]);
+        // Both copies still face +z
+        assert_eq!(obj.normals, [[0.0, 0.0, 1.0]]);
+    }
+
+    #[test]
+    fn broken_primitives_are_reported() {
+        let buffer = quad_buffer();
+        let json = document(&format!(
+            r#"{{"byteLength": {}, "uri": "data:;base64,{}"}}"#,
+            buffer.len(),
+            encode_base64(&buffer[..70])
+        ));
+        assert!(matches!(
+            parse_bytes(json.as_bytes(), "short.gltf", &ParseOptions::strict()),
+            Err(ObjError::InvalidData { .. })
+        ));
+
+        let json = document(r#"{"byteLength": 86, "uri": "data:;base64,"}"#);
+        assert!(parse_bytes(json.as_bytes(), "empty.gltf", &ParseOptions::default()).is_err());
+
+        let buffer = encode_base64(&quad_buffer());
+        let json = document(&format!(
+            r#"{{"byteLength": 86, "uri": "data:;base64,{}"}}"#,
+            buffer
+        ))
+        .replace(r#""indices": 2"#, r#""indices": 2, "mode": 9"#);
+        let obj = parse_bytes(json.as_bytes(), "mode.gltf", &ParseOptions::default()).unwrap();
+        assert!(obj.faces.is_empty());
+        assert_eq!(obj.warnings.len(), 2);
+
+        let json = document(&format!(
+            r#"{{"byteLength": 86, "uri": "data:;base64,{}"}}"#,
+            buffer
+        ))
+        .replacen(
+            r#""count": 4, "type": "VEC3""#,
+            r#""count": 4, "type": "VEC2""#,
+            1,
+        );
+        assert!(matches!(
+            parse_bytes(json.as_bytes(), "flat.gltf", &ParseOptions::strict()),
+            Err(ObjError::InvalidData { message, .. }) if message.contains("POSITION")
+        ));
+    }
+
+    #[test]
+    fn alpha_follows_the_alpha_mode() {
+        let buffer = format!(
+            r#"{{"byteLength": 86, "uri": "data:;base64,{}"}}"#,
+            encode_base64(&quad_buffer())
+        );
+        let material = |alpha_mode: &str| {
+            let json = document(&buffer).replace(
+                r#"{"name": "paint","#,
+                &format!(r#"{{"name": "paint", {}"#, alpha_mode),
+            );
+            let obj = parse_bytes(json.as_bytes(), "alpha.gltf", &ParseOptions::strict());
+            obj.unwrap().materials.remove(0)
+        };
+        let blend = material(r#""alphaMode": "BLEND","#);
+        assert_eq!((blend.alpha, blend.opacity), (AlphaUsage::Blend, 0.75));
+        let mask = material(r#""alphaMode": "MASK", "alphaCutoff": 0.25,"#);
+        assert_eq!((mask.alpha, mask.opacity), (AlphaUsage::Mask(0.25), 0.75));
+        let mask = material(r#""alphaMode": "MASK","#);
+        assert_eq!(mask.alpha, AlphaUsage::Mask(0.5));
+        let opaque = material(r#""alphaMode": "OPAQUE","#);
+        assert_eq!((opaque.alpha, opaque.opacity), (AlphaUsage::Opaque, 1.0));
+    }
+
+    #[test]
+    fn embedded_images_are_kept() {
+        let buffer = format!(
+            r#"{{"byteLength": 86, "uri": "data:;base64,{}"}}"#,
+            encode_base64(&quad_buffer())
+        );
+        let external = r#"{"uri": "paint%20chips.bmp"}"#;
+        let json = document(&buffer).replace(external, r#"{"bufferView": 1}"#);
+        let obj = parse_bytes(json.as_bytes(), "view.gltf", &ParseOptions::strict()).unwrap();
+        let image = obj.materials[0].diffuse_image.as_ref().unwrap();
+        assert_eq!(image.name, "view.gltf image 0");
+        assert_eq!(&image.bytes[..], [0, 1, 2, 0, 2, 3]);
+        assert_eq!(obj.materials[0].diffuse_map, None);
+
+        let image = r#"{"uri": "data:image/png;base64,aGVsbG8="}"#;
+        let json = document(&buffer).replace(external, image);
+        let obj = parse_bytes(json.as_bytes(), "uri.gltf", &ParseOptions::strict()).unwrap();
+        let image = obj.materials[0].diffuse_image.as_ref().unwrap();
+        assert_eq!(&image.bytes[..], b"hello");
+
+        let json = document(&buffer).replace(external, r#"{"bufferView": 7}"#);
+        let obj = parse_bytes(json.as_bytes(), "bad.gltf", &ParseOptions::default()).unwrap();
+        assert_eq!(obj.materials[0].diffuse_image, None);
+        assert_eq!(obj.warnings.len(), 1);
+    }
+
+    /// Reads accessors of `document` from a single buffer.
+    fn read_with<T>(document: &str, buffer: Vec<u8>, read: impl FnOnce(&GltfBuilder) -> T) -> T {
+        let document = Json::parse(document).unwrap();
+        let builder = GltfBuilder {
+            file_name: "accessors.gltf",
+            directory: Path::new(""),
+            options: &ParseOptions::strict(),
+            document: &document,
+            buffers: vec![buffer],
+            mesh: MeshBuilder::default(),
+            warnings: Vec::new(),
+        };
+        read(&builder)
+    }
+
+    #[test]
+    fn indices_are_read_as_integers() {
+        let document = r#"{
+  "accessors": [
+    {"bufferView": 0, "componentType": 5125, "count": 2, "type": "SCALAR"},
+    {"bufferView": 0, "componentType": 5126, "count": 2, "type": "SCALAR"}
+  ],
+  "bufferViews": [{"buffer": 0, "byteLength": 8}]
+}"#;
+        // 2^24 + 1 is the first integer a float can't hold
+        let mut buffer = 16_777_217u32.to_le_bytes().to_vec();
+        buffer.extend(7u32.to_le_bytes());
+        read_with(document, buffer, |builder| {
+            assert_eq!(builder.read_indices(0), Ok(vec![16_777_217, 7]));
+            assert!(builder.read_indices(1).is_err());
+        });
+    }
+
+    #[test]
+    fn accessor_counts_are_bounded() {
+        let document = r#"{
+  "accessors": [
+    {"componentType": 5126, "count": 4294967295, "type": "VEC3"},
+    {"componentType": 5126, "count": 2, "type": "VEC3"},
+    {"bufferView": 0, "componentType": 5126, "count": 4294967295, "type": "VEC3"},
+    {"bufferView": 1, "componentType": 5126, "count": 4294967295, "type": "VEC3"}
+  ],
+  "bufferViews": [
+    {"buffer": 0, "byteLength": 12},
+    {"buffer": 0, "byteLength": 12, "byteStride": 0}
+  ]
+}"#;
+        read_with(document, vec![0; 12], |builder| {
+            // Zero-filled accessors must match the vertex count
+            assert!(builder.read_accessor(0, None).is_err());
+            assert!(builder.read_accessor(0, Some(2)).is_err());
+            assert_eq!(builder.read_accessor(1, Some(2)), Ok(vec![vec![0.0; 3]; 2]));
+            assert!(builder.read_indices(0).is_err());
+            // Elements must fit in the buffer view, without overlapping
+            assert!(builder.read_accessor(2, None).is_err());
+            assert!(builder.read_accessor(3, None).is_err());
+        });
+    }
+
+    #[test]
+    fn base64_and_uris_are_decoded() {
+        assert_eq!(decode_base64("aGVsbG8="), Some(b"hello".to_vec()));
+        assert_eq!(decode_base64("aGVsbG8"), Some(b"hello".to_vec()));
+        assert_eq!(decode_base64("a$"), None);
+        assert_eq!(encode_base64(b"hello"), "aGVsbG8");
+        assert_eq!(decode_uri("a%20b%zz"), "a b%zz");
+    }
+}
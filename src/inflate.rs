@@ -0,0 +1,353 @@
+/// Base lengths and extra bits of length symbols 257..=285.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// The order code length code lengths are stored in.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+const MAX_BITS: usize = 15;
+
+/// Reads bits least significant first, as deflate packs them.
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, count: u32) -> Result<u32, String> {
+        while self.count < count {
+            let byte = *self
+                .bytes
+                .get(self.offset)
+                .ok_or_else(|| "compressed data is truncated".to_string())?;
+            self.offset += 1;
+            self.buffer |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1u64 << count) - 1) as u32;
+        self.buffer >>= count;
+        self.count -= count;
+        Ok(value)
+    }
+
+    /// Drops the bits left in the current byte.
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+/// A canonical Huffman code, stored as the number of codes of each length
+/// and the symbols sorted by code.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, String> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        // Incomplete codes are allowed, over-subscribed ones aren't
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return Err("invalid Huffman code lengths".to_string());
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for length in 1..=MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; offsets[MAX_BITS + 1] as usize];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= reader.bits(1)? as i32;
+            let count = count as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid Huffman code".to_string())
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    let literals = Huffman::new(&lengths).expect("the fixed code is valid");
+    let distances = Huffman::new(&[5; 30]).expect("the fixed code is valid");
+    (literals, distances)
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return Err("too many Huffman codes".to_string());
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for &symbol in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[symbol] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut at = 0;
+    while at < lengths.len() {
+        let symbol = code_length_code.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *at
+                    .checked_sub(1)
+                    .and_then(|previous| lengths.get(previous))
+                    .ok_or_else(|| "code length repeat with no previous length".to_string())?;
+                (previous, 3 + reader.bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize),
+        };
+        if at + repeat > lengths.len() {
+            return Err("too many code lengths".to_string());
+        }
+        lengths[at..at + repeat].fill(value);
+        at += repeat;
+    }
+    if lengths[256] == 0 {
+        return Err("missing end-of-block code".to_string());
+    }
+    Ok((
+        Huffman::new(&lengths[..literal_count])?,
+        Huffman::new(&lengths[literal_count..])?,
+    ))
+}
+
+fn inflate_block(
+    reader: &mut BitReader,
+    literals: &Huffman,
+    distances: &Huffman,
+    output: &mut Vec<u8>,
+    limit: usize,
+) -> Result<(), String> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+        if symbol < 256 {
+            output.push(symbol as u8);
+        } else if symbol == 256 {
+            return Ok(());
+        } else {
+            let index = symbol - 257;
+            if index >= LENGTH_BASE.len() {
+                return Err("invalid length code".to_string());
+            }
+            let length =
+                LENGTH_BASE[index] as usize + reader.bits(LENGTH_EXTRA[index] as u32)? as usize;
+            let index = distances.decode(reader)? as usize;
+            if index >= DISTANCE_BASE.len() {
+                return Err("invalid distance code".to_string());
+            }
+            let distance =
+                DISTANCE_BASE[index] as usize + reader.bits(DISTANCE_EXTRA[index] as u32)? as usize;
+            if distance > output.len() {
+                return Err("distance is before the start of the data".to_string());
+            }
+            // The copy may overlap what it produces
+            let start = output.len() - distance;
+            for i in 0..length {
+                output.push(output[start + i]);
+            }
+        }
+        if output.len() > limit {
+            return Err("decompressed data is larger than expected".to_string());
+        }
+    }
+}
+
+/// Decompresses a raw deflate stream into at most `limit` bytes, returning
+/// the data and the number of input bytes used.
+pub fn inflate(bytes: &[u8], limit: usize) -> Result<(Vec<u8>, usize), String> {
+    let mut reader = BitReader {
+        bytes,
+        offset: 0,
+        buffer: 0,
+        count: 0,
+    };
+    let mut output = Vec::new();
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let header = bytes
+                    .get(reader.offset..reader.offset + 4)
+                    .ok_or_else(|| "compressed data is truncated".to_string())?;
+                let length = u16::from_le_bytes([header[0], header[1]]) as usize;
+                if length as u16 != !u16::from_le_bytes([header[2], header[3]]) {
+                    return Err("stored block length check failed".to_string());
+                }
+                let start = reader.offset + 4;
+                let data = bytes
+                    .get(start..start + length)
+                    .ok_or_else(|| "compressed data is truncated".to_string())?;
+                if output.len() + length > limit {
+                    return Err("decompressed data is larger than expected".to_string());
+                }
+                output.extend_from_slice(data);
+                reader.offset = start + length;
+            }
+            1 => {
+                let (literals, distances) = fixed_codes();
+                inflate_block(&mut reader, &literals, &distances, &mut output, limit)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &literals, &distances, &mut output, limit)?;
+            }
+            _ => return Err("invalid deflate block type".to_string()),
+        }
+        if last {
+            return Ok((output, reader.offset));
+        }
+    }
+}
+
+pub fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 bytes is the most that can be summed before `b` could overflow
+    for chunk in bytes.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+/// Decompresses a zlib stream (a deflate stream with a header and an
+/// Adler-32 checksum) into at most `limit` bytes.
+pub fn zlib_decompress(bytes: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    let (&method, &flags) = bytes
+        .first()
+        .zip(bytes.get(1))
+        .ok_or_else(|| "zlib header is truncated".to_string())?;
+    if method & 0x0f != 8
+        || method >> 4 > 7
+        || !(method as u16 * 256 + flags as u16).is_multiple_of(31)
+    {
+        return Err("invalid zlib header".to_string());
+    }
+    if flags & 0x20 != 0 {
+        return Err("zlib preset dictionaries are not supported".to_string());
+    }
+    let (output, used) = inflate(&bytes[2..], limit)?;
+    let checksum = bytes
+        .get(2 + used..2 + used + 4)
+        .map(|c| u32::from_be_bytes([c[0], c[1], c[2], c[3]]))
+        .ok_or_else(|| "zlib checksum is missing".to_string())?;
+    if checksum != adler32(&output) {
+        return Err("zlib checksum mismatch".to_string());
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(text: &str) -> Vec<u8> {
+        (0..text.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn stored_fixed_and_dynamic_blocks_are_inflated() {
+        // Two stored blocks: "abc" then "de"
+        let mut stored = vec![0x78, 0x01, 0x00, 3, 0, 0xfc, 0xff];
+        stored.extend(b"abc");
+        stored.extend([0x01, 2, 0, 0xfd, 0xff]);
+        stored.extend(b"de");
+        stored.extend(adler32(b"abcde").to_be_bytes());
+        assert_eq!(zlib_decompress(&stored, 100).unwrap(), b"abcde");
+
+        let fixed = hex("78dacb48cdc9c957c8402701680308b1");
+        assert_eq!(
+            zlib_decompress(&fixed, 100).unwrap(),
+            b"hello hello hello hello"
+        );
+
+        let dynamic = hex(concat!(
+            "78da65d0891184200c05d096cca5abbf1adcfe7b5848020b3ae3814c4c3e6fbb0ae82a77b9c1f5f58d",
+            "0be26b686cf903769552b0d72d1c598a8f2ffa8d33f6415bad0545e3f6edbd4132ca4173effccb5a98",
+            "3dd2d031c5a198033ae740bc79226e63c03d3e58d6509c93c0ed04e0bda7621f01fe1f027cbe52499d",
+            "024923599052491626092771281952f2a0926ea56ea5c34ac34a272b7d5b69b3d2b4d2d94ad34a172b",
+            "0b2b732b1b56f6b0b26e656e65c3cac2ca262b7b5bfd00d041bb21",
+        ));
+        let expected: String = (0..60)
+            .map(|i| format!("{}:{};", i, "abcab"[..i % 5 + 1].repeat(i % 3 + 1)))
+            .collect();
+        assert_eq!(
+            zlib_decompress(&dynamic, 1000).unwrap(),
+            expected.as_bytes()
+        );
+    }
+
+    #[test]
+    fn corrupt_streams_are_rejected() {
+        let fixed = hex("78dacb48cdc9c957c8402701680308b1");
+        // Output limit, checksum, header check, truncation
+        assert!(zlib_decompress(&fixed, 10).is_err());
+        let mut corrupt = fixed.clone();
+        *corrupt.last_mut().unwrap() ^= 1;
+        assert!(zlib_decompress(&corrupt, 100).is_err());
+        assert!(zlib_decompress(&[0x78, 0xdb, 0x03, 0x00], 100).is_err());
+        assert!(zlib_decompress(&fixed[..8], 100).is_err());
+        // Stored block whose length check doesn't match, reserved block type
+        assert!(inflate(&[0x01, 3, 0, 0, 0, b'a', b'b', b'c'], 100).is_err());
+        assert!(inflate(&[0x07], 100).is_err());
+        // A back-reference before the start of the data
+        assert!(inflate(&[0x03, 0x02, 0x00], 100).is_err());
+    }
+}
@@ -0,0 +1,318 @@
+use std::fmt;
+
+/// A parsed JSON value. Objects keep their keys in file order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+/// Where and why a document isn't valid JSON.
+#[derive(Debug, PartialEq)]
+pub struct JsonError {
+    /// Byte offset into the text.
+    pub offset: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid JSON at byte {}: {}", self.offset, self.message)
+    }
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Result<Json, JsonError> {
+        let mut parser = Parser {
+            bytes: text.as_bytes(),
+            offset: 0,
+        };
+        let value = parser.value(0)?;
+        parser.skip_whitespace();
+        if parser.offset != parser.bytes.len() {
+            return Err(parser.error("unexpected data after the document"));
+        }
+        Ok(value)
+    }
+
+    /// The member called `key`, if this is an object that has one.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The value as an index or count: a non-negative whole number.
+    pub fn as_usize(&self) -> Option<usize> {
+        self.as_f64()
+            .filter(|v| *v >= 0.0 && v.fract() == 0.0 && *v <= u32::MAX as f64)
+            .map(|v| v as usize)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// The value as an array of exactly `N` numbers.
+    pub fn as_floats<const N: usize>(&self) -> Option<[f32; N]> {
+        let values = self.as_array().filter(|values| values.len() == N)?;
+        let mut result = [0.0; N];
+        for (result, value) in result.iter_mut().zip(values) {
+            *result = value.as_f64()? as f32;
+        }
+        Some(result)
+    }
+}
+
+/// Deeper documents are rejected rather than risking a stack overflow.
+const MAX_DEPTH: usize = 128;
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &'static str) -> JsonError {
+        JsonError {
+            offset: self.offset,
+            message,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.bytes.get(self.offset) {
+            self.offset += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.offset).copied()
+    }
+
+    fn expect(&mut self, literal: &str, message: &'static str) -> Result<(), JsonError> {
+        if !self.bytes[self.offset..].starts_with(literal.as_bytes()) {
+            return Err(self.error(message));
+        }
+        self.offset += literal.len();
+        Ok(())
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Json, JsonError> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nested too deeply"));
+        }
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.object(depth),
+            Some(b'[') => self.array(depth),
+            Some(b'"') => Ok(Json::String(self.string()?)),
+            Some(b't') => self
+                .expect("true", "expected 'true'")
+                .map(|_| Json::Bool(true)),
+            Some(b'f') => self
+                .expect("false", "expected 'false'")
+                .map(|_| Json::Bool(false)),
+            Some(b'n') => self.expect("null", "expected 'null'").map(|_| Json::Null),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("expected a value")),
+            None => Err(self.error("unexpected end of document")),
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<Json, JsonError> {
+        self.offset += 1;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.offset += 1;
+            return Ok(Json::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expected a member name"));
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(":", "expected ':'")?;
+            members.push((key, self.value(depth + 1)?));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.offset += 1,
+                Some(b'}') => {
+                    self.offset += 1;
+                    return Ok(Json::Object(members));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Result<Json, JsonError> {
+        self.offset += 1;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.offset += 1;
+            return Ok(Json::Array(values));
+        }
+        loop {
+            values.push(self.value(depth + 1)?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.offset += 1,
+                Some(b']') => {
+                    self.offset += 1;
+                    return Ok(Json::Array(values));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, JsonError> {
+        let start = self.offset;
+        while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') = self.peek() {
+            self.offset += 1;
+        }
+        // Only ASCII was consumed, so this can't split a character
+        std::str::from_utf8(&self.bytes[start..self.offset])
+            .ok()
+            .and_then(|text| text.parse().ok())
+            .map(Json::Number)
+            .ok_or(JsonError {
+                offset: start,
+                message: "malformed number",
+            })
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.offset += 1;
+        let mut result = Vec::new();
+        loop {
+            let Some(byte) = self.peek() else {
+                return Err(self.error("unterminated string"));
+            };
+            self.offset += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escaped = self
+                        .peek()
+                        .ok_or_else(|| self.error("unterminated string"))?;
+                    self.offset += 1;
+                    let character = match escaped {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.unicode_escape()?,
+                        _ => return Err(self.error("unknown escape")),
+                    };
+                    let mut buffer = [0; 4];
+                    result.extend(character.encode_utf8(&mut buffer).as_bytes());
+                }
+                0..=0x1f => return Err(self.error("control character in string")),
+                _ => result.push(byte),
+            }
+        }
+        String::from_utf8(result).map_err(|_| self.error("string is not UTF-8"))
+    }
+
+    /// Reads the digits of a `\u` escape, combining surrogate pairs.
+    fn unicode_escape(&mut self) -> Result<char, JsonError> {
+        let high = self.hex4()?;
+        let code = if (0xd800..0xdc00).contains(&high) {
+            self.expect("\\u", "unpaired surrogate")?;
+            let low = self.hex4()?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err(self.error("unpaired surrogate"));
+            }
+            0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| self.error("invalid character escape"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, JsonError> {
+        let digits = self
+            .bytes
+            .get(self.offset..self.offset + 4)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| self.error("expected 4 hex digits"))?;
+        self.offset += 4;
+        Ok(digits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn documents_are_parsed() {
+        let json = Json::parse(
+            r#" {"name": "box\u00e9\n", "values": [1, -2.5e1, true, null],
+                "nested": {"empty": [], "none": {}}, "emoji": "\ud83d\ude00"} "#,
+        )
+        .unwrap();
+        assert_eq!(json.get("name").and_then(Json::as_str), Some("boxé\n"));
+        let values = json.get("values").and_then(Json::as_array).unwrap();
+        assert_eq!(values[1], Json::Number(-25.0));
+        assert_eq!(values[2], Json::Bool(true));
+        assert_eq!(values[0].as_usize(), Some(1));
+        assert_eq!(values[1].as_usize(), None);
+        assert_eq!(
+            json.get("nested").and_then(|n| n.get("empty")),
+            Some(&Json::Array(Vec::new()))
+        );
+        assert_eq!(json.get("emoji").and_then(Json::as_str), Some("😀"));
+        assert_eq!(
+            Json::parse("[1, 2, 3]").unwrap().as_floats(),
+            Some([1.0, 2.0, 3.0])
+        );
+    }
+
+    #[test]
+    fn invalid_documents_are_rejected() {
+        for (text, offset) in [
+            ("{\"a\" 1}", 5),
+            ("[1, 2", 5),
+            ("[1] x", 4),
+            ("\"\\ud800\"", 7),
+            ("{\"a\": -}", 6),
+        ] {
+            assert_eq!(Json::parse(text).unwrap_err().offset, offset, "{}", text);
+        }
+        assert!(Json::parse(&"[".repeat(1000)).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use crate::vectors::Vec3;
+
+/// Must match `MAX_LIGHTS` in `shaders/fragment.glsl`.
+pub const MAX_LIGHTS: usize = 4;
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum LightKind {
+    /// Infinitely far away; `position` is the direction pointing towards the light.
+    Directional,
+    Point,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Light {
+    pub kind: LightKind,
+    pub position: Vec3,
+    pub color: Vec3,
+    /// Quadratic falloff factor for point lights, ignored for directional ones.
+    pub attenuation: f32,
+}
+
+impl Light {
+    pub fn directional(direction: Vec3, color: Vec3) -> Self {
+        Light {
+            kind: LightKind::Directional,
+            position: direction,
+            color,
+            attenuation: 0.0,
+        }
+    }
+
+    pub fn point(position: Vec3, color: Vec3) -> Self {
+        Light {
+            kind: LightKind::Point,
+            position,
+            color,
+            attenuation: 0.02,
+        }
+    }
+
+    /// Key light from the upper right plus a warm point light near the model.
+    pub fn default_rig(model_center: Vec3) -> Vec<Light> {
+        vec![
+            Light::directional(Vec3::new(1.0, 1.0, 1.0), Vec3::splat(0.8)),
+            Light::point(
+                model_center + Vec3::new(-3.0, 2.0, 3.0),
+                Vec3::new(1.0, 0.85, 0.6),
+            ),
+        ]
+    }
+
+    /// Parses `point:x,y,z[:r,g,b]` or `dir:x,y,z[:r,g,b]`.
+    pub fn from_spec(spec: &str) -> Result<Self, String> {
+        let mut parts = spec.split(':');
+        let kind = parts.next().unwrap_or_default();
+        let position = parts
+            .next()
+            .ok_or_else(|| format!("Missing light position in '{}'", spec))
+            .and_then(|values| parse_vec3(values, spec))?;
+        let color = match parts.next() {
+            Some(values) => parse_vec3(values, spec)?,
+            None => Vec3::splat(1.0),
+        };
+        if parts.next().is_some() {
+            return Err(format!("Too many fields in light '{}'", spec));
+        }
+
+        match kind {
+            "point" => Ok(Light::point(position, color)),
+            "dir" | "directional" => Ok(Light::directional(position, color)),
+            _ => Err(format!(
+                "Unknown light type '{}' (expected 'point' or 'dir')",
+                kind
+            )),
+        }
+    }
+
+    pub fn move_by(&mut self, delta: Vec3) {
+        self.position += delta;
+    }
+}
+
+fn parse_vec3(values: &str, spec: &str) -> Result<Vec3, String> {
+    let numbers: Vec<f32> = values
+        .split(',')
+        .map(|value| value.trim().parse::<f32>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("Invalid number in light '{}'", spec))?;
+
+    match numbers.as_slice() {
+        [x, y, z] => Ok(Vec3::new(*x, *y, *z)),
+        _ => Err(format!("Expected three comma-separated values in light '{}'", spec)),
+    }
+}
@@ -0,0 +1,126 @@
+use crate::gltf;
+use crate::off;
+use crate::parser::{self, ObjData, ObjError, ParseOptions};
+use crate::ply;
+use crate::stl;
+use std::path::Path;
+
+/// Model file formats the viewer can open.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModelFormat {
+    Obj,
+    Stl,
+    Ply,
+    Gltf,
+    Off,
+}
+
+impl ModelFormat {
+    pub fn from_extension(path: &str) -> Option<Self> {
+        let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "obj" => Some(ModelFormat::Obj),
+            "stl" => Some(ModelFormat::Stl),
+            "ply" => Some(ModelFormat::Ply),
+            "gltf" | "glb" => Some(ModelFormat::Gltf),
+            "off" => Some(ModelFormat::Off),
+            _ => None,
+        }
+    }
+
+    /// Guesses the format from the file's contents, for files without a
+    /// known extension.
+    pub fn sniff(bytes: &[u8]) -> Option<Self> {
+        if gltf::is_glb(bytes) || bytes.trim_ascii_start().starts_with(b"{") {
+            return Some(ModelFormat::Gltf);
+        }
+        if ply::is_ply(bytes) {
+            return Some(ModelFormat::Ply);
+        }
+        if stl::is_binary(bytes) || stl::is_ascii(bytes) {
+            return Some(ModelFormat::Stl);
+        }
+        if off::is_off(bytes) {
+            return Some(ModelFormat::Off);
+        }
+        let first_word = bytes
+            .trim_ascii_start()
+            .split(|b| b.is_ascii_whitespace())
+            .next()?;
+        match first_word {
+            b"v" | b"vn" | b"vt" | b"f" | b"o" | b"g" | b"mtllib" | b"usemtl" => {
+                Some(ModelFormat::Obj)
+            }
+            _ if first_word.starts_with(b"#") => Some(ModelFormat::Obj),
+            _ => None,
+        }
+    }
+}
+
+/// Loads a model, picking the parser by extension or, failing that, by the
+/// file's first bytes.
+pub fn load_model(path: &str, options: &ParseOptions) -> Result<ObjData, ObjError> {
+    let format = match ModelFormat::from_extension(path) {
+        Some(format) => format,
+        None => {
+            let bytes = parser::read_file(path)?;
+            ModelFormat::sniff(&bytes).ok_or_else(|| ObjError::InvalidData {
+                file: path.to_string(),
+                message: "unrecognized model format".to_string(),
+            })?
+        }
+    };
+
+    match format {
+        ModelFormat::Obj => ObjData::parse(path, options),
+        ModelFormat::Stl => stl::parse(path, options),
+        ModelFormat::Ply => ply::parse(path, options),
+        ModelFormat::Gltf => gltf::parse(path, options),
+        ModelFormat::Off => off::parse(path, options),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_are_detected() {
+        assert_eq!(
+            ModelFormat::from_extension("models/Part.STL"),
+            Some(ModelFormat::Stl)
+        );
+        assert_eq!(ModelFormat::from_extension("models/42"), None);
+        assert_eq!(
+            ModelFormat::sniff(b"solid part\nendsolid part\n"),
+            Some(ModelFormat::Stl)
+        );
+        assert_eq!(
+            ModelFormat::sniff(b"# exported\nv 0 0 0\n"),
+            Some(ModelFormat::Obj)
+        );
+        assert_eq!(
+            ModelFormat::sniff(b"ply\r\nformat ascii 1.0\r\n"),
+            Some(ModelFormat::Ply)
+        );
+        assert_eq!(
+            ModelFormat::sniff(b"glTF\x02\0\0\0"),
+            Some(ModelFormat::Gltf)
+        );
+        assert_eq!(
+            ModelFormat::sniff(b"# shape 12\nCOFF\n3 1 0\n"),
+            Some(ModelFormat::Off)
+        );
+        assert_eq!(ModelFormat::sniff(b"\x89PNG"), None);
+    }
+
+    #[test]
+    fn unknown_files_are_rejected() {
+        let options = ParseOptions::default();
+        assert!(matches!(
+            load_model("textures/sigma_cat.bmp", &options),
+            Err(ObjError::InvalidData { .. })
+        ));
+        assert!(load_model("models/42.obj", &options).is_ok());
+    }
+}
@@ -1,56 +1,101 @@
 extern crate gl;
 extern crate sdl2;
 
+mod args;
+mod bmp;
 mod camera;
+mod export;
+mod gltf;
+mod inflate;
+mod json;
+mod light;
+mod loader;
+mod material;
 mod matrices;
 mod model;
+mod netpbm;
+mod normals;
+mod off;
 mod opengl_setup;
 mod parser;
+mod ply;
+mod png;
+mod quaternion;
 mod renderer;
 mod shaders;
+mod stl;
 mod texture;
+mod tga;
+mod triangulation;
+mod vectors;
 
-use camera::Camera;
+use args::Args;
+use camera::{Camera, DragMode};
+use light::Light;
 use renderer::Renderer;
-use sdl2::keyboard::Keycode;	
+use vectors::Vec3;
+use sdl2::keyboard::{Keycode, Mod};	
 use sdl2::event::WindowEvent;
-use std::ffi::CString;
+use sdl2::mouse::MouseButton;
 use std::time::Instant;
 use std::env;
 
+fn rotation_title(model_rotation: &model::ModelRotation) -> String {
+    let [pitch, yaw, roll] = model_rotation.euler_angles();
+    format!(
+        "SCOP - pitch {:.0}° yaw {:.0}° roll {:.0}°",
+        pitch.to_degrees(),
+        yaw.to_degrees(),
+        roll.to_degrees()
+    )
+}
+
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let args = Args::parse(env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let (model_path, texture_path) = (args.model_path.as_str(), args.texture_path.as_str());
+
+    let model_data = loader::load_model(model_path, &args.parse_options).unwrap_or_else(|e| {
+        eprintln!("Failed to load model: {}", e);
+        std::process::exit(1);
+    });
+    // Broken exports tend to repeat the same mistake on every face
+    const MAX_PRINTED_WARNINGS: usize = 10;
+    for warning in model_data.warnings.iter().take(MAX_PRINTED_WARNINGS) {
+        eprintln!("Warning: {}", warning);
+    }
+    if model_data.warnings.len() > MAX_PRINTED_WARNINGS {
+        eprintln!(
+            "Warning: ... and {} more",
+            model_data.warnings.len() - MAX_PRINTED_WARNINGS
+        );
+    }
     
-    // argument handler
-    let (model_path, texture_path) = match args.len() {
-        1 => ("models/42.obj", "textures/sigma_cat.bmp"),
-        2 => {
-            let arg = args[1].as_str();
-            if arg.ends_with(".obj") {
-                (arg, "textures/sigma_cat.bmp")
-            } else if arg.ends_with(".bmp") {
-                ("models/42.obj", arg)
-            } else {
-				eprintln!("Invalid arguments\nModels accepted: .obj, Textures accepted: .bmp");
-				std::process::exit(1);
-			}
-        },
-        3 => {
-            let (arg1, arg2) = (args[1].as_str(), args[2].as_str());
-            if arg1.ends_with(".obj") && arg2.ends_with(".bmp") {
-                (arg1, arg2)
-            } else if arg1.ends_with(".bmp") && arg2.ends_with(".obj") {
-                (arg2, arg1)
-            } else {
-				eprintln!("Invalid arguments\nModels accepted: .obj, Textures accepted: .bmp");
-				std::process::exit(1);
-			}
-        },
-        _ => {
-            eprintln!("Error: Too many arguments");
+    let stats = &model_data.face_stats;
+    if stats.triangulated > 0 || stats.dropped > 0 {
+        println!(
+            "{}: triangulated {} polygons ({} with fan fallback), dropped {} faces",
+            model_path, stats.triangulated, stats.fan_fallbacks, stats.dropped
+        );
+    }
+
+    if let Some(export_path) = &args.export_path {
+        export::export(&model_data, export_path).unwrap_or_else(|e| {
+            eprintln!("Failed to export model: {}", e);
             std::process::exit(1);
-        }
-    };
+        });
+        println!("Exported {} to {}", model_path, export_path);
+        return;
+    }
+    // Ctrl+S writes to the working directory, never over the loaded file
+    let hotkey_export_path = format!(
+        "{}_export.obj",
+        std::path::Path::new(model_path)
+            .file_stem()
+            .map_or("model".into(), |stem| stem.to_string_lossy())
+    );
 
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
@@ -59,10 +104,10 @@ fn main() {
     gl_attr.set_context_profile(sdl2::video::GLProfile::Core);
     gl_attr.set_context_version(3, 3);
 
-    let mut window_width = 1024 as i32;
-	let mut window_height = 768 as i32;
+    let mut window_width = 1024_i32;
+	let mut window_height = 768_i32;
 	let mut minimized = false;
-    let window = video_subsystem
+    let mut window = video_subsystem
         .window("SCOP", window_width as u32, window_height as u32)
         .opengl()
         .position_centered()
@@ -72,13 +117,9 @@ fn main() {
     let _gl_context = window.gl_create_context().unwrap();
     gl::load_with(|s| window.subsystem().gl_get_proc_address(s) as *const _);
 
-    let model_data = parser::ObjData::parse(model_path).unwrap_or_else(|_| {
-        eprintln!("Failed to load model: {}", model_path);
-        std::process::exit(1);
-    });
-    
     let model_center = model::calculate_model_center(&model_data.vertices);
-    let (vao, _ebo) = opengl_setup::setup_opengl_buffers(&model_data);
+    let (interleaved_data, indices) = opengl_setup::build_vertex_buffer(&model_data);
+    let (vao, _ebo) = opengl_setup::setup_opengl_buffers(&interleaved_data, &indices);
 
     let vertex_shader =
         shaders::compile_shader(include_str!("shaders/vertex.glsl"), gl::VERTEX_SHADER).unwrap();
@@ -87,20 +128,34 @@ fn main() {
             .unwrap();
     let shader_program = shaders::link_program(vertex_shader, fragment_shader).unwrap();
 
-    let model_loc =
-        unsafe { gl::GetUniformLocation(shader_program, CString::new("model").unwrap().as_ptr()) };
+    let model_loc = shaders::uniform_location(shader_program, "model");
 
     let mut camera = Camera::new(window_width, window_height);
     camera.update_target(model_center);
     let mut model_rotation = model::ModelRotation::new();
     let mut model_position = model::ModelPosition::new();
     let mut last_frame = Instant::now();
-    let mut renderer = Renderer::new(
-        shader_program,
-        vao,
-        model_loc,
-        (model_data.faces.len() * 3) as i32,
+    let mut renderer = Renderer::new(shader_program, vao, model_loc);
+    for error in renderer.set_materials(&model_data) {
+        eprintln!("Warning: failed to load material texture {}", error);
+    }
+    renderer.set_parts(&model_data.parts);
+    renderer.set_has_vertex_colors(!model_data.vertex_colors.is_empty());
+    renderer.set_primitives(
+        &indices,
+        model_data.faces.len(),
+        model_data.lines.len(),
+        model_data.points.len(),
     );
+    for (index, part) in model_data.parts.iter().enumerate() {
+        println!("Part {}: {} ({} triangles)", index + 1, part.name, part.face_count);
+    }
+
+    renderer.set_lights(if args.lights.is_empty() {
+        Light::default_rig(model_center)
+    } else {
+        args.lights
+    });
 
     renderer.load_texture(texture_path).unwrap_or_else(|_| {
         eprintln!("Failed to load texture: {}", texture_path);
@@ -120,29 +175,119 @@ fn main() {
                 sdl2::event::Event::Window { win_event, .. } => match win_event {
 					WindowEvent::Resized(width, height) |
 					WindowEvent::SizeChanged(width, height) => {
-						window_width = width as i32;
-						window_height = height as i32;
+						window_width = width;
+						window_height = height;
 					}
 					WindowEvent::Minimized => minimized = true,
 					WindowEvent::Restored => minimized = false,
 					_ => {}
 				},
+				sdl2::event::Event::MouseButtonDown { mouse_btn, x, y, .. } => match mouse_btn {
+                    MouseButton::Left => camera.begin_drag(DragMode::Orbit, x, y),
+                    MouseButton::Middle => camera.begin_drag(DragMode::Pan, x, y),
+                    _ => {}
+                },
+                sdl2::event::Event::MouseButtonUp {
+                    mouse_btn: MouseButton::Left | MouseButton::Middle,
+                    ..
+                } => camera.end_drag(),
+                sdl2::event::Event::MouseMotion { x, y, .. } => camera.drag_to(x, y),
+                sdl2::event::Event::MouseWheel { y, .. } => camera.scroll(y as f32),
 				sdl2::event::Event::KeyDown {
                     keycode: Some(keycode),
+                    keymod,
                     ..
                 } => match keycode {
                     Keycode::Escape | Keycode::Q => break 'mainloop,
-                    Keycode::A => model_rotation.y_angle -= 0.1,
-                    Keycode::D => model_rotation.y_angle += 0.1,
-                    Keycode::W => model_rotation.x_angle -= 0.1,
-                    Keycode::S => model_rotation.x_angle += 0.1,
+                    Keycode::S if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+                        match export::export(&model_data, &hotkey_export_path) {
+                            Ok(()) => println!("Exported model to {}", hotkey_export_path),
+                            Err(e) => eprintln!("Failed to export model: {}", e),
+                        }
+                    }
+                    Keycode::A | Keycode::D | Keycode::W | Keycode::S | Keycode::R | Keycode::F => {
+                        // W/A/S/D turn the model the way they always have
+                        let (axis, angle) = match keycode {
+                            Keycode::A => (Vec3::Y, 0.1),
+                            Keycode::D => (Vec3::Y, -0.1),
+                            Keycode::W => (Vec3::X, 0.1),
+                            Keycode::S => (Vec3::X, -0.1),
+                            Keycode::R => (Vec3::Z, 0.1),
+                            _ => (Vec3::Z, -0.1),
+                        };
+                        model_rotation.rotate(axis, angle);
+                        window
+                            .set_title(&rotation_title(&model_rotation))
+                            .unwrap_or_default();
+                    }
+                    Keycode::Left | Keycode::Right | Keycode::Up | Keycode::Down
+                    | Keycode::PageUp | Keycode::PageDown
+                        if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) =>
+                    {
+                        let delta = match keycode {
+                            Keycode::Left => Vec3::new(-0.25, 0.0, 0.0),
+                            Keycode::Right => Vec3::new(0.25, 0.0, 0.0),
+                            Keycode::Up => Vec3::new(0.0, 0.25, 0.0),
+                            Keycode::Down => Vec3::new(0.0, -0.25, 0.0),
+                            Keycode::PageUp => Vec3::new(0.0, 0.0, -0.25),
+                            _ => Vec3::new(0.0, 0.0, 0.25),
+                        };
+                        renderer.move_active_light(delta);
+                    }
+                    Keycode::Left | Keycode::Right | Keycode::Up | Keycode::Down
+                    | Keycode::PageUp | Keycode::PageDown
+                        if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) =>
+                    {
+                        let delta = match keycode {
+                            Keycode::Left => Vec3::new(-0.1, 0.0, 0.0),
+                            Keycode::Right => Vec3::new(0.1, 0.0, 0.0),
+                            Keycode::Up => Vec3::new(0.0, 0.1, 0.0),
+                            Keycode::Down => Vec3::new(0.0, -0.1, 0.0),
+                            Keycode::PageUp => Vec3::new(0.0, 0.0, -0.1),
+                            _ => Vec3::new(0.0, 0.0, 0.1),
+                        };
+                        renderer.move_selected_part(delta);
+                    }
+                    Keycode::Tab => renderer.select_next_light(),
+                    Keycode::G | Keycode::V | Keycode::O => {
+                        match keycode {
+                            Keycode::G => renderer.select_next_part(),
+                            Keycode::V => renderer.toggle_selected_part(),
+                            _ => renderer.toggle_isolate_part(),
+                        }
+                        window.set_title(&renderer.part_title()).unwrap_or_default();
+                    }
+                    Keycode::C => renderer.toggle_part_colors(),
+                    Keycode::T => {
+                        let mode = renderer.cycle_alpha_mode();
+                        window
+                            .set_title(&format!("SCOP - {}", mode.name()))
+                            .unwrap_or_default();
+                    }
                     Keycode::Left => model_position.x -= 0.1,
                     Keycode::Right => model_position.x += 0.1,
                     Keycode::Up => model_position.y += 0.1,
                     Keycode::Down => model_position.y -= 0.1,
                     Keycode::Z => camera.zoom(-0.2),
                     Keycode::X => camera.zoom(0.2),
+                    Keycode::J | Keycode::L | Keycode::I | Keycode::K => {
+                        let (dx, dy) = match keycode {
+                            Keycode::J => (-0.1, 0.0),
+                            Keycode::L => (0.1, 0.0),
+                            Keycode::I => (0.0, 0.1),
+                            _ => (0.0, -0.1),
+                        };
+                        if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                            camera.pan(dx, dy);
+                        } else {
+                            camera.orbit(dx, dy);
+                        }
+                    }
+                    Keycode::P => camera.toggle_projection(),
+                    Keycode::LeftBracket => camera.set_fov(camera.fov.to_degrees() - 5.0),
+                    Keycode::RightBracket => camera.set_fov(camera.fov.to_degrees() + 5.0),
                     Keycode::E => renderer.cycle_render_mode(),
+                    Keycode::N => renderer.toggle_face_color_space(),
                     _ => {}
                 },
                 _ => {}
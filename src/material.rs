@@ -0,0 +1,324 @@
+use crate::parser::{self, LineContext, ObjError, ParseOptions, Token};
+use std::fs::File;
+use std::io::{self, BufRead};
+use std::path::Path;
+use std::rc::Rc;
+
+/// Surface description from an MTL `newmtl` block. Texture maps are stored as
+/// paths resolved against the library's directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Material {
+    pub name: String,
+    /// `Ka`, scaled by the scene's ambient light.
+    pub ambient: [f32; 3],
+    /// `Kd`, multiplied with `diffuse_map` when there is one.
+    pub diffuse: [f32; 3],
+    /// `Ks`
+    pub specular: [f32; 3],
+    /// `Ns`, the specular exponent.
+    pub shininess: f32,
+    /// `d`, or `1 - Tr`.
+    pub opacity: f32,
+    /// `illum`: 0 is unlit color, 1 diffuse only, 2 and above add specular.
+    pub illumination: u32,
+    /// `map_Kd`
+    pub diffuse_map: Option<String>,
+    /// A diffuse map stored inside the model file, used instead of
+    /// `diffuse_map`.
+    pub diffuse_image: Option<EmbeddedImage>,
+    /// `map_Bump` (or `bump`), a height map.
+    pub bump_map: Option<String>,
+    /// The `-bm` option of the bump map.
+    pub bump_multiplier: f32,
+    /// `map_d`, multiplied with `opacity`.
+    pub alpha_map: Option<String>,
+    /// How alpha is used, from glTF's `alphaMode`.
+    pub alpha: AlphaUsage,
+}
+
+/// How a material's alpha is applied. MTL materials always blend.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlphaUsage {
+    /// Alpha is ignored.
+    Opaque,
+    /// Fragments with alpha below the cutoff are cut out, the rest are opaque.
+    Mask(f32),
+    /// The surface is blended over what's behind it.
+    Blend,
+}
+
+impl Material {
+    /// Used for faces that have no `usemtl`; matches the viewer's look from
+    /// before materials were supported.
+    pub fn new(name: &str) -> Self {
+        Material {
+            name: name.to_string(),
+            ambient: [0.8; 3],
+            diffuse: [0.8; 3],
+            specular: [0.5; 3],
+            shininess: 32.0,
+            opacity: 1.0,
+            illumination: 2,
+            diffuse_map: None,
+            diffuse_image: None,
+            bump_map: None,
+            bump_multiplier: 1.0,
+            alpha_map: None,
+            alpha: AlphaUsage::Blend,
+        }
+    }
+}
+
+/// An image file carried inside a model, as glTF files usually do.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbeddedImage {
+    /// Names the image in messages, e.g. `model.glb image 0`.
+    pub name: String,
+    pub bytes: Rc<[u8]>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material::new("default")
+    }
+}
+
+/// Reads every material of an MTL file. Like OBJ parsing, bad lines are
+/// skipped and returned as warnings unless `options.strict` is set.
+pub fn parse_library(
+    path: &str,
+    options: &ParseOptions,
+) -> Result<(Vec<Material>, Vec<ObjError>), ObjError> {
+    let file = File::open(path).map_err(|source| ObjError::Io {
+        file: path.to_string(),
+        source,
+    })?;
+    let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+    parse_library_reader(io::BufReader::new(file), path, directory, options)
+}
+
+/// Parses MTL data from any reader; texture paths are resolved against
+/// `directory` and `file_name` is only used in errors.
+pub fn parse_library_reader<R: BufRead>(
+    reader: R,
+    file_name: &str,
+    directory: &Path,
+    options: &ParseOptions,
+) -> Result<(Vec<Material>, Vec<ObjError>), ObjError> {
+    let mut materials: Vec<Material> = Vec::new();
+    let mut warnings = Vec::new();
+
+    parser::read_lines(reader, file_name, options, &mut warnings, |line, _| {
+        let tokens = line.tokens();
+        let Some((keyword, values)) = tokens.split_first() else {
+            return Ok(());
+        };
+        if keyword.text.starts_with('#') {
+            return Ok(());
+        }
+        if keyword.text == "newmtl" {
+            return match values {
+                [name] => {
+                    materials.push(Material::new(name.text));
+                    Ok(())
+                }
+                _ => Err(line.malformed_statement(keyword.column)),
+            };
+        }
+
+        // Everything else describes the material being defined
+        let Some(material) = materials.last_mut() else {
+            return Err(line.malformed_statement(keyword.column));
+        };
+        match keyword.text {
+            "Ka" => material.ambient = parse_color(keyword, values, line)?,
+            "Kd" => material.diffuse = parse_color(keyword, values, line)?,
+            "Ks" => material.specular = parse_color(keyword, values, line)?,
+            "Ns" => material.shininess = parse_scalar(keyword, values, line)?,
+            "d" => material.opacity = parse_scalar(keyword, values, line)?,
+            "Tr" => material.opacity = 1.0 - parse_scalar(keyword, values, line)?,
+            "illum" => match values {
+                [value] => {
+                    material.illumination =
+                        value.text.parse().map_err(|_| ObjError::MalformedNumber {
+                            location: line.at(value.column),
+                            text: value.text.to_string(),
+                        })?
+                }
+                _ => return Err(line.malformed_statement(keyword.column)),
+            },
+            "map_Kd" => {
+                let map = parse_map(keyword, values, line, directory)?;
+                material.diffuse_map = Some(map.path);
+            }
+            "map_Bump" | "map_bump" | "bump" => {
+                let map = parse_map(keyword, values, line, directory)?;
+                material.bump_map = Some(map.path);
+                material.bump_multiplier = map.bump_multiplier;
+            }
+            "map_d" => {
+                let map = parse_map(keyword, values, line, directory)?;
+                material.alpha_map = Some(map.path);
+            }
+            // Common in exported files but irrelevant for a rasterizer
+            "Ni" | "Ke" | "Tf" | "sharpness" | "map_Ka" | "map_Ks" | "map_Ns" | "map_Ke"
+            | "disp" | "decal" | "refl" => {}
+            _ => {
+                return Err(ObjError::UnsupportedStatement {
+                    location: line.at(keyword.column),
+                    text: keyword.text.to_string(),
+                })
+            }
+        }
+        Ok(())
+    })?;
+
+    Ok((materials, warnings))
+}
+
+/// `Ka`, `Kd` and `Ks` take `r g b`, or a single value for grey.
+fn parse_color(
+    keyword: &Token,
+    values: &[Token],
+    line: &LineContext,
+) -> Result<[f32; 3], ObjError> {
+    match values.len() {
+        1 => Ok([parser::parse_floats::<1>(values, line)?[0]; 3]),
+        3 => parser::parse_floats(values, line),
+        _ => Err(line.malformed_statement(keyword.column)),
+    }
+}
+
+fn parse_scalar(keyword: &Token, values: &[Token], line: &LineContext) -> Result<f32, ObjError> {
+    match values.len() {
+        1 => Ok(parser::parse_floats::<1>(values, line)?[0]),
+        _ => Err(line.malformed_statement(keyword.column)),
+    }
+}
+
+struct TextureMap {
+    path: String,
+    bump_multiplier: f32,
+}
+
+/// Parses `map_* [-option values...] file`. Options are skipped except
+/// `-bm`; the file name is the last word, so names with spaces aren't
+/// supported.
+fn parse_map(
+    keyword: &Token,
+    values: &[Token],
+    line: &LineContext,
+    directory: &Path,
+) -> Result<TextureMap, ObjError> {
+    let Some((file, options)) = values.split_last() else {
+        return Err(line.malformed_statement(keyword.column));
+    };
+    if file.text.starts_with('-') {
+        return Err(line.malformed_statement(file.column));
+    }
+
+    let mut bump_multiplier = 1.0;
+    let mut remaining = options;
+    while let Some((option, rest)) = remaining.split_first() {
+        if !option.text.starts_with('-') {
+            return Err(line.malformed_statement(option.column));
+        }
+        // Options take a fixed number of values, except the 1-3 numbers of -o/-s/-t
+        let count = match option.text {
+            "-mm" => 2,
+            "-o" | "-s" | "-t" => rest
+                .iter()
+                .take(3)
+                .take_while(|value| value.text.parse::<f32>().is_ok())
+                .count(),
+            _ => 1,
+        };
+        if rest.len() < count {
+            return Err(line.malformed_statement(option.column));
+        }
+        if option.text == "-bm" {
+            bump_multiplier = parser::parse_floats::<1>(rest, line)?[0];
+        }
+        remaining = &rest[count..];
+    }
+
+    Ok(TextureMap {
+        path: directory.join(file.text).to_string_lossy().into_owned(),
+        bump_multiplier,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(source: &str, strict: bool) -> Result<(Vec<Material>, Vec<ObjError>), ObjError> {
+        parse_library_reader(
+            source.as_bytes(),
+            "inline.mtl",
+            Path::new("textures"),
+            &ParseOptions {
+                strict,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn materials_read_colors_and_maps() {
+        let (materials, warnings) = parse_str(
+            "# two materials\n\
+             newmtl brick\n\
+             Ka 0.1 0.1 0.1\nKd 0.8 0.2 0.1\nKs 0.3\nNs 64\nd 0.5\nillum 1\nNi 1.45\n\
+             map_Kd -s 2 2 brick.bmp\nmap_Bump -bm 0.25 brick_height.bmp\nmap_d mask.bmp\n\
+             newmtl glass\nTr 0.75\n",
+            true,
+        )
+        .unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(materials.len(), 2);
+
+        let brick = &materials[0];
+        assert_eq!(brick.name, "brick");
+        assert_eq!(brick.ambient, [0.1; 3]);
+        assert_eq!(brick.diffuse, [0.8, 0.2, 0.1]);
+        assert_eq!(brick.specular, [0.3; 3]);
+        assert_eq!((brick.shininess, brick.opacity), (64.0, 0.5));
+        assert_eq!(brick.illumination, 1);
+        let texture = |name: &str| {
+            Some(
+                Path::new("textures")
+                    .join(name)
+                    .to_string_lossy()
+                    .into_owned(),
+            )
+        };
+        assert_eq!(brick.diffuse_map, texture("brick.bmp"));
+        assert_eq!(brick.bump_map, texture("brick_height.bmp"));
+        assert_eq!(brick.bump_multiplier, 0.25);
+        assert_eq!(brick.alpha_map, texture("mask.bmp"));
+
+        assert_eq!(materials[1].opacity, 0.25);
+        assert_eq!(materials[1].diffuse, Material::default().diffuse);
+    }
+
+    #[test]
+    fn bad_lines_are_reported_with_locations() {
+        match parse_str("newmtl a\nKd 1 x 1\n", true) {
+            Err(ObjError::MalformedNumber { location, text }) => {
+                assert_eq!((location.line, location.column), (2, 6));
+                assert_eq!(text, "x");
+            }
+            other => panic!("unexpected result: {:?}", other.map(|(m, _)| m.len())),
+        }
+
+        let (materials, warnings) = parse_str(
+            "Kd 1 1 1\nnewmtl a\nKd 1 1\nmap_Kd\nmap_Kd -bm\nfoo 1\n",
+            false,
+        )
+        .unwrap();
+        assert_eq!(materials.len(), 1);
+        assert_eq!(warnings.len(), 5);
+        assert!(matches!(warnings[4], ObjError::UnsupportedStatement { .. }));
+    }
+}
@@ -1,34 +1,561 @@
-pub type Mat4 = [[f32; 4]; 4];
-
-pub fn mat4_mul(a: Mat4, b: Mat4) -> Mat4 {
-    let mut result = [[0.0; 4]; 4];
-    for i in 0..4 {
-        for j in 0..4 {
-            result[i][j] =
-                a[i][0] * b[0][j] + a[i][1] * b[1][j] + a[i][2] * b[2][j] + a[i][3] * b[3][j];
-        }
-    }
-    result
-}
-
-pub fn mat4_rotation_y(angle: f32) -> Mat4 {
-    let cos_theta = angle.cos();
-    let sin_theta = angle.sin();
-    [
-        [cos_theta, 0.0, sin_theta, 0.0],
-        [0.0, 1.0, 0.0, 0.0],
-        [-sin_theta, 0.0, cos_theta, 0.0],
-        [0.0, 0.0, 0.0, 1.0],
-    ]
-}
-
-pub fn mat4_rotation_x(angle: f32) -> Mat4 {
-    let cos_theta = angle.cos();
-    let sin_theta = angle.sin();
-    [
-        [1.0, 0.0, 0.0, 0.0],
-        [0.0, cos_theta, -sin_theta, 0.0],
-        [0.0, sin_theta, cos_theta, 0.0],
-        [0.0, 0.0, 0.0, 1.0],
-    ]
+use crate::quaternion::Quat;
+use crate::vectors::{Vec3, Vec4};
+use std::ops::Mul;
+
+/// Row-major 3x3 matrix (`rows[row][column]`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat3 {
+    pub rows: [[f32; 3]; 3],
+}
+
+/// Row-major 4x4 matrix (`rows[row][column]`) acting on column vectors.
+/// Upload with `transpose = GL_TRUE`, since GL expects column-major data.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat4 {
+    pub rows: [[f32; 4]; 4],
+}
+
+impl Mat3 {
+    #[allow(dead_code)]
+    pub const IDENTITY: Mat3 = Mat3 {
+        rows: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+    };
+
+    pub const fn new(rows: [[f32; 3]; 3]) -> Self {
+        Mat3 { rows }
+    }
+
+    /// Upper-left 3x3 block, i.e. the linear part of an affine transform.
+    pub fn from_mat4(m: &Mat4) -> Self {
+        let r = &m.rows;
+        Mat3::new([
+            [r[0][0], r[0][1], r[0][2]],
+            [r[1][0], r[1][1], r[1][2]],
+            [r[2][0], r[2][1], r[2][2]],
+        ])
+    }
+
+    /// Inverse-transpose of the model's linear part, which keeps normals
+    /// perpendicular to surfaces under non-uniform scaling.
+    pub fn normal_matrix(model: &Mat4) -> Self {
+        let linear = Mat3::from_mat4(model);
+        linear.inverse().map_or(linear, |inverse| inverse.transpose())
+    }
+
+    pub fn transpose(&self) -> Mat3 {
+        let mut result = [[0.0; 3]; 3];
+        for (i, row) in self.rows.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                result[j][i] = *value;
+            }
+        }
+        Mat3::new(result)
+    }
+
+    pub fn determinant(&self) -> f32 {
+        let m = &self.rows;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    /// Returns `None` for singular matrices.
+    pub fn inverse(&self) -> Option<Mat3> {
+        let det = self.determinant();
+        if det.abs() < f32::EPSILON {
+            return None;
+        }
+        let m = &self.rows;
+        let inv_det = 1.0 / det;
+        // Transposed cofactor matrix scaled by 1/det
+        Some(Mat3::new([
+            [
+                (m[1][1] * m[2][2] - m[1][2] * m[2][1]) * inv_det,
+                (m[0][2] * m[2][1] - m[0][1] * m[2][2]) * inv_det,
+                (m[0][1] * m[1][2] - m[0][2] * m[1][1]) * inv_det,
+            ],
+            [
+                (m[1][2] * m[2][0] - m[1][0] * m[2][2]) * inv_det,
+                (m[0][0] * m[2][2] - m[0][2] * m[2][0]) * inv_det,
+                (m[0][2] * m[1][0] - m[0][0] * m[1][2]) * inv_det,
+            ],
+            [
+                (m[1][0] * m[2][1] - m[1][1] * m[2][0]) * inv_det,
+                (m[0][1] * m[2][0] - m[0][0] * m[2][1]) * inv_det,
+                (m[0][0] * m[1][1] - m[0][1] * m[1][0]) * inv_det,
+            ],
+        ]))
+    }
+
+    pub fn as_ptr(&self) -> *const f32 {
+        self.rows.as_ptr() as *const f32
+    }
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4 {
+        rows: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    };
+
+    pub const fn new(rows: [[f32; 4]; 4]) -> Self {
+        Mat4 { rows }
+    }
+
+    pub fn translation(offset: Vec3) -> Self {
+        Mat4::new([
+            [1.0, 0.0, 0.0, offset.x],
+            [0.0, 1.0, 0.0, offset.y],
+            [0.0, 0.0, 1.0, offset.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn scale(factors: Vec3) -> Self {
+        Mat4::new([
+            [factors.x, 0.0, 0.0, 0.0],
+            [0.0, factors.y, 0.0, 0.0],
+            [0.0, 0.0, factors.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    #[allow(dead_code)]
+    pub fn rotation_x(angle: f32) -> Self {
+        let (sin_theta, cos_theta) = angle.sin_cos();
+        Mat4::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, cos_theta, -sin_theta, 0.0],
+            [0.0, sin_theta, cos_theta, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    #[allow(dead_code)]
+    pub fn rotation_y(angle: f32) -> Self {
+        let (sin_theta, cos_theta) = angle.sin_cos();
+        Mat4::new([
+            [cos_theta, 0.0, sin_theta, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [-sin_theta, 0.0, cos_theta, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    #[allow(dead_code)]
+    pub fn rotation_z(angle: f32) -> Self {
+        let (sin_theta, cos_theta) = angle.sin_cos();
+        Mat4::new([
+            [cos_theta, -sin_theta, 0.0, 0.0],
+            [sin_theta, cos_theta, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn from_quat(q: Quat) -> Self {
+        let Quat { x, y, z, w } = q;
+        Mat4::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Model matrix rotating around `pivot` and then offsetting by `translation`.
+    pub fn model(orientation: Quat, pivot: Vec3, translation: Vec3) -> Self {
+        Mat4::translation(pivot + translation)
+            * Mat4::from_quat(orientation)
+            * Mat4::translation(-pivot)
+    }
+
+    pub fn look_at(eye: Vec3, target: Vec3, up: Vec3) -> Self {
+        let forward = (target - eye).normalize();
+        let right = forward.cross(up).normalize();
+        let true_up = right.cross(forward);
+
+        Mat4::new([
+            [right.x, right.y, right.z, -right.dot(eye)],
+            [true_up.x, true_up.y, true_up.z, -true_up.dot(eye)],
+            [-forward.x, -forward.y, -forward.z, forward.dot(eye)],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// OpenGL-style perspective projection (`fov` is vertical, in radians).
+    pub fn perspective(fov: f32, aspect_ratio: f32, near: f32, far: f32) -> Self {
+        let f = 1.0 / (fov / 2.0).tan();
+
+        Mat4::new([
+            [f / aspect_ratio, 0.0, 0.0, 0.0],
+            [0.0, f, 0.0, 0.0],
+            [
+                0.0,
+                0.0,
+                -(far + near) / (far - near),
+                -2.0 * far * near / (far - near),
+            ],
+            [0.0, 0.0, -1.0, 0.0],
+        ])
+    }
+
+    pub fn orthographic(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Self {
+        Mat4::new([
+            [
+                2.0 / (right - left),
+                0.0,
+                0.0,
+                -(right + left) / (right - left),
+            ],
+            [
+                0.0,
+                2.0 / (top - bottom),
+                0.0,
+                -(top + bottom) / (top - bottom),
+            ],
+            [0.0, 0.0, -2.0 / (far - near), -(far + near) / (far - near)],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    #[allow(dead_code)]
+    pub fn transpose(&self) -> Mat4 {
+        let mut result = [[0.0; 4]; 4];
+        for (i, row) in self.rows.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                result[j][i] = *value;
+            }
+        }
+        Mat4::new(result)
+    }
+
+    #[allow(dead_code)]
+    pub fn determinant(&self) -> f32 {
+        let (cofactors, _) = self.cofactor_pairs();
+        let [s0, s1, s2, s3, s4, s5, c0, c1, c2, c3, c4, c5] = cofactors;
+        s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0
+    }
+
+    /// General inverse via 2x2 sub-determinants; `None` for singular matrices.
+    #[allow(dead_code)]
+    pub fn inverse(&self) -> Option<Mat4> {
+        let (cofactors, m) = self.cofactor_pairs();
+        let [s0, s1, s2, s3, s4, s5, c0, c1, c2, c3, c4, c5] = cofactors;
+        let det = s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0;
+        if det.abs() < f32::EPSILON {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        let rows = [
+            [
+                (m[1][1] * c5 - m[1][2] * c4 + m[1][3] * c3) * inv_det,
+                (-m[0][1] * c5 + m[0][2] * c4 - m[0][3] * c3) * inv_det,
+                (m[3][1] * s5 - m[3][2] * s4 + m[3][3] * s3) * inv_det,
+                (-m[2][1] * s5 + m[2][2] * s4 - m[2][3] * s3) * inv_det,
+            ],
+            [
+                (-m[1][0] * c5 + m[1][2] * c2 - m[1][3] * c1) * inv_det,
+                (m[0][0] * c5 - m[0][2] * c2 + m[0][3] * c1) * inv_det,
+                (-m[3][0] * s5 + m[3][2] * s2 - m[3][3] * s1) * inv_det,
+                (m[2][0] * s5 - m[2][2] * s2 + m[2][3] * s1) * inv_det,
+            ],
+            [
+                (m[1][0] * c4 - m[1][1] * c2 + m[1][3] * c0) * inv_det,
+                (-m[0][0] * c4 + m[0][1] * c2 - m[0][3] * c0) * inv_det,
+                (m[3][0] * s4 - m[3][1] * s2 + m[3][3] * s0) * inv_det,
+                (-m[2][0] * s4 + m[2][1] * s2 - m[2][3] * s0) * inv_det,
+            ],
+            [
+                (-m[1][0] * c3 + m[1][1] * c1 - m[1][2] * c0) * inv_det,
+                (m[0][0] * c3 - m[0][1] * c1 + m[0][2] * c0) * inv_det,
+                (-m[3][0] * s3 + m[3][1] * s1 - m[3][2] * s0) * inv_det,
+                (m[2][0] * s3 - m[2][1] * s1 + m[2][2] * s0) * inv_det,
+            ],
+        ];
+        Some(Mat4::new(rows))
+    }
+
+    /// 2x2 minors of the top two rows (`s`) and bottom two rows (`c`).
+    #[allow(dead_code)]
+    fn cofactor_pairs(&self) -> ([f32; 12], &[[f32; 4]; 4]) {
+        let m = &self.rows;
+        (
+            [
+                m[0][0] * m[1][1] - m[1][0] * m[0][1],
+                m[0][0] * m[1][2] - m[1][0] * m[0][2],
+                m[0][0] * m[1][3] - m[1][0] * m[0][3],
+                m[0][1] * m[1][2] - m[1][1] * m[0][2],
+                m[0][1] * m[1][3] - m[1][1] * m[0][3],
+                m[0][2] * m[1][3] - m[1][2] * m[0][3],
+                m[2][0] * m[3][1] - m[3][0] * m[2][1],
+                m[2][0] * m[3][2] - m[3][0] * m[2][2],
+                m[2][0] * m[3][3] - m[3][0] * m[2][3],
+                m[2][1] * m[3][2] - m[3][1] * m[2][2],
+                m[2][1] * m[3][3] - m[3][1] * m[2][3],
+                m[2][2] * m[3][3] - m[3][2] * m[2][3],
+            ],
+            m,
+        )
+    }
+
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        let v = *self * p.extend(1.0);
+        if v.w != 0.0 && v.w != 1.0 {
+            v.truncate() / v.w
+        } else {
+            v.truncate()
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        (*self * v.extend(0.0)).truncate()
+    }
+
+    pub fn as_ptr(&self) -> *const f32 {
+        self.rows.as_ptr() as *const f32
+    }
+}
+
+impl Mul for Mat3 {
+    type Output = Mat3;
+
+    fn mul(self, b: Mat3) -> Mat3 {
+        let mut result = [[0.0; 3]; 3];
+        for (i, row) in result.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..3).map(|k| self.rows[i][k] * b.rows[k][j]).sum();
+            }
+        }
+        Mat3::new(result)
+    }
+}
+
+impl Mul<Vec3> for Mat3 {
+    type Output = Vec3;
+
+    fn mul(self, v: Vec3) -> Vec3 {
+        let r = &self.rows;
+        Vec3::new(
+            r[0][0] * v.x + r[0][1] * v.y + r[0][2] * v.z,
+            r[1][0] * v.x + r[1][1] * v.y + r[1][2] * v.z,
+            r[2][0] * v.x + r[2][1] * v.y + r[2][2] * v.z,
+        )
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, b: Mat4) -> Mat4 {
+        let mut result = [[0.0; 4]; 4];
+        for (i, row) in result.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.rows[i][k] * b.rows[k][j]).sum();
+            }
+        }
+        Mat4::new(result)
+    }
+}
+
+impl Mul<Vec4> for Mat4 {
+    type Output = Vec4;
+
+    fn mul(self, v: Vec4) -> Vec4 {
+        let dot = |row: &[f32; 4]| row[0] * v.x + row[1] * v.y + row[2] * v.z + row[3] * v.w;
+        Vec4::new(
+            dot(&self.rows[0]),
+            dot(&self.rows[1]),
+            dot(&self.rows[2]),
+            dot(&self.rows[3]),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vectors::tests::{assert_vec3_eq, Rng, EPSILON};
+    use std::f32::consts::FRAC_PI_2;
+
+    fn assert_mat4_eq(a: &Mat4, b: &Mat4) {
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!(
+                    (a.rows[i][j] - b.rows[i][j]).abs() < EPSILON * 10.0,
+                    "{:?} != {:?}",
+                    a,
+                    b
+                );
+            }
+        }
+    }
+
+    fn random_affine(rng: &mut Rng) -> Mat4 {
+        let rotation =
+            Quat::from_axis_angle(rng.vec3(1.0) + Vec3::splat(0.01), rng.next_f32() * 3.0);
+        let scale = Vec3::new(
+            1.0 + rng.next_f32().abs(),
+            1.0 + rng.next_f32().abs(),
+            1.0 + rng.next_f32().abs(),
+        );
+        Mat4::translation(rng.vec3(10.0)) * Mat4::from_quat(rotation) * Mat4::scale(scale)
+    }
+
+    #[test]
+    fn identity_is_neutral() {
+        let m = Mat4::translation(Vec3::new(1.0, 2.0, 3.0)) * Mat4::rotation_y(0.3);
+        assert_eq!(Mat4::IDENTITY * m, m);
+        assert_eq!(m * Mat4::IDENTITY, m);
+        assert_eq!(Mat3::IDENTITY * Mat3::from_mat4(&m), Mat3::from_mat4(&m));
+    }
+
+    #[test]
+    fn translation_moves_points_not_vectors() {
+        let m = Mat4::translation(Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(m.transform_point(Vec3::ZERO), Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(m.transform_vector(Vec3::X), Vec3::X);
+    }
+
+    #[test]
+    fn axis_rotations_follow_right_hand_rule() {
+        assert_vec3_eq(
+            Mat4::rotation_x(FRAC_PI_2).transform_vector(Vec3::Y),
+            Vec3::Z,
+        );
+        assert_vec3_eq(
+            Mat4::rotation_y(FRAC_PI_2).transform_vector(Vec3::Z),
+            Vec3::X,
+        );
+        assert_vec3_eq(
+            Mat4::rotation_z(FRAC_PI_2).transform_vector(Vec3::X),
+            Vec3::Y,
+        );
+    }
+
+    #[test]
+    fn look_at_puts_target_on_negative_z() {
+        let eye = Vec3::new(3.0, 4.0, 5.0);
+        let target = Vec3::new(-1.0, 0.5, 2.0);
+        let view = Mat4::look_at(eye, target, Vec3::Y);
+        assert_vec3_eq(view.transform_point(eye), Vec3::ZERO);
+        let distance = (target - eye).length();
+        assert_vec3_eq(view.transform_point(target), Vec3::new(0.0, 0.0, -distance));
+    }
+
+    #[test]
+    fn perspective_maps_clip_planes_to_ndc() {
+        let projection = Mat4::perspective(FRAC_PI_2, 1.0, 0.5, 50.0);
+        let near = projection.transform_point(Vec3::new(0.0, 0.0, -0.5));
+        let far = projection.transform_point(Vec3::new(0.0, 0.0, -50.0));
+        assert!((near.z + 1.0).abs() < EPSILON);
+        assert!((far.z - 1.0).abs() < EPSILON);
+        // 90 degree fov: the frustum edge at depth 1 is at y = 1
+        let edge = projection.transform_point(Vec3::new(0.0, 1.0, -1.0));
+        assert!((edge.y - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn orthographic_maps_box_to_ndc_cube() {
+        let projection = Mat4::orthographic(-2.0, 4.0, -1.0, 3.0, 1.0, 11.0);
+        assert_vec3_eq(
+            projection.transform_point(Vec3::new(-2.0, -1.0, -1.0)),
+            Vec3::new(-1.0, -1.0, -1.0),
+        );
+        assert_vec3_eq(
+            projection.transform_point(Vec3::new(4.0, 3.0, -11.0)),
+            Vec3::new(1.0, 1.0, 1.0),
+        );
+    }
+
+    #[test]
+    fn normal_matrix_keeps_normals_perpendicular() {
+        let mut rng = Rng::new(23);
+        for _ in 0..200 {
+            let model = random_affine(&mut rng);
+            let normal_matrix = Mat3::normal_matrix(&model);
+            // Build a tangent lying in the plane with normal `n`
+            let n = rng.vec3(1.0).normalize();
+            let tangent = n.cross(rng.vec3(1.0)).normalize();
+            if tangent.length() < 0.5 {
+                continue;
+            }
+            let transformed_normal = normal_matrix * n;
+            let transformed_tangent = model.transform_vector(tangent);
+            assert!(transformed_normal.dot(transformed_tangent).abs() < EPSILON * 100.0);
+        }
+    }
+
+    #[test]
+    fn singular_matrices_have_no_inverse() {
+        assert!(Mat4::scale(Vec3::new(1.0, 0.0, 1.0)).inverse().is_none());
+        assert!(
+            Mat3::new([[1.0, 2.0, 3.0], [2.0, 4.0, 6.0], [0.0, 1.0, 0.0]])
+                .inverse()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn inverse_undoes_random_affine_transforms() {
+        let mut rng = Rng::new(5);
+        for _ in 0..200 {
+            let m = random_affine(&mut rng);
+            let inverse = m.inverse().expect("affine transform should be invertible");
+            assert_mat4_eq(&(m * inverse), &Mat4::IDENTITY);
+            assert_mat4_eq(&(inverse * m), &Mat4::IDENTITY);
+
+            let m3 = Mat3::from_mat4(&m);
+            let inverse3 = m3.inverse().expect("linear part should be invertible");
+            let v = rng.vec3(3.0);
+            assert_vec3_eq(inverse3 * (m3 * v), v);
+        }
+    }
+
+    #[test]
+    fn transpose_properties() {
+        let mut rng = Rng::new(13);
+        for _ in 0..200 {
+            let (a, b) = (random_affine(&mut rng), random_affine(&mut rng));
+            assert_eq!(a.transpose().transpose(), a);
+            assert_mat4_eq(&(a * b).transpose(), &(b.transpose() * a.transpose()));
+            assert!((a.determinant() - a.transpose().determinant()).abs() < EPSILON * 100.0);
+            assert!(
+                (Mat3::from_mat4(&a).determinant() - Mat3::from_mat4(&a).transpose().determinant())
+                    .abs()
+                    < EPSILON * 100.0
+            );
+        }
+    }
+
+    #[test]
+    fn determinant_is_multiplicative() {
+        let mut rng = Rng::new(17);
+        for _ in 0..200 {
+            let (a, b) = (random_affine(&mut rng), random_affine(&mut rng));
+            let expected = a.determinant() * b.determinant();
+            assert!(((a * b).determinant() - expected).abs() < expected.abs() * 1e-3);
+        }
+    }
 }
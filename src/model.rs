@@ -1,6 +1,8 @@
+use crate::quaternion::Quat;
+use crate::vectors::Vec3;
+
 pub struct ModelRotation {
-    pub x_angle: f32,
-    pub y_angle: f32,
+    pub orientation: Quat,
 }
 
 pub struct ModelPosition {
@@ -11,11 +13,36 @@ pub struct ModelPosition {
 
 impl ModelRotation {
     pub fn new() -> Self {
+        ModelRotation::from_euler(0.0, 0.0, 0.0)
+    }
+
+    /// Builds an orientation from X (pitch), Y (yaw) and Z (roll) angles in
+    /// radians, applied in Z, then X, then Y order.
+    pub fn from_euler(pitch: f32, yaw: f32, roll: f32) -> Self {
+        let x = Quat::from_axis_angle(Vec3::X, pitch);
+        let y = Quat::from_axis_angle(Vec3::Y, yaw);
+        let z = Quat::from_axis_angle(Vec3::Z, roll);
         ModelRotation {
-            x_angle: 0.0,
-            y_angle: 0.0,
+            orientation: y * x * z,
         }
     }
+
+    /// Returns `[pitch, yaw, roll]` in radians, matching `from_euler`.
+    pub fn euler_angles(&self) -> [f32; 3] {
+        let Quat { x, y, z, w } = self.orientation;
+        let sin_pitch = (2.0 * (w * x - y * z)).clamp(-1.0, 1.0);
+        let pitch = sin_pitch.asin();
+        let yaw = (2.0 * (x * z + w * y)).atan2(1.0 - 2.0 * (x * x + y * y));
+        let roll = (2.0 * (x * y + w * z)).atan2(1.0 - 2.0 * (x * x + z * z));
+        [pitch, yaw, roll]
+    }
+
+    /// Rotates around a world-space axis, so keys behave the same whatever the
+    /// current orientation is.
+    pub fn rotate(&mut self, axis: Vec3, angle: f32) {
+        let rotation = Quat::from_axis_angle(axis, angle);
+        self.orientation = (rotation * self.orientation).normalize();
+    }
 }
 
 impl ModelPosition {
@@ -28,22 +55,15 @@ impl ModelPosition {
     }
 }
 
-pub fn calculate_model_center(vertices: &[[f32; 3]]) -> [f32; 3] {
-    let mut min = [f32::MAX, f32::MAX, f32::MAX];
-    let mut max = [f32::MIN, f32::MIN, f32::MIN];
+pub fn calculate_model_center(vertices: &[[f32; 3]]) -> Vec3 {
+    let mut min = Vec3::splat(f32::MAX);
+    let mut max = Vec3::splat(f32::MIN);
 
-    for vertex in vertices {
-        for i in 0..3 {
-            min[i] = min[i].min(vertex[i]);
-            max[i] = max[i].max(vertex[i]);
-        }
+    for &vertex in vertices {
+        let vertex = Vec3::from(vertex);
+        min = min.min(vertex);
+        max = max.max(vertex);
     }
 
-    let center = [
-        (min[0] + max[0]) / 2.0,
-        (min[1] + max[1]) / 2.0,
-        min[2] + (max[2] - min[2]) * 0.5,
-    ];
-
-    center
+    (min + max) / 2.0
 }
@@ -0,0 +1,137 @@
+use crate::texture::Image;
+
+/// Whether `bytes` start with the magic number of a PGM or PPM file, plain
+/// (`P2`, `P3`) or raw (`P5`, `P6`).
+pub fn is_netpbm(bytes: &[u8]) -> bool {
+    matches!(
+        bytes,
+        [b'P', b'2' | b'3' | b'5' | b'6', next, ..] if next.is_ascii_whitespace()
+    )
+}
+
+/// Reads the whitespace separated header fields, skipping `#` comments.
+struct Header<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl Header<'_> {
+    fn skip_whitespace_and_comments(&mut self) {
+        while let Some(&byte) = self.bytes.get(self.offset) {
+            if byte == b'#' {
+                while self.bytes.get(self.offset).is_some_and(|b| *b != b'\n') {
+                    self.offset += 1;
+                }
+            } else if byte.is_ascii_whitespace() {
+                self.offset += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn number(&mut self, what: &str) -> Result<u32, String> {
+        self.skip_whitespace_and_comments();
+        let start = self.offset;
+        while self.bytes.get(self.offset).is_some_and(u8::is_ascii_digit) {
+            self.offset += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.offset])
+            .ok()
+            .and_then(|digits| digits.parse().ok())
+            .ok_or_else(|| format!("Netpbm {} is missing or malformed", what))
+    }
+}
+
+/// Decodes a plain or raw PGM (grayscale) or PPM (RGB) image with any
+/// maximum value up to 65535.
+pub fn decode(bytes: &[u8]) -> Result<Image, String> {
+    if !is_netpbm(bytes) {
+        return Err("not a PGM or PPM file".to_string());
+    }
+    let plain = matches!(bytes[1], b'2' | b'3');
+    let channels = if matches!(bytes[1], b'2' | b'5') {
+        1
+    } else {
+        3
+    };
+    let mut header = Header { bytes, offset: 2 };
+    let width = header.number("width")? as usize;
+    let height = header.number("height")? as usize;
+    let max_value = header.number("maximum value")?;
+    if !(1..=65535).contains(&max_value) {
+        return Err(format!("invalid Netpbm maximum value {}", max_value));
+    }
+    let mut image = Image::new(width, height)?;
+    let count = width * height * channels;
+
+    let samples: Vec<u32> = if plain {
+        (0..count)
+            .map(|_| header.number("sample"))
+            .collect::<Result<_, _>>()?
+    } else {
+        // A single whitespace character separates the header from the data
+        let start = header.offset + 1;
+        let sample_size = if max_value > 255 { 2 } else { 1 };
+        let data = bytes
+            .get(start..)
+            .and_then(|data| data.get(..count * sample_size))
+            .ok_or_else(|| "Netpbm pixel data is truncated".to_string())?;
+        if sample_size == 2 {
+            data.chunks_exact(2)
+                .map(|s| u16::from_be_bytes([s[0], s[1]]) as u32)
+                .collect()
+        } else {
+            data.iter().map(|&s| s as u32).collect()
+        }
+    };
+    if let Some(sample) = samples.iter().find(|&&sample| sample > max_value) {
+        return Err(format!(
+            "Netpbm sample {} is above the maximum value {}",
+            sample, max_value
+        ));
+    }
+
+    let scale = |sample: u32| (sample * 255 / max_value) as u8;
+    for (i, pixel) in samples.chunks_exact(channels).enumerate() {
+        let color = match *pixel {
+            [gray] => [scale(gray), scale(gray), scale(gray), 255],
+            [r, g, b] => [scale(r), scale(g), scale(b), 255],
+            _ => unreachable!("Netpbm pixels have 1 or 3 samples"),
+        };
+        image.set_pixel(i % width, i / width, color);
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_and_raw_images_are_read() {
+        let image = decode(b"P3\n# a comment\n2 1 # size\n15\n15 0 0  0 15 5\n").unwrap();
+        assert_eq!(image.pixel(0, 0), [255, 0, 0, 255]);
+        assert_eq!(image.pixel(1, 0), [0, 255, 85, 255]);
+        let image = decode(b"P2 1 2 4 0 4").unwrap();
+        assert_eq!(image.pixel(0, 1), [255, 255, 255, 255]);
+
+        // The first data byte here is a newline, not separating whitespace
+        let image = decode(b"P5 2 1 255\n\n\x80").unwrap();
+        assert_eq!(image.pixel(0, 0), [10, 10, 10, 255]);
+        assert_eq!(image.pixel(1, 0), [128, 128, 128, 255]);
+        let image = decode(b"P6\n1 1\n65535\n\xff\xff\x80\x00\x00\x00").unwrap();
+        assert_eq!(image.pixel(0, 0), [255, 127, 0, 255]);
+    }
+
+    #[test]
+    fn malformed_files_are_rejected() {
+        assert!(!is_netpbm(b"P1\n1 1\n0"));
+        assert!(!is_netpbm(b"P61 1 255"));
+        assert!(decode(b"P2 2 1 255 0").is_err());
+        assert!(decode(b"P2 1 1 15 16").is_err());
+        assert!(decode(b"P5 2 1 255\n\x00").is_err());
+        assert!(decode(b"P6 1 1 0\n\x00\x00\x00").is_err());
+        assert!(decode(b"P3 x 1 255 0 0 0").is_err());
+    }
+}
@@ -0,0 +1,221 @@
+use crate::vectors::Vec3;
+use std::collections::HashMap;
+
+/// Smoothing group of faces that never share normals (`s off`).
+pub const FLAT: u32 = 0;
+
+/// How much each face contributes to the normals of its corners.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Weighting {
+    /// Every face counts the same.
+    #[default]
+    Uniform,
+    /// Large faces count more than small ones.
+    Area,
+    /// Faces count by the angle they span at the corner, which doesn't depend
+    /// on how a surface was triangulated.
+    Angle,
+}
+
+impl Weighting {
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "uniform" => Ok(Weighting::Uniform),
+            "area" => Ok(Weighting::Area),
+            "angle" => Ok(Weighting::Angle),
+            _ => Err(format!(
+                "Unknown normal weighting '{}' (expected 'uniform', 'area' or 'angle')",
+                name
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct NormalOptions {
+    /// Faces meeting at a sharper angle than this (in degrees) keep separate
+    /// normals even within a smoothing group.
+    pub crease_angle: Option<f32>,
+    pub weighting: Weighting,
+}
+
+/// Normals for every triangle corner, deduplicated.
+#[derive(Debug)]
+pub struct GeneratedNormals {
+    pub normals: Vec<[f32; 3]>,
+    /// For each triangle, the index into `normals` of each corner.
+    pub indices: Vec<[u32; 3]>,
+}
+
+/// Generates per-corner normals for `triangles` (indices into `positions`).
+/// A corner averages the faces around its position that share its triangle's
+/// smoothing group and, with a crease angle, aren't too steep relative to it.
+pub fn generate(
+    positions: &[[f32; 3]],
+    triangles: &[[u32; 3]],
+    smoothing_groups: &[u32],
+    options: &NormalOptions,
+) -> GeneratedNormals {
+    let corner = |triangle: &[u32; 3], i: usize| Vec3::from(positions[triangle[i] as usize]);
+    // Twice the triangle's area long, which is what area weighting needs
+    let face_normals: Vec<Vec3> = triangles
+        .iter()
+        .map(|t| (corner(t, 1) - corner(t, 0)).cross(corner(t, 2) - corner(t, 0)))
+        .collect();
+    let unit_normals: Vec<Vec3> = face_normals.iter().map(|n| n.normalize()).collect();
+
+    let mut adjacent: Vec<Vec<(usize, usize)>> = vec![Vec::new(); positions.len()];
+    for (index, triangle) in triangles.iter().enumerate() {
+        for (i, &position) in triangle.iter().enumerate() {
+            adjacent[position as usize].push((index, i));
+        }
+    }
+
+    let min_cosine = options.crease_angle.map(|angle| angle.to_radians().cos());
+    let smooths_with = |a: usize, b: usize| {
+        a == b
+            || (smoothing_groups[a] != FLAT
+                && smoothing_groups[a] == smoothing_groups[b]
+                && min_cosine.is_none_or(|min| unit_normals[a].dot(unit_normals[b]) >= min))
+    };
+    let weighted = |index: usize, i: usize| match options.weighting {
+        Weighting::Uniform => unit_normals[index],
+        Weighting::Area => face_normals[index],
+        Weighting::Angle => {
+            let t = &triangles[index];
+            let origin = corner(t, i);
+            let to_next = (corner(t, (i + 1) % 3) - origin).normalize();
+            let to_prev = (corner(t, (i + 2) % 3) - origin).normalize();
+            unit_normals[index] * to_next.dot(to_prev).clamp(-1.0, 1.0).acos()
+        }
+    };
+
+    let mut unique: HashMap<[u32; 3], u32> = HashMap::new();
+    let mut normals = Vec::new();
+    let indices = triangles
+        .iter()
+        .enumerate()
+        .map(|(index, triangle)| {
+            [0, 1, 2].map(|i| {
+                let sum = adjacent[triangle[i] as usize]
+                    .iter()
+                    .filter(|&&(other, _)| smooths_with(index, other))
+                    .fold(Vec3::ZERO, |sum, &(other, j)| sum + weighted(other, j));
+                let normal = if sum.length() > f32::EPSILON {
+                    sum.normalize()
+                } else if unit_normals[index].length() > 0.0 {
+                    unit_normals[index]
+                } else {
+                    Vec3::Y // degenerate triangle
+                };
+                *unique
+                    .entry(normal.to_array().map(f32::to_bits))
+                    .or_insert_with(|| {
+                        normals.push(normal.to_array());
+                        (normals.len() - 1) as u32
+                    })
+            })
+        })
+        .collect();
+
+    GeneratedNormals { normals, indices }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vectors::tests::assert_vec3_eq;
+
+    /// Two faces of a unit cube meeting at a right angle along the z axis.
+    const POSITIONS: [[f32; 3]; 6] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0],
+        [1.0, 0.0, 1.0],
+        [0.0, 1.0, 0.0],
+        [0.0, 1.0, 1.0],
+    ];
+    /// Bottom (normal -y) and side (normal -x), sharing the edge 0-2.
+    const TRIANGLES: [[u32; 3]; 4] = [[0, 1, 2], [1, 3, 2], [0, 2, 4], [2, 5, 4]];
+
+    fn normal_at(generated: &GeneratedNormals, triangle: usize, corner: usize) -> Vec3 {
+        Vec3::from(generated.normals[generated.indices[triangle][corner] as usize])
+    }
+
+    #[test]
+    fn shared_edges_are_smoothed_within_a_group() {
+        let generated = generate(&POSITIONS, &TRIANGLES, &[1; 4], &NormalOptions::default());
+        let diagonal = Vec3::new(-1.0, -1.0, 0.0).normalize();
+        assert_vec3_eq(normal_at(&generated, 0, 0), diagonal);
+        assert_vec3_eq(normal_at(&generated, 2, 0), diagonal);
+        // Corners away from the shared edge only see their own face
+        assert_vec3_eq(normal_at(&generated, 1, 1), Vec3::new(0.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn flat_faces_and_other_groups_keep_their_own_normals() {
+        for groups in [[FLAT; 4], [1, 1, 2, 2]] {
+            let generated = generate(&POSITIONS, &TRIANGLES, &groups, &NormalOptions::default());
+            assert_vec3_eq(normal_at(&generated, 0, 0), Vec3::new(0.0, -1.0, 0.0));
+            assert_vec3_eq(normal_at(&generated, 2, 0), Vec3::new(-1.0, 0.0, 0.0));
+            // Only the two face normals remain after deduplication
+            assert_eq!(generated.normals.len(), 2);
+        }
+    }
+
+    #[test]
+    fn crease_angle_splits_sharp_edges() {
+        let options = |angle| NormalOptions {
+            crease_angle: Some(angle),
+            ..Default::default()
+        };
+        let sharp = generate(&POSITIONS, &TRIANGLES, &[1; 4], &options(60.0));
+        assert_vec3_eq(normal_at(&sharp, 0, 0), Vec3::new(0.0, -1.0, 0.0));
+        let smooth = generate(&POSITIONS, &TRIANGLES, &[1; 4], &options(100.0));
+        assert_vec3_eq(
+            normal_at(&smooth, 0, 0),
+            Vec3::new(-1.0, -1.0, 0.0).normalize(),
+        );
+    }
+
+    #[test]
+    fn weighting_changes_the_balance_between_faces() {
+        // At position 0 the bottom face has one triangle spanning 90 degrees,
+        // the side face two triangles spanning 45 degrees each
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0],
+            [0.0, 1.0, 1.0],
+            [0.0, 1.0, 0.0],
+        ];
+        let triangles = [[0, 1, 2], [0, 2, 3], [0, 3, 4]];
+        let normal = |weighting| {
+            let options = NormalOptions {
+                weighting,
+                ..Default::default()
+            };
+            normal_at(&generate(&positions, &triangles, &[1; 3], &options), 0, 0)
+        };
+
+        assert_vec3_eq(
+            normal(Weighting::Uniform),
+            Vec3::new(-2.0, -1.0, 0.0).normalize(),
+        );
+        assert_vec3_eq(
+            normal(Weighting::Angle),
+            Vec3::new(-1.0, -1.0, 0.0).normalize(),
+        );
+        // Side triangles have the same area as the bottom one
+        assert_vec3_eq(
+            normal(Weighting::Area),
+            Vec3::new(-2.0, -1.0, 0.0).normalize(),
+        );
+    }
+
+    #[test]
+    fn weighting_names_are_parsed() {
+        assert_eq!(Weighting::from_name("angle"), Ok(Weighting::Angle));
+        assert!(Weighting::from_name("median").is_err());
+    }
+}
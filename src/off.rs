@@ -0,0 +1,413 @@
+use crate::material::Material;
+use crate::parser::{
+    self, FaceVertex, LineContext, MeshBuilder, ObjData, ObjError, ParseOptions, Token,
+};
+use std::collections::HashMap;
+
+/// What the `[ST][C][N]OFF` keyword says each vertex line holds besides its
+/// position.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct VertexLayout {
+    tex_coords: bool,
+    colors: bool,
+    normals: bool,
+}
+
+impl VertexLayout {
+    fn from_keyword(keyword: &str) -> Option<Self> {
+        let prefix = keyword.strip_suffix("OFF")?;
+        let (tex_coords, prefix) = match prefix.strip_prefix("ST") {
+            Some(rest) => (true, rest),
+            None => (false, prefix),
+        };
+        let (colors, prefix) = match prefix.strip_prefix('C') {
+            Some(rest) => (true, rest),
+            None => (false, prefix),
+        };
+        let (normals, prefix) = match prefix.strip_prefix('N') {
+            Some(rest) => (true, rest),
+            None => (false, prefix),
+        };
+        prefix.is_empty().then_some(VertexLayout {
+            tex_coords,
+            colors,
+            normals,
+        })
+    }
+}
+
+/// The words of a line before any `#` comment.
+fn words(line: &str) -> impl Iterator<Item = &str> {
+    line.split('#').next().unwrap_or("").split_whitespace()
+}
+
+fn without_comment<'a>(tokens: &'a [Token<'a>]) -> &'a [Token<'a>] {
+    let end = tokens
+        .iter()
+        .position(|t| t.text.starts_with('#'))
+        .unwrap_or(tokens.len());
+    &tokens[..end]
+}
+
+/// OFF files open with a keyword such as `OFF` or `COFF`, possibly after
+/// comments.
+pub fn is_off(bytes: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(&bytes[..bytes.len().min(1024)]);
+    let keyword = text.lines().flat_map(words).next();
+    keyword.is_some_and(|keyword| {
+        VertexLayout::from_keyword(keyword).is_some() || matches!(keyword, "4OFF" | "nOFF")
+    })
+}
+
+struct Header {
+    layout: VertexLayout,
+    vertex_count: usize,
+    face_count: usize,
+    /// Lines up to and including the one with the counts.
+    line_count: usize,
+}
+
+impl Header {
+    /// Reads the keyword and the vertex, face and edge counts, which may
+    /// share its line. Without them nothing else can be read, so problems
+    /// here are errors even when parsing leniently.
+    fn parse(text: &str, file_name: &str) -> Result<Self, ObjError> {
+        let invalid = |message: String| ObjError::InvalidData {
+            file: file_name.to_string(),
+            message,
+        };
+        let mut layout = None;
+        let mut counts = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let mut words = words(line).peekable();
+            if layout.is_none() {
+                let Some(keyword) = words.next() else {
+                    continue;
+                };
+                layout = Some(VertexLayout::from_keyword(keyword).ok_or_else(|| {
+                    invalid(format!("'{}' is not a supported OFF keyword", keyword))
+                })?);
+                if words.peek() == Some(&"BINARY") {
+                    return Err(invalid("binary OFF files are not supported".to_string()));
+                }
+            }
+            for word in words {
+                counts.push(word.parse::<usize>().map_err(|_| {
+                    invalid(format!("line {}: malformed count '{}'", index + 1, word))
+                })?);
+            }
+            // The edge count is often left out, and never needed
+            if counts.len() >= 2 {
+                return Ok(Header {
+                    layout: layout.unwrap(),
+                    vertex_count: counts[0],
+                    face_count: counts[1],
+                    line_count: index + 1,
+                });
+            }
+        }
+        Err(invalid(
+            "missing OFF keyword or vertex and face counts".to_string(),
+        ))
+    }
+}
+
+pub fn parse(path: &str, options: &ParseOptions) -> Result<ObjData, ObjError> {
+    let bytes = parser::read_file(path)?;
+    parse_bytes(&bytes, path, options)
+}
+
+/// Parses an OFF file and its `ST`, `C` and `N` variants. Polygons of any
+/// size are triangulated. Vertex colors become vertex colors; face colors
+/// become one material per distinct color.
+pub fn parse_bytes(
+    bytes: &[u8],
+    file_name: &str,
+    options: &ParseOptions,
+) -> Result<ObjData, ObjError> {
+    let text = String::from_utf8_lossy(bytes);
+    let header = Header::parse(&text, file_name)?;
+    let mut builder = OffBuilder {
+        layout: header.layout,
+        mesh: MeshBuilder::default(),
+        part: 0,
+        face_materials: HashMap::new(),
+    };
+    builder.part = builder.mesh.part("default");
+    let mut warnings = Vec::new();
+
+    let mut line_number = 0;
+    let mut vertices_left = header.vertex_count;
+    let mut faces_left = header.face_count;
+    parser::read_lines(
+        text.as_bytes(),
+        file_name,
+        options,
+        &mut warnings,
+        |line, _| {
+            line_number += 1;
+            let tokens = line.tokens();
+            let tokens = without_comment(&tokens);
+            if line_number <= header.line_count || tokens.is_empty() {
+                return Ok(());
+            }
+            if vertices_left > 0 {
+                vertices_left -= 1;
+                builder.add_vertex(tokens, line)
+            } else if faces_left > 0 {
+                faces_left -= 1;
+                builder.add_face(tokens, line)
+            } else {
+                // Edges, which only matter to other tools
+                Ok(())
+            }
+        },
+    )?;
+
+    if vertices_left > 0 || faces_left > 0 {
+        let error = ObjError::InvalidData {
+            file: file_name.to_string(),
+            message: format!(
+                "ends with {} vertices and {} faces missing",
+                vertices_left, faces_left
+            ),
+        };
+        parser::report(error, options, &mut warnings)?;
+    }
+    Ok(builder.mesh.finish(&options.normals, warnings))
+}
+
+#[derive(Debug, Clone, Default)]
+struct Vertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+    color: Option<[f32; 4]>,
+    tex_coord: [f32; 2],
+}
+
+struct OffBuilder {
+    layout: VertexLayout,
+    mesh: MeshBuilder,
+    part: usize,
+    /// Material index by the bits of a face color.
+    face_materials: HashMap<[u32; 4], usize>,
+}
+
+impl OffBuilder {
+    /// `x y z [nx ny nz] [r g b [a]] [s t]`, as the keyword says.
+    fn parse_vertex(&self, tokens: &[Token], line: &LineContext) -> Result<Vertex, ObjError> {
+        let layout = self.layout;
+        let normal_len = if layout.normals { 3 } else { 0 };
+        let tex_coord_len = if layout.tex_coords { 2 } else { 0 };
+        let color_len = tokens.len().wrapping_sub(3 + normal_len + tex_coord_len);
+        if !matches!((layout.colors, color_len), (true, 3 | 4) | (false, 0)) {
+            return Err(line.malformed_statement(tokens[0].column));
+        }
+
+        let mut vertex = Vertex {
+            position: parser::parse_floats(tokens, line)?,
+            ..Default::default()
+        };
+        let rest = &tokens[3..];
+        if layout.normals {
+            vertex.normal = parser::parse_floats(rest, line)?;
+        }
+        let rest = &rest[normal_len..];
+        if layout.colors {
+            vertex.color = parse_color(&rest[..color_len], line)?;
+        }
+        if layout.tex_coords {
+            vertex.tex_coord = parser::parse_floats(&rest[color_len..], line)?;
+        }
+        Ok(vertex)
+    }
+
+    /// Adds a vertex, or a placeholder if the line is bad so later faces
+    /// still refer to the right vertices.
+    fn add_vertex(&mut self, tokens: &[Token], line: &LineContext) -> Result<(), ObjError> {
+        let vertex = self.parse_vertex(tokens, line);
+        let Vertex {
+            position,
+            normal,
+            color,
+            tex_coord,
+        } = vertex.as_ref().cloned().unwrap_or_default();
+        self.mesh
+            .add_vertex(position, color.map(|c| [c[0], c[1], c[2]]));
+        if self.layout.normals {
+            self.mesh.normals.push(normal);
+        }
+        if self.layout.tex_coords {
+            self.mesh.tex_coords.push(tex_coord);
+        }
+        vertex.map(|_| ())
+    }
+
+    /// `n i1 ... in [color]`, with 0-based indices.
+    fn add_face(&mut self, tokens: &[Token], line: &LineContext) -> Result<(), ObjError> {
+        let (count, rest) = tokens.split_first().unwrap();
+        let count: usize = count.text.parse().map_err(|_| ObjError::MalformedNumber {
+            location: line.at(count.column),
+            text: count.text.to_string(),
+        })?;
+        if rest.len() < count {
+            return Err(line.malformed_statement(tokens[0].column));
+        }
+        let (indices, color_tokens) = rest.split_at(count);
+
+        let vertex_count = self.mesh.vertices.len();
+        let corners = indices
+            .iter()
+            .map(|token| {
+                let position =
+                    token
+                        .text
+                        .parse::<usize>()
+                        .map_err(|_| ObjError::MalformedNumber {
+                            location: line.at(token.column),
+                            text: token.text.to_string(),
+                        })?;
+                if position >= vertex_count {
+                    return Err(ObjError::IndexOutOfRange {
+                        location: line.at(token.column),
+                        text: token.text.to_string(),
+                        count: vertex_count,
+                    });
+                }
+                let position = position as u32;
+                Ok(FaceVertex {
+                    position,
+                    tex_coord: self.layout.tex_coords.then_some(position),
+                    normal: self.layout.normals.then_some(position),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // A single value indexes a color map, which isn't supported
+        let material = match color_tokens.len() {
+            0 | 1 => None,
+            3 | 4 => parse_color(color_tokens, line)?.map(|color| self.face_material(color)),
+            _ => return Err(line.malformed_statement(color_tokens[0].column)),
+        };
+        self.mesh.add_face(
+            &corners,
+            self.part,
+            material,
+            parser::DEFAULT_SMOOTHING_GROUP,
+        );
+        Ok(())
+    }
+
+    fn face_material(&mut self, color: [f32; 4]) -> usize {
+        let materials = &mut self.mesh.materials;
+        *self
+            .face_materials
+            .entry(color.map(f32::to_bits))
+            .or_insert_with(|| {
+                let mut material = Material::new(&format!("face color {}", materials.len() + 1));
+                material.diffuse = [color[0], color[1], color[2]];
+                material.ambient = material.diffuse;
+                material.opacity = color[3];
+                materials.push(material);
+                materials.len() - 1
+            })
+    }
+}
+
+/// Three or four channels from 0 to 1, or from 0 to 255 if any is above 1.
+/// Returns `None` for no channels.
+fn parse_color(tokens: &[Token], line: &LineContext) -> Result<Option<[f32; 4]>, ObjError> {
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+    let mut color = [1.0; 4];
+    let values = parser::parse_floats::<4>(tokens, line)?;
+    color[..tokens.len()].copy_from_slice(&values[..tokens.len()]);
+    if color[..tokens.len()].iter().any(|&channel| channel > 1.0) {
+        for channel in &mut color[..tokens.len()] {
+            *channel /= 255.0;
+        }
+    }
+    Ok(Some(color))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A cube with two faces colored alike and one with a polygon of its own.
+    const CUBE: &str = "OFF
+# cube
+8 6 12
+0 0 0
+1 0 0
+1 1 0
+0 1 0
+0 0 1
+1 0 1
+1 1 1
+0 1 1
+4 0 3 2 1 255 0 0
+4 4 5 6 7 255 0 0
+4 0 1 5 4 0.0 0.5 1.0 0.5
+4 2 3 7 6
+4 1 2 6 5  # right
+4 0 4 7 3
+";
+
+    #[test]
+    fn face_colors_become_materials() {
+        let obj = parse_bytes(CUBE.as_bytes(), "cube.off", &ParseOptions::strict()).unwrap();
+        assert_eq!(obj.vertices.len(), 8);
+        assert_eq!(obj.faces.len(), 12);
+        assert_eq!(obj.face_stats.triangulated, 6);
+        assert_eq!(obj.materials.len(), 2);
+        assert_eq!(obj.materials[0].diffuse, [1.0, 0.0, 0.0]);
+        assert_eq!(obj.materials[1].opacity, 0.5);
+        // Red, blue and uncolored faces
+        assert_eq!(obj.material_ranges.len(), 3);
+        assert!(obj.vertex_colors.is_empty());
+    }
+
+    #[test]
+    fn vertex_attributes_follow_the_keyword() {
+        let source = "# triangle\nSTCNOFF 3 1 0\n\
+                      0 0 0 0 0 1 1 0 0 0 0\n\
+                      1 0 0 0 0 1 0 1 0 1 0\n\
+                      0 1 0 0 0 1 0 0 255 0 1\n\
+                      3 0 1 2\n";
+        let obj = parse_bytes(source.as_bytes(), "triangle.off", &ParseOptions::strict()).unwrap();
+        assert_eq!(obj.faces.len(), 1);
+        assert_eq!(obj.vertex_colors[2], [0.0, 0.0, 1.0]);
+        assert_eq!(obj.vertex_colors[1], [0.0, 1.0, 0.0]);
+        assert_eq!(obj.tex_coords[1], [1.0, 0.0]);
+        assert_eq!(obj.normals, [[0.0, 0.0, 1.0]; 3]);
+        assert!(is_off(source.as_bytes()));
+    }
+
+    #[test]
+    fn bad_lines_are_reported() {
+        let source = CUBE
+            .replace("1 1 0\n", "1 x 0\n")
+            .replace("4 2 3 7 6", "4 2 3 7 8");
+        match parse_bytes(source.as_bytes(), "bad.off", &ParseOptions::strict()) {
+            Err(ObjError::MalformedNumber { location, text }) => {
+                assert_eq!((location.line, location.column), (6, 3));
+                assert_eq!(text, "x");
+            }
+            other => panic!("unexpected result: {:?}", other.map(|o| o.faces.len())),
+        }
+
+        let obj = parse_bytes(source.as_bytes(), "bad.off", &ParseOptions::default()).unwrap();
+        assert_eq!(obj.vertices.len(), 8);
+        assert_eq!(obj.faces.len(), 10);
+        assert!(matches!(
+            obj.warnings[1],
+            ObjError::IndexOutOfRange { count: 8, .. }
+        ));
+
+        let truncated = &CUBE[..CUBE.find("4 0 4 7 3").unwrap()];
+        assert!(parse_bytes(truncated.as_bytes(), "short.off", &ParseOptions::strict()).is_err());
+        assert!(parse_bytes(b"OFF\n", "empty.off", &ParseOptions::default()).is_err());
+        assert!(parse_bytes(b"4OFF\n1 0 0\n", "4d.off", &ParseOptions::default()).is_err());
+    }
+}
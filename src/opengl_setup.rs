@@ -1,36 +1,74 @@
 use crate::parser::ObjData;
-use gl;
+use std::collections::HashMap;
 use std::mem;
 use std::os::raw::c_void;
 use std::ptr;
 
-pub fn setup_opengl_buffers(obj_data: &ObjData) -> (gl::types::GLuint, gl::types::GLuint) {
-    let mut vbo: gl::types::GLuint = 0;
-    let mut vao: gl::types::GLuint = 0;
-    let mut ebo: gl::types::GLuint = 0;
+/// Floats per interleaved vertex: position (3), texture coordinates (2),
+/// normal (3), color (3).
+const VERTEX_STRIDE: usize = 11;
 
-    // Create flat index array from faces
-    let indices: Vec<u32> = obj_data
-        .faces
-        .iter()
-        .flat_map(|face| face.vertex_indices.iter().cloned())
-        .collect();
+/// Color of every vertex in models without vertex colors.
+const DEFAULT_COLOR: [f32; 3] = [1.0; 3];
 
-    // Interleave vertex data
+/// Builds the interleaved vertex buffer and its index buffer. Each distinct
+/// position/texture/normal combination becomes one GPU vertex, shared by every
+/// corner that uses it. Triangle indices come first, followed by those of
+/// `obj_data.lines` (two per segment) and `obj_data.points`.
+pub fn build_vertex_buffer(obj_data: &ObjData) -> (Vec<f32>, Vec<u32>) {
+    let mut unique: HashMap<(u32, u32, u32), u32> = HashMap::new();
     let mut interleaved_data = Vec::new();
-    for (i, vertex) in obj_data.vertices.iter().enumerate() {
-        // Position
-        interleaved_data.extend_from_slice(vertex);
+    let mut indices = Vec::with_capacity(obj_data.faces.len() * 3);
+    let color = |position: u32| {
+        obj_data
+            .vertex_colors
+            .get(position as usize)
+            .unwrap_or(&DEFAULT_COLOR)
+    };
 
-        // Texture coordinates (with default fallback)
-        let tex_coord = obj_data.tex_coords.get(i).unwrap_or(&[0.0, 0.0]);
-        interleaved_data.extend_from_slice(tex_coord);
+    for face in &obj_data.faces {
+        for corner in 0..3 {
+            let key = (
+                face.vertex_indices[corner],
+                face.tex_coord_indices[corner],
+                face.normal_indices[corner],
+            );
+            let index = *unique.entry(key).or_insert_with(|| {
+                interleaved_data.extend_from_slice(&obj_data.vertices[key.0 as usize]);
+                interleaved_data.extend_from_slice(&obj_data.tex_coords[key.1 as usize]);
+                interleaved_data.extend_from_slice(&obj_data.normals[key.2 as usize]);
+                interleaved_data.extend_from_slice(color(key.0));
+                (interleaved_data.len() / VERTEX_STRIDE - 1) as u32
+            });
+            indices.push(index);
+        }
+    }
 
-        // Normals (with default fallback)
-        let normal = obj_data.normals.get(i).unwrap_or(&[0.0, 1.0, 0.0]);
-        interleaved_data.extend_from_slice(normal);
+    // Lines and points are unlit and untextured, so only positions and colors matter
+    let mut primitive_vertices: HashMap<u32, u32> = HashMap::new();
+    let lines = obj_data.lines.iter().flatten();
+    for &position in lines.chain(&obj_data.points) {
+        let index = *primitive_vertices.entry(position).or_insert_with(|| {
+            interleaved_data.extend_from_slice(&obj_data.vertices[position as usize]);
+            interleaved_data.extend_from_slice(&[0.0; 5]);
+            interleaved_data.extend_from_slice(color(position));
+            (interleaved_data.len() / VERTEX_STRIDE - 1) as u32
+        });
+        indices.push(index);
     }
 
+    (interleaved_data, indices)
+}
+
+/// Uploads the buffers from `build_vertex_buffer`.
+pub fn setup_opengl_buffers(
+    interleaved_data: &[f32],
+    indices: &[u32],
+) -> (gl::types::GLuint, gl::types::GLuint) {
+    let mut vbo: gl::types::GLuint = 0;
+    let mut vao: gl::types::GLuint = 0;
+    let mut ebo: gl::types::GLuint = 0;
+
     unsafe {
         // Generate and bind VAO
         gl::GenVertexArrays(1, &mut vao);
@@ -41,22 +79,23 @@ pub fn setup_opengl_buffers(obj_data: &ObjData) -> (gl::types::GLuint, gl::types
         gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
         gl::BufferData(
             gl::ARRAY_BUFFER,
-            (interleaved_data.len() * mem::size_of::<f32>()) as gl::types::GLsizeiptr,
+            mem::size_of_val(interleaved_data) as gl::types::GLsizeiptr,
             interleaved_data.as_ptr() as *const c_void,
             gl::STATIC_DRAW,
         );
 
-        // Generate and bind EBO
+        // Generate and bind EBO; translucent triangles are re-sorted in it
+        // every frame
         gl::GenBuffers(1, &mut ebo);
         gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
         gl::BufferData(
             gl::ELEMENT_ARRAY_BUFFER,
-            (indices.len() * mem::size_of::<u32>()) as gl::types::GLsizeiptr,
+            mem::size_of_val(indices) as gl::types::GLsizeiptr,
             indices.as_ptr() as *const c_void,
-            gl::STATIC_DRAW,
+            gl::DYNAMIC_DRAW,
         );
 
-        let stride = 8 * mem::size_of::<f32>();
+        let stride = VERTEX_STRIDE * mem::size_of::<f32>();
 
         // Position attribute (3 floats)
         gl::VertexAttribPointer(
@@ -91,6 +130,17 @@ pub fn setup_opengl_buffers(obj_data: &ObjData) -> (gl::types::GLuint, gl::types
         );
         gl::EnableVertexAttribArray(2);
 
+        // Color attribute (3 floats)
+        gl::VertexAttribPointer(
+            3,
+            3,
+            gl::FLOAT,
+            gl::FALSE,
+            stride as gl::types::GLint,
+            (8 * mem::size_of::<f32>()) as *const c_void,
+        );
+        gl::EnableVertexAttribArray(3);
+
         gl::BindVertexArray(0);
         gl::BindBuffer(gl::ARRAY_BUFFER, 0);
         gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
@@ -98,3 +148,111 @@ pub fn setup_opengl_buffers(obj_data: &ObjData) -> (gl::types::GLuint, gl::types
 
     (vao, ebo)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Face;
+
+    fn quad(faces: Vec<Face>, tex_coords: usize, normals: usize) -> ObjData {
+        ObjData {
+            vertices: vec![
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [1.0, 1.0, 0.0],
+                [0.0, 1.0, 0.0],
+            ],
+            tex_coords: vec![[0.0, 0.0]; tex_coords],
+            normals: vec![[0.0, 0.0, 1.0]; normals],
+            faces,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn shared_corners_are_deduplicated() {
+        let obj = quad(
+            vec![
+                Face {
+                    vertex_indices: [0, 1, 2],
+                    tex_coord_indices: [0, 1, 2],
+                    normal_indices: [0, 0, 0],
+                },
+                Face {
+                    vertex_indices: [0, 2, 3],
+                    tex_coord_indices: [0, 2, 3],
+                    normal_indices: [0, 0, 0],
+                },
+            ],
+            4,
+            1,
+        );
+        let (data, indices) = build_vertex_buffer(&obj);
+        assert_eq!(data.len(), 4 * VERTEX_STRIDE);
+        assert_eq!(indices, vec![0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn same_position_with_different_attributes_is_split() {
+        let obj = quad(
+            vec![
+                Face {
+                    vertex_indices: [0, 1, 2],
+                    tex_coord_indices: [0, 0, 0],
+                    normal_indices: [0, 0, 0],
+                },
+                Face {
+                    vertex_indices: [0, 2, 3],
+                    tex_coord_indices: [1, 1, 1],
+                    normal_indices: [0, 0, 0],
+                },
+            ],
+            2,
+            1,
+        );
+        let (data, indices) = build_vertex_buffer(&obj);
+        assert_eq!(data.len(), 6 * VERTEX_STRIDE);
+        assert_eq!(indices, vec![0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn lines_and_points_follow_the_triangles() {
+        let mut obj = quad(
+            vec![Face {
+                vertex_indices: [0, 1, 2],
+                tex_coord_indices: [0, 0, 0],
+                normal_indices: [0, 0, 0],
+            }],
+            1,
+            1,
+        );
+        obj.lines = vec![[0, 3], [3, 2]];
+        obj.points = vec![3, 1];
+        let (data, indices) = build_vertex_buffer(&obj);
+        // Line and point vertices are shared with each other, not with faces
+        assert_eq!(data.len(), 7 * VERTEX_STRIDE);
+        assert_eq!(indices, vec![0, 1, 2, 3, 4, 4, 5, 4, 6]);
+        assert_eq!(
+            &data[4 * VERTEX_STRIDE..5 * VERTEX_STRIDE],
+            &[0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn vertex_colors_are_interleaved() {
+        let mut obj = quad(
+            vec![Face {
+                vertex_indices: [0, 1, 2],
+                tex_coord_indices: [0, 0, 0],
+                normal_indices: [0, 0, 0],
+            }],
+            1,
+            1,
+        );
+        obj.vertex_colors = vec![[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [1.0; 3]];
+        let (data, _) = build_vertex_buffer(&obj);
+        let color = |vertex: usize| &data[vertex * VERTEX_STRIDE + 8..(vertex + 1) * VERTEX_STRIDE];
+        assert_eq!(color(0), [1.0, 0.0, 0.0]);
+        assert_eq!(color(2), [0.0, 0.0, 1.0]);
+    }
+}
@@ -1,93 +1,798 @@
+use crate::material::{self, Material};
+use crate::normals::{self, NormalOptions};
+use crate::triangulation;
+use crate::vectors::Vec3;
+use std::fmt;
 use std::fs::File;
 use std::io::{self, BufRead};
+use std::path::Path;
+
+/// Where in a file a problem was found; line and column are 1-based.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
 
 #[derive(Debug)]
+pub enum ObjError {
+    Io {
+        file: String,
+        source: io::Error,
+    },
+    /// A value that should be a number (or index) but isn't.
+    MalformedNumber {
+        location: Location,
+        text: String,
+    },
+    /// A known statement with the wrong number of values.
+    MalformedStatement {
+        location: Location,
+        text: String,
+    },
+    /// A face referencing an element that doesn't exist.
+    IndexOutOfRange {
+        location: Location,
+        text: String,
+        count: usize,
+    },
+    UnsupportedStatement {
+        location: Location,
+        text: String,
+    },
+    /// `usemtl` naming a material no loaded library defines.
+    UnknownMaterial {
+        location: Location,
+        name: String,
+    },
+    /// Binary data, or a whole file, that doesn't follow its format.
+    InvalidData {
+        file: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io { file, source } => write!(f, "{}: {}", file, source),
+            ObjError::MalformedNumber { location, text } => {
+                write!(f, "{}: malformed number '{}'", location, text)
+            }
+            ObjError::MalformedStatement { location, text } => {
+                write!(f, "{}: malformed statement '{}'", location, text)
+            }
+            ObjError::IndexOutOfRange {
+                location,
+                text,
+                count,
+            } => write!(
+                f,
+                "{}: index '{}' out of range ({} defined so far)",
+                location, text, count
+            ),
+            ObjError::UnsupportedStatement { location, text } => {
+                write!(f, "{}: unsupported statement '{}'", location, text)
+            }
+            ObjError::UnknownMaterial { location, name } => {
+                write!(f, "{}: unknown material '{}'", location, name)
+            }
+            ObjError::InvalidData { file, message } => write!(f, "{}: {}", file, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ObjError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ParseOptions {
+    /// Fail on the first bad line instead of skipping it with a warning.
+    pub strict: bool,
+    /// How normals are generated for faces that don't reference any.
+    pub normals: NormalOptions,
+}
+
+impl ParseOptions {
+    /// Options that fail on the first problem.
+    pub fn strict() -> Self {
+        ParseOptions {
+            strict: true,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Default)]
 pub struct ObjData {
     pub vertices: Vec<[f32; 3]>,
+    /// Colors from `v x y z r g b`, one per vertex, or empty when the file
+    /// has none. Vertices written without a color are white.
+    pub vertex_colors: Vec<[f32; 3]>,
     pub tex_coords: Vec<[f32; 2]>,
     pub normals: Vec<[f32; 3]>,
+    /// Sorted so faces of a part, and then of a material, are contiguous.
     pub faces: Vec<Face>,
+    /// Materials from every `mtllib`, in definition order.
+    pub materials: Vec<Material>,
+    /// Named sub-meshes from `o` and `g`, in order of first use.
+    pub parts: Vec<Part>,
+    /// One run of `faces` per material of each part, in order of first use.
+    pub material_ranges: Vec<MaterialRange>,
+    /// Segments of `l` polylines, as position indices.
+    pub lines: Vec<[u32; 2]>,
+    /// Positions listed by `p` statements.
+    pub points: Vec<u32>,
+    pub face_stats: FaceStats,
+    /// Lines skipped in lenient mode.
+    pub warnings: Vec<ObjError>,
 }
 
+/// Consecutive faces following the same `o` or `g` statement; faces before
+/// any are in a part named "default".
+#[derive(Debug, Clone, PartialEq)]
+pub struct Part {
+    pub name: String,
+    pub first_face: usize,
+    pub face_count: usize,
+}
+
+/// Consecutive faces of one part drawn with the same material.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterialRange {
+    /// Index into `ObjData::parts`.
+    pub part: usize,
+    /// Index into `ObjData::materials`, `None` for faces without `usemtl`.
+    pub material: Option<usize>,
+    pub first_face: usize,
+    pub face_count: usize,
+}
+
+/// How polygon faces were turned into triangles while parsing.
+#[derive(Debug, Default)]
+pub struct FaceStats {
+    /// Faces with more than three corners that were split into triangles.
+    pub triangulated: usize,
+    /// Of those, faces ear clipping couldn't handle and which were fanned instead.
+    pub fan_fallbacks: usize,
+    /// Faces with fewer than three corners.
+    pub dropped: usize,
+}
+
+/// Triangle whose corners index positions, texture coordinates and normals
+/// independently, like OBJ `v/vt/vn` references. Every index is valid once
+/// parsing is done; missing attributes point at generated data.
 #[derive(Debug)]
 pub struct Face {
     pub vertex_indices: [u32; 3],
+    pub tex_coord_indices: [u32; 3],
+    pub normal_indices: [u32; 3],
 }
 
-impl ObjData {
-    fn calculate_face_normals(vertices: &[[f32; 3]], faces: &[Face]) -> Vec<[f32; 3]> {
-        let mut normals = vec![[0.0, 0.0, 0.0]; vertices.len()];
-        let mut normal_counts = vec![0; vertices.len()];
-
-        for face in faces {
-            let v0 = vertices[face.vertex_indices[0] as usize];
-            let v1 = vertices[face.vertex_indices[1] as usize];
-            let v2 = vertices[face.vertex_indices[2] as usize];
-
-            // Calculate face normal using cross product
-            let edge1 = [v1[0] - v0[0], v1[1] - v0[1], v1[2] - v0[2]];
-            let edge2 = [v2[0] - v0[0], v2[1] - v0[1], v2[2] - v0[2]];
-
-            // Cross product
-            let normal = [
-                edge1[1] * edge2[2] - edge1[2] * edge2[1],
-                edge1[2] * edge2[0] - edge1[0] * edge2[2],
-                edge1[0] * edge2[1] - edge1[1] * edge2[0],
-            ];
-
-            // Normalize
-            let length =
-                (normal[0] * normal[0] + normal[1] * normal[1] + normal[2] * normal[2]).sqrt();
-            let normalized = if length > 0.0 {
-                [normal[0] / length, normal[1] / length, normal[2] / length]
-            } else {
-                // Fallback for degenerate triangles
-                [0.0, 1.0, 0.0]
-            };
-
-            // Accumulate normals for each vertex
-            for &vertex_idx in &face.vertex_indices {
-                let idx = vertex_idx as usize;
-                normals[idx][0] += normalized[0];
-                normals[idx][1] += normalized[1];
-                normals[idx][2] += normalized[2];
-                normal_counts[idx] += 1;
-            }
-        }
-
-        // Average and renormalize the normals
-        for i in 0..normals.len() {
-            if normal_counts[i] > 0 {
-                let count = normal_counts[i] as f32;
-                normals[i][0] /= count;
-                normals[i][1] /= count;
-                normals[i][2] /= count;
-
-                // Renormalize
-                let length = (normals[i][0] * normals[i][0]
-                    + normals[i][1] * normals[i][1]
-                    + normals[i][2] * normals[i][2])
-                    .sqrt();
-                if length > 0.0 {
-                    normals[i][0] /= length;
-                    normals[i][1] /= length;
-                    normals[i][2] /= length;
+/// One face corner as written in the file: `v`, `v/vt`, `v//vn` or `v/vt/vn`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FaceVertex {
+    pub position: u32,
+    pub tex_coord: Option<u32>,
+    pub normal: Option<u32>,
+}
+
+impl FaceVertex {
+    /// A corner without texture coordinates or normal.
+    pub fn at(position: u32) -> Self {
+        FaceVertex {
+            position,
+            tex_coord: None,
+            normal: None,
+        }
+    }
+
+    /// Parses a corner, resolving each index against the number of elements
+    /// defined so far (`counts` = positions, texture coordinates, normals).
+    /// Dangling texture/normal references are returned separately so lenient
+    /// parsing can keep the face and fall back to generated attributes.
+    fn parse(
+        token: Token,
+        line: &LineContext,
+        counts: [usize; 3],
+        dangling: &mut Vec<ObjError>,
+    ) -> Result<Self, ObjError> {
+        let mut parts = token.text.split('/');
+        let mut next_index = |count: usize| match parts.next() {
+            None | Some("") => Ok(None),
+            Some(part) => resolve_index(part, token, line, count).map(Some),
+        };
+
+        let position = next_index(counts[0])?.ok_or_else(|| ObjError::MalformedNumber {
+            location: line.at(token.column),
+            text: token.text.to_string(),
+        })?;
+        let mut optional = |count: usize| match next_index(count) {
+            Err(error @ ObjError::IndexOutOfRange { .. }) => {
+                dangling.push(error);
+                Ok(None)
+            }
+            other => other,
+        };
+        let tex_coord = optional(counts[1])?;
+        let normal = optional(counts[2])?;
+        Ok(FaceVertex {
+            position,
+            tex_coord,
+            normal,
+        })
+    }
+}
+
+/// Parses the position of an `l` or `p` element, `v` or `v/vt`. Texture
+/// coordinates are ignored since lines and points are drawn untextured.
+fn parse_position(token: Token, line: &LineContext, count: usize) -> Result<u32, ObjError> {
+    let position = token.text.split('/').next().unwrap_or_default();
+    resolve_index(position, token, line, count)
+}
+
+/// Turns `text`, a 1-based OBJ index or a negative one counting back from
+/// the last element defined so far, into a 0-based index. Errors point at the
+/// whole `token` it is part of.
+fn resolve_index(
+    text: &str,
+    token: Token,
+    line: &LineContext,
+    count: usize,
+) -> Result<u32, ObjError> {
+    let index: i64 = text.parse().map_err(|_| ObjError::MalformedNumber {
+        location: line.at(token.column),
+        text: token.text.to_string(),
+    })?;
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        count as i64 + index // 0 resolves to `count`, which is out of range
+    };
+    if (0..count as i64).contains(&resolved) {
+        Ok(resolved as u32)
+    } else {
+        Err(ObjError::IndexOutOfRange {
+            location: line.at(token.column),
+            text: token.text.to_string(),
+            count,
+        })
+    }
+}
+
+/// A whitespace-separated word of a line and its 1-based column.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Token<'a> {
+    pub text: &'a str,
+    pub column: usize,
+}
+
+/// The line being parsed, used to build error locations.
+pub(crate) struct LineContext<'a> {
+    file: &'a str,
+    number: usize,
+    text: &'a str,
+}
+
+impl LineContext<'_> {
+    pub fn at(&self, column: usize) -> Location {
+        Location {
+            file: self.file.to_string(),
+            line: self.number,
+            column,
+        }
+    }
+
+    pub fn tokens(&self) -> Vec<Token<'_>> {
+        let start = self.text.as_ptr() as usize;
+        self.text
+            .split_whitespace()
+            .map(|text| Token {
+                text,
+                column: text.as_ptr() as usize - start + 1,
+            })
+            .collect()
+    }
+
+    pub fn malformed_statement(&self, column: usize) -> ObjError {
+        ObjError::MalformedStatement {
+            location: self.at(column),
+            text: self.text.trim().to_string(),
+        }
+    }
+}
+
+/// Reads a whole model file.
+pub(crate) fn read_file(path: &str) -> Result<Vec<u8>, ObjError> {
+    std::fs::read(path).map_err(|source| ObjError::Io {
+        file: path.to_string(),
+        source,
+    })
+}
+
+/// Fails with `error` in strict mode, otherwise keeps it as a warning.
+pub(crate) fn report(
+    error: ObjError,
+    options: &ParseOptions,
+    warnings: &mut Vec<ObjError>,
+) -> Result<(), ObjError> {
+    if options.strict {
+        return Err(error);
+    }
+    warnings.push(error);
+    Ok(())
+}
+
+/// Feeds every line of `reader` to `parse_line`. In strict mode the first
+/// error is returned, otherwise errors are collected into `warnings`, which
+/// `parse_line` may also add recoverable problems to.
+pub(crate) fn read_lines<R: BufRead>(
+    reader: R,
+    file_name: &str,
+    options: &ParseOptions,
+    warnings: &mut Vec<ObjError>,
+    mut parse_line: impl FnMut(&LineContext, &mut Vec<ObjError>) -> Result<(), ObjError>,
+) -> Result<(), ObjError> {
+    for (index, line) in reader.lines().enumerate() {
+        let text = line.map_err(|source| ObjError::Io {
+            file: file_name.to_string(),
+            source,
+        })?;
+        let line = LineContext {
+            file: file_name,
+            number: index + 1,
+            text: &text,
+        };
+
+        if let Err(error) = parse_line(&line, warnings) {
+            report(error, options, warnings)?;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn parse_floats<const N: usize>(
+    tokens: &[Token],
+    line: &LineContext,
+) -> Result<[f32; N], ObjError> {
+    let mut values = [0.0; N];
+    for (value, token) in values.iter_mut().zip(tokens) {
+        *value = token.text.parse().map_err(|_| ObjError::MalformedNumber {
+            location: line.at(token.column),
+            text: token.text.to_string(),
+        })?;
+    }
+    Ok(values)
+}
+
+/// Faces before any `s` statement are smoothed together, like most viewers do.
+/// Formats without smoothing information use it for all their faces.
+pub(crate) const DEFAULT_SMOOTHING_GROUP: u32 = u32::MAX;
+
+/// Geometry accumulated by a loader before it becomes an `ObjData`. Indices
+/// passed in must be valid for the attributes pushed so far.
+#[derive(Default)]
+pub(crate) struct MeshBuilder {
+    pub vertices: Vec<[f32; 3]>,
+    /// Only as long as needed to reach the last colored vertex.
+    pub vertex_colors: Vec<[f32; 3]>,
+    pub tex_coords: Vec<[f32; 2]>,
+    pub normals: Vec<[f32; 3]>,
+    pub materials: Vec<Material>,
+    pub lines: Vec<[u32; 2]>,
+    pub points: Vec<u32>,
+    pub face_stats: FaceStats,
+    triangles: Vec<[FaceVertex; 3]>,
+    /// Part names, some of which may end up without faces.
+    part_names: Vec<String>,
+    /// The part (into `part_names`), material and smoothing group of each
+    /// entry of `triangles`.
+    triangle_tags: Vec<(usize, Option<usize>, u32)>,
+}
+
+impl MeshBuilder {
+    /// Colorless vertices default to white once any vertex has a color.
+    pub fn add_vertex(&mut self, position: [f32; 3], color: Option<[f32; 3]>) {
+        if let Some(color) = color {
+            self.vertex_colors.resize(self.vertices.len(), [1.0; 3]);
+            self.vertex_colors.push(color);
+        }
+        self.vertices.push(position);
+    }
+
+    /// Returns the index of the part called `name`, adding it if needed.
+    pub fn part(&mut self, name: &str) -> usize {
+        match self.part_names.iter().position(|n| n == name) {
+            Some(index) => index,
+            None => {
+                self.part_names.push(name.to_string());
+                self.part_names.len() - 1
+            }
+        }
+    }
+
+    /// Adds a polygon, triangulating it if needed.
+    pub fn add_face(
+        &mut self,
+        corners: &[FaceVertex],
+        part: usize,
+        material: Option<usize>,
+        smoothing_group: u32,
+    ) {
+        ObjData::triangulate_face(
+            &self.vertices,
+            corners,
+            &mut self.triangles,
+            &mut self.face_stats,
+        );
+        self.triangle_tags
+            .resize(self.triangles.len(), (part, material, smoothing_group));
+    }
+
+    /// Generates missing attributes and groups faces by part and material.
+    pub fn finish(self, normal_options: &NormalOptions, warnings: Vec<ObjError>) -> ObjData {
+        let MeshBuilder {
+            vertices,
+            mut vertex_colors,
+            mut tex_coords,
+            mut normals,
+            materials,
+            lines,
+            points,
+            face_stats,
+            triangles,
+            part_names,
+            triangle_tags,
+        } = self;
+        let smoothing_groups: Vec<u32> = triangle_tags.iter().map(|tag| tag.2).collect();
+        let mut faces = ObjData::resolve_faces(
+            &vertices,
+            &mut tex_coords,
+            &mut normals,
+            &triangles,
+            &smoothing_groups,
+            normal_options,
+        );
+        let (parts, material_ranges) = ObjData::group_faces(
+            &mut faces,
+            triangle_tags.iter().map(|tag| (tag.0, tag.1)).collect(),
+            &part_names,
+        );
+        if !vertex_colors.is_empty() {
+            vertex_colors.resize(vertices.len(), [1.0; 3]);
+        }
+
+        ObjData {
+            vertices,
+            vertex_colors,
+            tex_coords,
+            normals,
+            faces,
+            materials,
+            parts,
+            material_ranges,
+            lines,
+            points,
+            face_stats,
+            warnings,
+        }
+    }
+}
+
+/// OBJ state on top of the mesh being built.
+struct ObjBuilder<'a> {
+    options: ParseOptions,
+    /// Where `mtllib` paths are resolved from.
+    directory: &'a Path,
+    mesh: MeshBuilder,
+    current_material: Option<usize>,
+    current_part: Option<usize>,
+    current_smoothing_group: u32,
+}
+
+impl ObjBuilder<'_> {
+    /// Applies one line; on error nothing has been added, so lenient parsing
+    /// can simply move on.
+    fn parse_line(
+        &mut self,
+        line: &LineContext,
+        warnings: &mut Vec<ObjError>,
+    ) -> Result<(), ObjError> {
+        let tokens = line.tokens();
+        let Some((keyword, values)) = tokens.split_first() else {
+            return Ok(());
+        };
+
+        match keyword.text {
+            "vn" if values.len() != 3 => Err(line.malformed_statement(keyword.column)),
+            "v" => {
+                // `v x y z [w] [r g b]`; w only matters for rational curves
+                let (position, color) = match values.len() {
+                    3 => (parse_floats(values, line)?, None),
+                    4 => {
+                        let position = parse_floats(values, line)?;
+                        parse_floats::<1>(&values[3..], line)?;
+                        (position, None)
+                    }
+                    6 => (
+                        parse_floats(values, line)?,
+                        Some(parse_floats(&values[3..], line)?),
+                    ),
+                    7 => {
+                        let position = parse_floats(values, line)?;
+                        parse_floats::<1>(&values[3..], line)?;
+                        (position, Some(parse_floats(&values[4..], line)?))
+                    }
+                    _ => return Err(line.malformed_statement(keyword.column)),
+                };
+                self.mesh.add_vertex(position, color);
+                Ok(())
+            }
+            "vn" => {
+                self.mesh.normals.push(parse_floats(values, line)?);
+                Ok(())
+            }
+            "vt" => {
+                // `vt u [v [w]]`; the optional w is irrelevant for 2D textures
+                if values.is_empty() || values.len() > 3 {
+                    return Err(line.malformed_statement(keyword.column));
+                }
+                let [u, v, _] = parse_floats::<3>(values, line)?;
+                self.mesh.tex_coords.push([u, v]);
+                Ok(())
+            }
+            "l" | "p" => {
+                let positions = values
+                    .iter()
+                    .map(|&token| parse_position(token, line, self.mesh.vertices.len()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                match (keyword.text, positions.len()) {
+                    ("l", 0..=1) | ("p", 0) => Err(line.malformed_statement(keyword.column)),
+                    ("l", _) => {
+                        self.mesh
+                            .lines
+                            .extend(positions.windows(2).map(|pair| [pair[0], pair[1]]));
+                        Ok(())
+                    }
+                    _ => {
+                        self.mesh.points.extend(positions);
+                        Ok(())
+                    }
+                }
+            }
+            "f" => {
+                let counts = [
+                    self.mesh.vertices.len(),
+                    self.mesh.tex_coords.len(),
+                    self.mesh.normals.len(),
+                ];
+                let mut dangling = Vec::new();
+                let corners = values
+                    .iter()
+                    .map(|&token| FaceVertex::parse(token, line, counts, &mut dangling))
+                    .collect::<Result<Vec<_>, _>>()?;
+                // One report per face is enough to point at the problem
+                if let Some(error) = dangling.into_iter().next() {
+                    report(error, &self.options, warnings)?;
                 }
+                // Faces before any `o` or `g` belong to an unnamed part
+                let part = match self.current_part {
+                    Some(part) => part,
+                    None => *self.current_part.insert(self.mesh.part("default")),
+                };
+                self.mesh.add_face(
+                    &corners,
+                    part,
+                    self.current_material,
+                    self.current_smoothing_group,
+                );
+                Ok(())
             }
+            "mtllib" if values.is_empty() => Err(line.malformed_statement(keyword.column)),
+            "mtllib" => {
+                for file in values {
+                    let path = self.directory.join(file.text);
+                    match material::parse_library(&path.to_string_lossy(), &self.options) {
+                        Ok((materials, library_warnings)) => {
+                            self.mesh.materials.extend(materials);
+                            warnings.extend(library_warnings);
+                        }
+                        Err(error) => report(error, &self.options, warnings)?,
+                    }
+                }
+                Ok(())
+            }
+            "usemtl" => {
+                let [name] = values else {
+                    return Err(line.malformed_statement(keyword.column));
+                };
+                // Unknown materials fall back to the default one
+                self.current_material =
+                    self.mesh.materials.iter().position(|m| m.name == name.text);
+                match self.current_material {
+                    Some(_) => Ok(()),
+                    None => Err(ObjError::UnknownMaterial {
+                        location: line.at(name.column),
+                        name: name.text.to_string(),
+                    }),
+                }
+            }
+            "o" | "g" => {
+                // Reopening a part by name appends to it
+                let name = match values {
+                    [] => "default".to_string(),
+                    _ => values.iter().map(|v| v.text).collect::<Vec<_>>().join(" "),
+                };
+                self.current_part = Some(self.mesh.part(&name));
+                Ok(())
+            }
+            "s" => {
+                let [group] = values else {
+                    return Err(line.malformed_statement(keyword.column));
+                };
+                self.current_smoothing_group = match group.text {
+                    "off" => normals::FLAT,
+                    text => text.parse().map_err(|_| ObjError::MalformedNumber {
+                        location: line.at(group.column),
+                        text: text.to_string(),
+                    })?,
+                };
+                Ok(())
+            }
+            _ if keyword.text.starts_with('#') => Ok(()),
+            _ => Err(ObjError::UnsupportedStatement {
+                location: line.at(keyword.column),
+                text: keyword.text.to_string(),
+            }),
         }
+    }
+}
 
-        normals
+impl ObjData {
+    pub fn parse(file_path: &str, options: &ParseOptions) -> Result<Self, ObjError> {
+        let file = File::open(file_path).map_err(|source| ObjError::Io {
+            file: file_path.to_string(),
+            source,
+        })?;
+        Self::parse_reader(io::BufReader::new(file), file_path, options)
     }
 
-    pub fn parse(file_path: &str) -> io::Result<Self> {
-        let file = File::open(file_path)?;
-        let reader = io::BufReader::new(file);
+    /// Parses OBJ data from any reader. `file_name` is used in errors and to
+    /// find material libraries, which live next to the OBJ file.
+    pub fn parse_reader<R: BufRead>(
+        reader: R,
+        file_name: &str,
+        options: &ParseOptions,
+    ) -> Result<Self, ObjError> {
+        let mut builder = ObjBuilder {
+            options: options.clone(),
+            directory: Path::new(file_name).parent().unwrap_or(Path::new("")),
+            mesh: MeshBuilder::default(),
+            current_material: None,
+            current_part: None,
+            current_smoothing_group: DEFAULT_SMOOTHING_GROUP,
+        };
+        let mut warnings = Vec::new();
+        read_lines(
+            reader,
+            file_name,
+            options,
+            &mut warnings,
+            |line, warnings| builder.parse_line(line, warnings),
+        )?;
+
+        Ok(builder.mesh.finish(&options.normals, warnings))
+    }
+
+    /// Reorders faces so each part's are contiguous, and within a part each
+    /// material's, keeping their relative order otherwise. Returns the parts
+    /// that have faces and the resulting material runs.
+    fn group_faces(
+        faces: &mut Vec<Face>,
+        triangle_tags: Vec<(usize, Option<usize>)>,
+        part_names: &[String],
+    ) -> (Vec<Part>, Vec<MaterialRange>) {
+        let mut part_order: Vec<usize> = Vec::new();
+        let mut material_order: Vec<Option<usize>> = Vec::new();
+        for (part, material) in &triangle_tags {
+            if !part_order.contains(part) {
+                part_order.push(*part);
+            }
+            if !material_order.contains(material) {
+                material_order.push(*material);
+            }
+        }
+        let rank = |(part, material): &(usize, Option<usize>)| {
+            (
+                part_order.iter().position(|p| p == part),
+                material_order.iter().position(|m| m == material),
+            )
+        };
+
+        let mut tagged: Vec<_> = triangle_tags.into_iter().zip(faces.drain(..)).collect();
+        tagged.sort_by_key(|(tag, _)| rank(tag));
+
+        let mut parts: Vec<Part> = Vec::new();
+        let mut ranges: Vec<MaterialRange> = Vec::new();
+        for (index, ((part, material), face)) in tagged.into_iter().enumerate() {
+            faces.push(face);
+            match parts.last_mut() {
+                Some(last) if last.name == part_names[part] => last.face_count += 1,
+                _ => parts.push(Part {
+                    name: part_names[part].clone(),
+                    first_face: index,
+                    face_count: 1,
+                }),
+            }
+            let part = parts.len() - 1;
+            match ranges.last_mut() {
+                Some(range) if range.part == part && range.material == material => {
+                    range.face_count += 1
+                }
+                _ => ranges.push(MaterialRange {
+                    part,
+                    material,
+                    first_face: index,
+                    face_count: 1,
+                }),
+            }
+        }
+        (parts, ranges)
+    }
 
-        let mut vertices = Vec::new();
-        let mut tex_coords = Vec::new();
-        let mut normals = Vec::new();
-        let mut faces = Vec::new();
+    fn triangulate_face(
+        vertices: &[[f32; 3]],
+        corners: &[FaceVertex],
+        triangles: &mut Vec<[FaceVertex; 3]>,
+        face_stats: &mut FaceStats,
+    ) {
+        match corners.len() {
+            0..=2 => face_stats.dropped += 1,
+            3 => triangles.push([corners[0], corners[1], corners[2]]),
+            count => {
+                let points: Option<Vec<Vec3>> = corners
+                    .iter()
+                    .map(|corner| {
+                        vertices
+                            .get(corner.position as usize)
+                            .map(|&p| Vec3::from(p))
+                    })
+                    .collect();
+                let split = points
+                    .and_then(|points| triangulation::ear_clip(&points))
+                    .unwrap_or_else(|| {
+                        face_stats.fan_fallbacks += 1;
+                        triangulation::fan(count)
+                    });
+                triangles.extend(split.iter().map(|t| t.map(|i| corners[i])));
+                face_stats.triangulated += 1;
+            }
+        }
+    }
 
+    /// Turns parsed corners into faces, appending generated texture
+    /// coordinates and normals for corners that don't reference any.
+    fn resolve_faces(
+        vertices: &[[f32; 3]],
+        tex_coords: &mut Vec<[f32; 2]>,
+        normals: &mut Vec<[f32; 3]>,
+        triangles: &[[FaceVertex; 3]],
+        smoothing_groups: &[u32],
+        normal_options: &NormalOptions,
+    ) -> Vec<Face> {
         // Generate default texture coordinates based on vertex position
         fn generate_tex_coords(vertex: &[f32; 3]) -> [f32; 2] {
             [
@@ -96,62 +801,405 @@ impl ObjData {
             ]
         }
 
-        for line in reader.lines() {
-            let line = line?;
-            let parts: Vec<&str> = line.split_whitespace().collect();
+        let file_tex_coords = tex_coords.len() as u32;
+        let file_normals = normals.len() as u32;
+        let needs_tex_coords = triangles
+            .iter()
+            .flatten()
+            .any(|corner| corner.tex_coord.is_none());
+        let needs_normals = triangles
+            .iter()
+            .flatten()
+            .any(|corner| corner.normal.is_none());
 
-            match parts.as_slice() {
-                ["v", x, y, z] => {
-                    let vertex = [x.parse().unwrap(), y.parse().unwrap(), z.parse().unwrap()];
-                    vertices.push(vertex);
-                    // Generate texture coordinates for each vertex
-                    tex_coords.push(generate_tex_coords(&vertex));
-                }
-                ["vt", u, v] => {
-                    tex_coords.push([u.parse().unwrap(), v.parse().unwrap()]);
-                }
-                ["vn", x, y, z] => {
-                    normals.push([x.parse().unwrap(), y.parse().unwrap(), z.parse().unwrap()]);
-                }
-                ["f", v1, v2, v3] => {
-                    faces.push(Face {
-                        vertex_indices: [
-                            v1.split('/').next().unwrap().parse::<u32>().unwrap() - 1,
-                            v2.split('/').next().unwrap().parse::<u32>().unwrap() - 1,
-                            v3.split('/').next().unwrap().parse::<u32>().unwrap() - 1,
-                        ],
-                    });
-                }
-                ["f", v1, v2, v3, v4] => {
-                    faces.push(Face {
-                        vertex_indices: [
-                            v1.split('/').next().unwrap().parse::<u32>().unwrap() - 1,
-                            v2.split('/').next().unwrap().parse::<u32>().unwrap() - 1,
-                            v3.split('/').next().unwrap().parse::<u32>().unwrap() - 1,
-                        ],
-                    });
-                    faces.push(Face {
-                        vertex_indices: [
-                            v1.split('/').next().unwrap().parse::<u32>().unwrap() - 1,
-                            v3.split('/').next().unwrap().parse::<u32>().unwrap() - 1,
-                            v4.split('/').next().unwrap().parse::<u32>().unwrap() - 1,
-                        ],
-                    });
+        // Generated attributes are stored after the file's own: texture
+        // coordinates per position, normals per distinct value
+        if needs_tex_coords {
+            tex_coords.extend(vertices.iter().map(generate_tex_coords));
+        }
+        let generated_normals = needs_normals.then(|| {
+            let positions: Vec<[u32; 3]> = triangles
+                .iter()
+                .map(|t| [t[0].position, t[1].position, t[2].position])
+                .collect();
+            let generated =
+                normals::generate(vertices, &positions, smoothing_groups, normal_options);
+            normals.extend(generated.normals);
+            generated.indices
+        });
+
+        triangles
+            .iter()
+            .enumerate()
+            .map(|(index, triangle)| Face {
+                vertex_indices: triangle.map(|corner| corner.position),
+                tex_coord_indices: triangle.map(|corner| {
+                    corner
+                        .tex_coord
+                        .unwrap_or(file_tex_coords + corner.position)
+                }),
+                normal_indices: [0, 1, 2].map(|i| {
+                    triangle[i].normal.unwrap_or_else(|| {
+                        let generated = generated_normals.as_ref().unwrap();
+                        file_normals + generated[index][i]
+                    })
+                }),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/malformed");
+
+    fn parse_fixture(name: &str, strict: bool) -> Result<ObjData, ObjError> {
+        ObjData::parse(
+            &format!("{}/{}", FIXTURES, name),
+            &ParseOptions {
+                strict,
+                ..Default::default()
+            },
+        )
+    }
+
+    fn parse_str(source: &str) -> Result<ObjData, ObjError> {
+        ObjData::parse_reader(source.as_bytes(), "inline.obj", &ParseOptions::strict())
+    }
+
+    fn assert_location(location: &Location, line: usize, column: usize) {
+        assert_eq!((location.line, location.column), (line, column));
+        assert!(location.file.ends_with(".obj"));
+    }
+
+    #[test]
+    fn strict_mode_reports_first_error_location() {
+        match parse_fixture("bad_number.obj", true) {
+            Err(ObjError::MalformedNumber { location, text }) => {
+                assert_location(&location, 3, 7);
+                assert_eq!(text, "0.O");
+            }
+            other => panic!("unexpected result: {:?}", other.map(|o| o.faces.len())),
+        }
+
+        match parse_fixture("bad_face_index.obj", true) {
+            Err(ObjError::MalformedNumber { location, text }) => {
+                assert_location(&location, 5, 5);
+                assert_eq!(text, "two");
+            }
+            other => panic!("unexpected result: {:?}", other.map(|o| o.faces.len())),
+        }
+
+        match parse_fixture("zero_index.obj", true) {
+            Err(ObjError::IndexOutOfRange { location, text, .. }) => {
+                assert_location(&location, 4, 3);
+                assert_eq!(text, "0");
+            }
+            other => panic!("unexpected result: {:?}", other.map(|o| o.faces.len())),
+        }
+
+        match parse_fixture("missing_component.obj", true) {
+            Err(ObjError::MalformedStatement { location, text }) => {
+                assert_location(&location, 2, 1);
+                assert_eq!(text, "v 1.0 0.0");
+            }
+            other => panic!("unexpected result: {:?}", other.map(|o| o.faces.len())),
+        }
+
+        match parse_fixture("unsupported_statement.obj", true) {
+            Err(ObjError::UnsupportedStatement { location, text }) => {
+                assert_location(&location, 4, 1);
+                assert_eq!(text, "cstype");
+            }
+            other => panic!("unexpected result: {:?}", other.map(|o| o.faces.len())),
+        }
+
+        assert!(matches!(
+            parse_fixture("bad_tex_coord.obj", true),
+            Err(ObjError::MalformedNumber { .. })
+        ));
+    }
+
+    #[test]
+    fn lenient_mode_skips_bad_lines_with_warnings() {
+        let cases = [
+            ("bad_number.obj", 2, 3, 1),
+            ("bad_face_index.obj", 1, 3, 1),
+            ("zero_index.obj", 1, 3, 1),
+            ("missing_component.obj", 2, 3, 0),
+            ("unsupported_statement.obj", 2, 3, 1),
+            ("bad_tex_coord.obj", 1, 3, 1),
+        ];
+        for (name, warnings, vertices, faces) in cases {
+            let obj = parse_fixture(name, false).unwrap();
+            assert_eq!(obj.warnings.len(), warnings, "{}", name);
+            assert_eq!(obj.vertices.len(), vertices, "{}", name);
+            assert_eq!(obj.faces.len(), faces, "{}", name);
+        }
+    }
+
+    #[test]
+    fn missing_file_is_an_io_error() {
+        assert!(matches!(
+            parse_fixture("does_not_exist.obj", false),
+            Err(ObjError::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn bundled_models_parse() {
+        let path = |model: &str| format!("{}/models/{}", env!("CARGO_MANIFEST_DIR"), model);
+        for model in ["42.obj", "lego.obj"] {
+            let obj = ObjData::parse(&path(model), &ParseOptions::strict()).unwrap();
+            assert!(!obj.faces.is_empty(), "{}", model);
+            assert_eq!(obj.materials.len(), 1, "{}", model);
+            assert_eq!(obj.material_ranges.last().unwrap().material, Some(0));
+        }
+
+        // These reference texture coordinates and normals they never define,
+        // and sphere.obj a material without any library
+        for model in ["cow.obj", "sphere.obj"] {
+            match ObjData::parse(&path(model), &ParseOptions::strict()) {
+                Err(ObjError::IndexOutOfRange { .. }) if model == "cow.obj" => {}
+                Err(ObjError::UnknownMaterial { .. }) if model == "sphere.obj" => {}
+                other => panic!("unexpected result: {:?}", other.map(|o| o.faces.len())),
+            }
+            let obj = ObjData::parse(&path(model), &ParseOptions::default()).unwrap();
+            assert!(!obj.faces.is_empty(), "{}", model);
+            assert!(!obj.warnings.is_empty(), "{}", model);
+        }
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_current_element() {
+        let obj = parse_str(
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf -3//-1 -2//-1 -1//1\n\
+             v 5 5 5\nf 1 -3 -1\n",
+        )
+        .unwrap();
+        assert_eq!(obj.faces[0].vertex_indices, [0, 1, 2]);
+        assert_eq!(obj.faces[0].normal_indices, [0, 0, 0]);
+        assert_eq!(obj.faces[1].vertex_indices, [0, 1, 3]);
+    }
+
+    #[test]
+    fn every_face_reference_is_range_checked() {
+        for (source, count) in [
+            ("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n", 3),
+            ("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -4 2 3\n", 3),
+            ("v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nf 1/2 2/1 3/1\n", 1),
+            ("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1//1 2//1 3//1\nvn 0 0 1\n", 0),
+        ] {
+            match parse_str(source) {
+                Err(ObjError::IndexOutOfRange { count: actual, .. }) => {
+                    assert_eq!(actual, count, "{}", source)
                 }
-                _ => {}
+                other => panic!("unexpected result: {:?}", other.map(|o| o.faces.len())),
             }
         }
+    }
+
+    #[test]
+    fn dangling_attributes_fall_back_in_lenient_mode() {
+        let obj = ObjData::parse_reader(
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nf 1/1/1 2/1/2 3/9/3\n".as_bytes(),
+            "inline.obj",
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(obj.faces.len(), 1);
+        assert_eq!(obj.warnings.len(), 1, "one warning per face");
+        // The valid texture coordinates are kept, the rest are generated
+        assert_eq!(obj.faces[0].tex_coord_indices, [0, 0, 3]);
+        // A lone triangle has a single generated normal
+        assert_eq!(obj.faces[0].normal_indices, [0, 0, 0]);
+        assert_eq!(obj.normals.len(), 1);
+    }
+
+    #[test]
+    fn smoothing_groups_control_generated_normals() {
+        // Two perpendicular faces sharing the edge 1-2
+        let source = |group: &str| {
+            format!(
+                "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 1 0 -1\n{}\nf 1 2 3\nf 2 4 3\n",
+                group
+            )
+        };
+        let shared =
+            |obj: &ObjData| obj.faces[0].normal_indices[1] == obj.faces[1].normal_indices[0];
+
+        assert!(shared(&parse_str(&source("")).unwrap()));
+        assert!(shared(&parse_str(&source("s 1")).unwrap()));
+        assert!(!shared(&parse_str(&source("s off")).unwrap()));
+
+        let creased = ObjData::parse_reader(
+            source("s 1").as_bytes(),
+            "inline.obj",
+            &ParseOptions {
+                normals: NormalOptions {
+                    crease_angle: Some(30.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(!shared(&creased));
 
-        // If no normals were provided in the file, calculate them
-        if normals.is_empty() {
-            normals = Self::calculate_face_normals(&vertices, &faces);
+        assert!(matches!(
+            parse_str("s smooth\n"),
+            Err(ObjError::MalformedNumber { .. })
+        ));
+    }
+
+    #[test]
+    fn face_corners_keep_their_own_attributes() {
+        let obj = parse_str(
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\nvn 0 0 1\nf 1/3/1 2/2/1 3/1/1\n",
+        )
+        .unwrap();
+        assert_eq!(obj.faces[0].vertex_indices, [0, 1, 2]);
+        assert_eq!(obj.faces[0].tex_coord_indices, [2, 1, 0]);
+        assert_eq!(obj.faces[0].normal_indices, [0, 0, 0]);
+        assert_eq!(obj.tex_coords.len(), 3);
+        assert_eq!(obj.normals.len(), 1);
+    }
+
+    #[test]
+    fn faces_are_grouped_by_material() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/materials/grouped.obj"
+        );
+        let obj = ObjData::parse(path, &ParseOptions::default()).unwrap();
+
+        let names: Vec<_> = obj.materials.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["red", "blue"]);
+        assert!(obj.materials[0]
+            .diffuse_map
+            .as_ref()
+            .unwrap()
+            .ends_with("red.bmp"));
+
+        // The unknown material is reported and falls back to the default one
+        assert_eq!(obj.warnings.len(), 1);
+        assert!(
+            matches!(&obj.warnings[0], ObjError::UnknownMaterial { name, .. } if name == "missing")
+        );
+
+        let range = |material, first_face, face_count| MaterialRange {
+            part: 0,
+            material,
+            first_face,
+            face_count,
+        };
+        assert_eq!(
+            obj.material_ranges,
+            [
+                range(None, 0, 2),
+                range(Some(0), 2, 2),
+                range(Some(1), 4, 1)
+            ]
+        );
+        let vertex_indices: Vec<_> = obj.faces.iter().map(|f| f.vertex_indices).collect();
+        assert_eq!(
+            vertex_indices,
+            [[0, 1, 2], [0, 1, 2], [0, 2, 3], [1, 2, 3], [0, 1, 3]]
+        );
+    }
+
+    #[test]
+    fn objects_and_groups_become_parts() {
+        let obj = parse_str(
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n\
+             o Cube\nf 1 2 3\ng top side\nf 1 2 3\no Empty\no Cube\nf 3 2 1\n",
+        )
+        .unwrap();
+
+        let parts: Vec<_> = obj
+            .parts
+            .iter()
+            .map(|p| (p.name.as_str(), p.first_face, p.face_count))
+            .collect();
+        assert_eq!(
+            parts,
+            [("default", 0, 1), ("Cube", 1, 2), ("top side", 3, 1)]
+        );
+        // The reopened part's faces were moved next to its first ones
+        assert_eq!(obj.faces[2].vertex_indices, [2, 1, 0]);
+        let range_parts: Vec<_> = obj.material_ranges.iter().map(|r| r.part).collect();
+        assert_eq!(range_parts, [0, 1, 2]);
+    }
+
+    #[test]
+    fn missing_material_library_is_reported() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/materials/missing_library.obj"
+        );
+        assert!(matches!(
+            ObjData::parse(path, &ParseOptions::strict()),
+            Err(ObjError::Io { .. })
+        ));
+
+        let obj = ObjData::parse(path, &ParseOptions::default()).unwrap();
+        assert_eq!(obj.faces.len(), 1);
+        assert!(matches!(obj.warnings[0], ObjError::Io { .. }));
+        assert!(matches!(obj.warnings[1], ObjError::UnknownMaterial { .. }));
+    }
+
+    #[test]
+    fn lines_and_points_are_kept_apart_from_faces() {
+        let obj = parse_str("v 0 0 0\nv 1 0 0\nv 0 1 0\nl 1 2/1 -1\np 3 1\np -2\n").unwrap();
+        assert!(obj.faces.is_empty());
+        assert_eq!(obj.lines, [[0, 1], [1, 2]]);
+        assert_eq!(obj.points, [2, 0, 1]);
+
+        assert!(matches!(
+            parse_str("v 0 0 0\nl 1\n"),
+            Err(ObjError::MalformedStatement { .. })
+        ));
+        assert!(matches!(
+            parse_str("v 0 0 0\np 1 2\n"),
+            Err(ObjError::IndexOutOfRange { count: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn vertices_may_have_weights_and_colors() {
+        let obj = parse_str("v 0 0 0\nv 1 0 0 1\nv 0 1 0 1 0 0\nv 0 0 1 1 0 1 0\n").unwrap();
+        assert_eq!(obj.vertices[3], [0.0, 0.0, 1.0]);
+        assert_eq!(
+            obj.vertex_colors,
+            [[1.0; 3], [1.0; 3], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+        );
+
+        assert!(parse_str("v 0 0 0\n").unwrap().vertex_colors.is_empty());
+        assert!(matches!(
+            parse_str("v 0 0 0 1 0\n"),
+            Err(ObjError::MalformedStatement { .. })
+        ));
+        for text in ["v 1 2 3 abc\n", "v 1 2 3 abc 0 0 0\n"] {
+            assert!(
+                matches!(
+                    parse_str(text),
+                    Err(ObjError::MalformedNumber { ref text, .. }) if text == "abc"
+                ),
+                "{}",
+                text
+            );
         }
+    }
 
-        Ok(ObjData {
-            vertices,
-            tex_coords,
-            normals,
-            faces,
-        })
+    #[test]
+    fn polygons_are_triangulated() {
+        let obj =
+            parse_str("v 0 0 0\nv 2 0 0\nv 2 1 0\nv 1 1 0\nv 1 2 0\nv 0 2 0\nf 1 2 3 4 5 6\n")
+                .unwrap();
+        assert_eq!(obj.faces.len(), 4);
+        assert_eq!(obj.face_stats.triangulated, 1);
+        assert_eq!(obj.face_stats.fan_fallbacks, 0);
     }
 }
@@ -0,0 +1,736 @@
+use crate::parser::{
+    self, FaceVertex, LineContext, MeshBuilder, ObjData, ObjError, ParseOptions, Token,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScalarType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl ScalarType {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "char" | "int8" => Some(ScalarType::Int8),
+            "uchar" | "uint8" => Some(ScalarType::UInt8),
+            "short" | "int16" => Some(ScalarType::Int16),
+            "ushort" | "uint16" => Some(ScalarType::UInt16),
+            "int" | "int32" => Some(ScalarType::Int32),
+            "uint" | "uint32" => Some(ScalarType::UInt32),
+            "float" | "float32" => Some(ScalarType::Float32),
+            "double" | "float64" => Some(ScalarType::Float64),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            ScalarType::Int8 | ScalarType::UInt8 => 1,
+            ScalarType::Int16 | ScalarType::UInt16 => 2,
+            ScalarType::Int32 | ScalarType::UInt32 | ScalarType::Float32 => 4,
+            ScalarType::Float64 => 8,
+        }
+    }
+
+    /// What a color channel of this type is divided by to get 0-1.
+    fn color_range(self) -> f32 {
+        match self {
+            ScalarType::Int8 => i8::MAX as f32,
+            ScalarType::UInt8 => u8::MAX as f32,
+            ScalarType::Int16 => i16::MAX as f32,
+            ScalarType::UInt16 => u16::MAX as f32,
+            ScalarType::Int32 => i32::MAX as f32,
+            ScalarType::UInt32 => u32::MAX as f32,
+            ScalarType::Float32 | ScalarType::Float64 => 1.0,
+        }
+    }
+
+    /// Decodes `bytes`, which are exactly `size()` long.
+    fn decode(self, bytes: &[u8], big_endian: bool) -> f64 {
+        let mut le = [0; 8];
+        le[..bytes.len()].copy_from_slice(bytes);
+        if big_endian {
+            le[..bytes.len()].reverse();
+        }
+        match self {
+            ScalarType::Int8 => le[0] as i8 as f64,
+            ScalarType::UInt8 => le[0] as f64,
+            ScalarType::Int16 => i16::from_le_bytes([le[0], le[1]]) as f64,
+            ScalarType::UInt16 => u16::from_le_bytes([le[0], le[1]]) as f64,
+            ScalarType::Int32 => i32::from_le_bytes([le[0], le[1], le[2], le[3]]) as f64,
+            ScalarType::UInt32 => u32::from_le_bytes([le[0], le[1], le[2], le[3]]) as f64,
+            ScalarType::Float32 => f32::from_le_bytes([le[0], le[1], le[2], le[3]]) as f64,
+            ScalarType::Float64 => f64::from_le_bytes(le),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    kind: ScalarType,
+    /// The type of the length prefix, for list properties.
+    count_kind: Option<ScalarType>,
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    /// Finds the scalar property for each list of accepted names.
+    fn find<const N: usize>(&self, names: [&[&str]; N]) -> Option<[usize; N]> {
+        let mut indices = [0; N];
+        for (index, names) in indices.iter_mut().zip(names) {
+            *index = self
+                .properties
+                .iter()
+                .position(|p| p.count_kind.is_none() && names.contains(&p.name.as_str()))?;
+        }
+        Some(indices)
+    }
+}
+
+#[derive(Debug)]
+struct Header {
+    format: Option<Format>,
+    elements: Vec<Element>,
+    /// The number of lines up to and including `end_header`.
+    line_count: usize,
+    /// Where the element data starts.
+    body_offset: usize,
+}
+
+impl Header {
+    /// Reads the text header. Since nothing after it can be interpreted
+    /// without the header, any problem in it is an error even when parsing
+    /// leniently.
+    fn parse(bytes: &[u8], file_name: &str) -> Result<Self, ObjError> {
+        let invalid = |message: &str| ObjError::InvalidData {
+            file: file_name.to_string(),
+            message: message.to_string(),
+        };
+        if !is_ply(bytes) {
+            return Err(invalid("missing 'ply' signature"));
+        }
+
+        let mut header = Header {
+            format: None,
+            elements: Vec::new(),
+            line_count: 0,
+            body_offset: 0,
+        };
+        let mut offset = 0;
+        loop {
+            let end = bytes[offset..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .ok_or_else(|| invalid("header has no 'end_header' line"))?;
+            let line = &bytes[offset..offset + end];
+            offset += end + 1;
+            header.line_count += 1;
+            if line.trim_ascii() == b"end_header" {
+                break;
+            }
+        }
+        header.body_offset = offset;
+
+        let options = ParseOptions::strict();
+        parser::read_lines(
+            &bytes[..offset],
+            file_name,
+            &options,
+            &mut Vec::new(),
+            |line, _| header.parse_line(line),
+        )?;
+        if header.format.is_none() {
+            return Err(invalid("header has no 'format' line"));
+        }
+        Ok(header)
+    }
+
+    fn parse_line(&mut self, line: &LineContext) -> Result<(), ObjError> {
+        let tokens = line.tokens();
+        let Some((keyword, values)) = tokens.split_first() else {
+            return Ok(());
+        };
+        let scalar_type = |token: &Token| {
+            ScalarType::from_name(token.text).ok_or_else(|| ObjError::UnsupportedStatement {
+                location: line.at(token.column),
+                text: token.text.to_string(),
+            })
+        };
+
+        match (keyword.text, values) {
+            ("ply" | "end_header", []) | ("comment" | "obj_info", _) => {}
+            ("format", [kind, _version]) => {
+                self.format = Some(match kind.text {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => {
+                        return Err(ObjError::UnsupportedStatement {
+                            location: line.at(kind.column),
+                            text: kind.text.to_string(),
+                        })
+                    }
+                });
+            }
+            ("element", [name, count]) => self.elements.push(Element {
+                name: name.text.to_string(),
+                count: count.text.parse().map_err(|_| ObjError::MalformedNumber {
+                    location: line.at(count.column),
+                    text: count.text.to_string(),
+                })?,
+                properties: Vec::new(),
+            }),
+            ("property", _) => {
+                let Some(element) = self.elements.last_mut() else {
+                    return Err(line.malformed_statement(keyword.column));
+                };
+                let property = match values {
+                    [list, count_kind, kind, name] if list.text == "list" => Property {
+                        name: name.text.to_string(),
+                        kind: scalar_type(kind)?,
+                        count_kind: Some(scalar_type(count_kind)?),
+                    },
+                    [kind, name] => Property {
+                        name: name.text.to_string(),
+                        kind: scalar_type(kind)?,
+                        count_kind: None,
+                    },
+                    _ => return Err(line.malformed_statement(keyword.column)),
+                };
+                element.properties.push(property);
+            }
+            ("ply" | "end_header" | "format" | "element", _) => {
+                return Err(line.malformed_statement(keyword.column))
+            }
+            _ => {
+                return Err(ObjError::UnsupportedStatement {
+                    location: line.at(keyword.column),
+                    text: keyword.text.to_string(),
+                })
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn is_ply(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"ply\n") || bytes.starts_with(b"ply\r\n")
+}
+
+/// The values of one element: its scalar properties in order (list
+/// properties hold their length) and the items of each list.
+#[derive(Default)]
+struct Record {
+    values: Vec<f64>,
+    lists: Vec<Vec<f64>>,
+}
+
+impl Record {
+    fn clear(&mut self) {
+        self.values.clear();
+        self.lists.clear();
+    }
+}
+
+/// Where the attributes the viewer uses are found in a vertex record.
+struct VertexLayout {
+    position: [usize; 3],
+    normal: Option<[usize; 3]>,
+    /// The color properties and what they are divided by.
+    color: Option<([usize; 3], f32)>,
+    tex_coord: Option<[usize; 2]>,
+}
+
+impl VertexLayout {
+    fn new(element: &Element) -> Option<Self> {
+        let color = element.find([
+            &["red", "diffuse_red", "r"],
+            &["green", "diffuse_green", "g"],
+            &["blue", "diffuse_blue", "b"],
+        ]);
+        Some(VertexLayout {
+            position: element.find([&["x"], &["y"], &["z"]])?,
+            normal: element.find([&["nx"], &["ny"], &["nz"]]),
+            color: color.map(|c| (c, element.properties[c[0]].kind.color_range())),
+            tex_coord: element.find([
+                &["u", "s", "texture_u", "texture_s"],
+                &["v", "t", "texture_v", "texture_t"],
+            ]),
+        })
+    }
+}
+
+struct PlyBuilder {
+    mesh: MeshBuilder,
+    vertex_element: Option<(usize, VertexLayout)>,
+    /// The face element and which of its lists holds the vertex indices.
+    face_element: Option<(usize, usize)>,
+    /// Vertex count announced by the header, which faces are checked against.
+    vertex_count: usize,
+    /// Faces are only added once all vertices are known, since a file may
+    /// list them first.
+    faces: Vec<Vec<u32>>,
+}
+
+impl PlyBuilder {
+    fn new(header: &Header, file_name: &str) -> Result<Self, ObjError> {
+        let element = |name: &str| header.elements.iter().position(|e| e.name == name);
+        let vertex_element = match element("vertex") {
+            Some(index) => Some((
+                index,
+                VertexLayout::new(&header.elements[index]).ok_or_else(|| {
+                    ObjError::InvalidData {
+                        file: file_name.to_string(),
+                        message: "vertex element lacks x, y or z".to_string(),
+                    }
+                })?,
+            )),
+            None => None,
+        };
+        let face_element = element("face").and_then(|index| {
+            let list = header.elements[index]
+                .properties
+                .iter()
+                .filter(|p| p.count_kind.is_some())
+                .position(|p| p.name == "vertex_indices" || p.name == "vertex_index")?;
+            Some((index, list))
+        });
+
+        Ok(PlyBuilder {
+            mesh: MeshBuilder::default(),
+            vertex_count: vertex_element
+                .as_ref()
+                .map_or(0, |(i, _)| header.elements[*i].count),
+            vertex_element,
+            face_element,
+            faces: Vec::new(),
+        })
+    }
+
+    /// Adds a vertex or face; other elements are ignored. A face with an
+    /// invalid vertex index is rejected with the index's position in its list.
+    fn add_record(&mut self, element: usize, record: &Record) -> Result<(), usize> {
+        if let Some((_, layout)) = self.vertex_element.as_ref().filter(|v| v.0 == element) {
+            let get = |i: usize| record.values[i] as f32;
+            let color = layout.color.map(|(c, range)| c.map(|i| get(i) / range));
+            self.mesh.add_vertex(layout.position.map(get), color);
+            if let Some(normal) = layout.normal {
+                self.mesh.normals.push(normal.map(get));
+            }
+            if let Some(tex_coord) = layout.tex_coord {
+                self.mesh.tex_coords.push(tex_coord.map(get));
+            }
+        } else if let Some((_, list)) = self.face_element.filter(|f| f.0 == element) {
+            let face = record.lists[list]
+                .iter()
+                .enumerate()
+                .map(|(i, &index)| {
+                    match index.fract() == 0.0 && (0.0..self.vertex_count as f64).contains(&index) {
+                        true => Ok(index as u32),
+                        false => Err(i),
+                    }
+                })
+                .collect::<Result<_, _>>()?;
+            self.faces.push(face);
+        }
+        Ok(())
+    }
+
+    /// Keeps vertex numbering intact when a vertex record can't be read.
+    fn add_placeholder(&mut self, element: &Element, index: usize) {
+        let record = Record {
+            values: vec![0.0; element.properties.len()],
+            lists: Vec::new(),
+        };
+        if self.vertex_element.as_ref().is_some_and(|v| v.0 == index) {
+            let _ = self.add_record(index, &record);
+        }
+    }
+
+    fn finish(mut self, options: &ParseOptions, warnings: Vec<ObjError>) -> ObjData {
+        if self.face_element.is_none() {
+            // A point cloud
+            self.mesh.points = (0..self.mesh.vertices.len() as u32).collect();
+        }
+
+        let part = self.mesh.part("default");
+        let layout = self.vertex_element.as_ref().map(|v| &v.1);
+        let has_normals = layout.is_some_and(|l| l.normal.is_some());
+        let has_tex_coords = layout.is_some_and(|l| l.tex_coord.is_some());
+        for face in &self.faces {
+            // Vertices may be missing from a truncated file
+            if face.iter().any(|&i| i as usize >= self.mesh.vertices.len()) {
+                continue;
+            }
+            let corners: Vec<FaceVertex> = face
+                .iter()
+                .map(|&position| FaceVertex {
+                    position,
+                    tex_coord: has_tex_coords.then_some(position),
+                    normal: has_normals.then_some(position),
+                })
+                .collect();
+            self.mesh
+                .add_face(&corners, part, None, parser::DEFAULT_SMOOTHING_GROUP);
+        }
+        self.mesh.finish(&options.normals, warnings)
+    }
+}
+
+pub fn parse(path: &str, options: &ParseOptions) -> Result<ObjData, ObjError> {
+    let bytes = parser::read_file(path)?;
+    parse_bytes(&bytes, path, options)
+}
+
+/// Parses an ASCII or binary PLY file. `vertex` elements provide positions
+/// and, when present, normals, colors and texture coordinates; `face`
+/// elements provide polygons. A file without faces is shown as a point cloud.
+pub fn parse_bytes(
+    bytes: &[u8],
+    file_name: &str,
+    options: &ParseOptions,
+) -> Result<ObjData, ObjError> {
+    let header = Header::parse(bytes, file_name)?;
+    let mut builder = PlyBuilder::new(&header, file_name)?;
+    let mut warnings = Vec::new();
+    let truncated = |element: &Element| ObjError::InvalidData {
+        file: file_name.to_string(),
+        message: format!(
+            "ends before all {} '{}' elements",
+            element.count, element.name
+        ),
+    };
+
+    let mut instances = header
+        .elements
+        .iter()
+        .enumerate()
+        .flat_map(|(index, element)| std::iter::repeat_n(index, element.count));
+    let mut record = Record::default();
+
+    if header.format == Some(Format::Ascii) {
+        let mut line_number = 0;
+        parser::read_lines(bytes, file_name, options, &mut warnings, |line, _| {
+            line_number += 1;
+            let tokens = line.tokens();
+            if line_number <= header.line_count || tokens.is_empty() {
+                return Ok(());
+            }
+            let Some(index) = instances.next() else {
+                return Err(line.malformed_statement(tokens[0].column));
+            };
+            let element = &header.elements[index];
+            let list_starts = match read_ascii_record(element, &tokens, line, &mut record) {
+                Ok(list_starts) => list_starts,
+                Err(error) => {
+                    builder.add_placeholder(element, index);
+                    return Err(error);
+                }
+            };
+            builder.add_record(index, &record).map_err(|i| {
+                let list = builder.face_element.map_or(0, |f| f.1);
+                let token = tokens[list_starts[list] + i];
+                ObjError::IndexOutOfRange {
+                    location: line.at(token.column),
+                    text: token.text.to_string(),
+                    count: builder.vertex_count,
+                }
+            })
+        })?;
+        if let Some(index) = instances.next() {
+            parser::report(truncated(&header.elements[index]), options, &mut warnings)?;
+        }
+    } else {
+        let mut reader = BinaryReader {
+            bytes: &bytes[header.body_offset..],
+            big_endian: header.format == Some(Format::BinaryBigEndian),
+        };
+        for (count, index) in instances.enumerate() {
+            let element = &header.elements[index];
+            if reader.read_record(element, &mut record).is_none() {
+                parser::report(truncated(element), options, &mut warnings)?;
+                break;
+            }
+            if let Err(i) = builder.add_record(index, &record) {
+                let list = builder.face_element.map_or(0, |f| f.1);
+                let error = ObjError::InvalidData {
+                    file: file_name.to_string(),
+                    message: format!(
+                        "element {} references vertex {} of {}",
+                        count + 1,
+                        record.lists[list][i],
+                        builder.vertex_count
+                    ),
+                };
+                parser::report(error, options, &mut warnings)?;
+            }
+        }
+    }
+
+    Ok(builder.finish(options, warnings))
+}
+
+/// Reads one element from a line. Returns the index in `tokens` of the first
+/// item of each list.
+fn read_ascii_record(
+    element: &Element,
+    tokens: &[Token],
+    line: &LineContext,
+    record: &mut Record,
+) -> Result<Vec<usize>, ObjError> {
+    let number = |token: &Token| {
+        token
+            .text
+            .parse::<f64>()
+            .map_err(|_| ObjError::MalformedNumber {
+                location: line.at(token.column),
+                text: token.text.to_string(),
+            })
+    };
+    let too_short = || line.malformed_statement(tokens[0].column);
+
+    record.clear();
+    let mut list_starts = Vec::new();
+    let mut remaining = tokens;
+    for property in &element.properties {
+        let (first, rest) = remaining.split_first().ok_or_else(too_short)?;
+        let value = number(first)?;
+        record.values.push(value);
+        remaining = rest;
+        if property.count_kind.is_some() {
+            if value.fract() != 0.0 || value < 0.0 || value as usize > remaining.len() {
+                return Err(too_short());
+            }
+            list_starts.push(tokens.len() - remaining.len());
+            let (items, rest) = remaining.split_at(value as usize);
+            record
+                .lists
+                .push(items.iter().map(number).collect::<Result<_, _>>()?);
+            remaining = rest;
+        }
+    }
+    if let Some(extra) = remaining.first() {
+        return Err(line.malformed_statement(extra.column));
+    }
+    Ok(list_starts)
+}
+
+struct BinaryReader<'a> {
+    bytes: &'a [u8],
+    big_endian: bool,
+}
+
+impl BinaryReader<'_> {
+    fn read(&mut self, kind: ScalarType) -> Option<f64> {
+        if self.bytes.len() < kind.size() {
+            return None;
+        }
+        let (value, rest) = self.bytes.split_at(kind.size());
+        self.bytes = rest;
+        Some(kind.decode(value, self.big_endian))
+    }
+
+    /// Returns `None` if the data ends first.
+    fn read_record(&mut self, element: &Element, record: &mut Record) -> Option<()> {
+        record.clear();
+        for property in &element.properties {
+            match property.count_kind {
+                Some(count_kind) => {
+                    let count = self.read(count_kind)?;
+                    record.values.push(count);
+                    let list = (0..count as usize)
+                        .map(|_| self.read(property.kind))
+                        .collect::<Option<_>>()?;
+                    record.lists.push(list);
+                }
+                None => record.values.push(self.read(property.kind)?),
+            }
+        }
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A colored quad with normals, followed by an unrelated element.
+    const ASCII: &str = "ply
+format ascii 1.0
+comment made by hand
+element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+element edge 1
+property int vertex1
+property int vertex2
+end_header
+0 0 0 0 0 1 255 0 0
+1 0 0 0 0 1 0 255 0
+1 1 0 0 0 1 0 0 255
+0 1 0 0 0 1 255 255 255
+4 0 1 2 3
+0 2
+";
+
+    /// The quad of `ASCII` as binary, without normals and colors.
+    fn binary(format: &str, big_endian: bool) -> Vec<u8> {
+        let mut bytes = format!(
+            "ply\nformat {} 1.0\nelement vertex 4\nproperty float x\nproperty float y\n\
+             property float z\nelement face 1\nproperty list uchar uint vertex_indices\n\
+             end_header\n",
+            format
+        )
+        .into_bytes();
+        let float = |v: f32| match big_endian {
+            true => v.to_be_bytes(),
+            false => v.to_le_bytes(),
+        };
+        let uint = |v: u32| match big_endian {
+            true => v.to_be_bytes(),
+            false => v.to_le_bytes(),
+        };
+        for position in [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ] {
+            for value in position {
+                bytes.extend(float(value));
+            }
+        }
+        bytes.push(4);
+        for index in 0..4 {
+            bytes.extend(uint(index));
+        }
+        bytes
+    }
+
+    #[test]
+    fn ascii_files_keep_colors_and_normals() {
+        let obj = parse_bytes(ASCII.as_bytes(), "quad.ply", &ParseOptions::strict()).unwrap();
+        assert_eq!(obj.vertices.len(), 4);
+        assert_eq!(obj.faces.len(), 2);
+        assert_eq!(obj.vertex_colors[1], [0.0, 1.0, 0.0]);
+        assert_eq!(obj.normals.len(), 4);
+        assert_eq!(obj.faces[0].normal_indices, obj.faces[0].vertex_indices);
+        assert!(obj.points.is_empty());
+    }
+
+    #[test]
+    fn binary_files_of_both_byte_orders_match() {
+        let little = parse_bytes(
+            &binary("binary_little_endian", false),
+            "quad.ply",
+            &ParseOptions::strict(),
+        )
+        .unwrap();
+        let big = parse_bytes(
+            &binary("binary_big_endian", true),
+            "quad.ply",
+            &ParseOptions::strict(),
+        )
+        .unwrap();
+        assert_eq!(little.vertices, big.vertices);
+        assert_eq!(little.vertices[2], [1.0, 1.0, 0.0]);
+        assert_eq!(little.faces.len(), 2);
+        assert_eq!(
+            little
+                .faces
+                .iter()
+                .map(|f| f.vertex_indices)
+                .collect::<Vec<_>>(),
+            big.faces
+                .iter()
+                .map(|f| f.vertex_indices)
+                .collect::<Vec<_>>()
+        );
+        assert!(little.vertex_colors.is_empty());
+    }
+
+    #[test]
+    fn files_without_faces_are_point_clouds() {
+        let source = "ply\nformat ascii 1.0\nelement vertex 3\nproperty double x\n\
+                      property double y\nproperty double z\nproperty ushort red\n\
+                      property ushort green\nproperty ushort blue\nend_header\n\
+                      0 0 0 65535 0 0\n1 0 0 0 65535 0\n0 1 0 0 0 65535\n";
+        let obj = parse_bytes(source.as_bytes(), "cloud.ply", &ParseOptions::strict()).unwrap();
+        assert!(obj.faces.is_empty());
+        assert_eq!(obj.points, [0, 1, 2]);
+        assert_eq!(obj.vertex_colors[2], [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn bad_elements_are_reported() {
+        let source = ASCII
+            .replace("1 0 0 0 0 1 0 255 0", "1 0 zero 0 0 1 0 255 0")
+            .replace("4 0 1 2 3", "4 0 1 2 7");
+        match parse_bytes(source.as_bytes(), "bad.ply", &ParseOptions::strict()) {
+            Err(ObjError::MalformedNumber { location, text }) => {
+                assert_eq!((location.line, location.column), (21, 5));
+                assert_eq!(text, "zero");
+            }
+            other => panic!("unexpected result: {:?}", other.map(|o| o.faces.len())),
+        }
+
+        let obj = parse_bytes(source.as_bytes(), "bad.ply", &ParseOptions::default()).unwrap();
+        assert_eq!(obj.warnings.len(), 2);
+        assert!(matches!(
+            obj.warnings[1],
+            ObjError::IndexOutOfRange { count: 4, .. }
+        ));
+        // The unreadable vertex keeps its place
+        assert_eq!(obj.vertices.len(), 4);
+        assert!(obj.faces.is_empty());
+    }
+
+    #[test]
+    fn truncated_and_unsupported_files_are_reported() {
+        let mut bytes = binary("binary_little_endian", false);
+        bytes.truncate(bytes.len() - 2);
+        assert!(matches!(
+            parse_bytes(&bytes, "short.ply", &ParseOptions::strict()),
+            Err(ObjError::InvalidData { .. })
+        ));
+        let obj = parse_bytes(&bytes, "short.ply", &ParseOptions::default()).unwrap();
+        assert_eq!((obj.vertices.len(), obj.faces.len()), (4, 0));
+        assert_eq!(obj.warnings.len(), 1);
+
+        let source = ASCII.replace("property float nz", "property half nz");
+        assert!(matches!(
+            parse_bytes(source.as_bytes(), "half.ply", &ParseOptions::default()),
+            Err(ObjError::UnsupportedStatement { .. })
+        ));
+        assert!(parse_bytes(b"solid x\n", "stl.ply", &ParseOptions::default()).is_err());
+    }
+}
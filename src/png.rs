@@ -0,0 +1,574 @@
+use crate::inflate;
+use crate::texture::Image;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// Pass origins and steps of Adam7 interlacing: x, y, dx, dy.
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, &byte| {
+        CRC_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+pub fn is_png(bytes: &[u8]) -> bool {
+    bytes.starts_with(&SIGNATURE)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ColorType {
+    Gray,
+    Rgb,
+    Indexed,
+    GrayAlpha,
+    Rgba,
+}
+
+impl ColorType {
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(ColorType::Gray),
+            2 => Some(ColorType::Rgb),
+            3 => Some(ColorType::Indexed),
+            4 => Some(ColorType::GrayAlpha),
+            6 => Some(ColorType::Rgba),
+            _ => None,
+        }
+    }
+
+    fn channels(self) -> usize {
+        match self {
+            ColorType::Gray | ColorType::Indexed => 1,
+            ColorType::GrayAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+
+    fn allows_depth(self, depth: u8) -> bool {
+        match self {
+            ColorType::Gray => matches!(depth, 1 | 2 | 4 | 8 | 16),
+            ColorType::Indexed => matches!(depth, 1 | 2 | 4 | 8),
+            _ => matches!(depth, 8 | 16),
+        }
+    }
+}
+
+struct Header {
+    width: usize,
+    height: usize,
+    depth: u8,
+    color_type: ColorType,
+    interlaced: bool,
+}
+
+impl Header {
+    fn bits_per_pixel(&self) -> usize {
+        self.color_type.channels() * self.depth as usize
+    }
+
+    /// Bytes in a scanline of `width` pixels, without its filter byte.
+    fn row_size(&self, width: usize) -> usize {
+        (width * self.bits_per_pixel()).div_ceil(8)
+    }
+
+    /// The size of each pass in pixels, or of the whole image if it isn't
+    /// interlaced.
+    fn passes(&self) -> Vec<(usize, usize, usize, usize, usize, usize)> {
+        if !self.interlaced {
+            return vec![(0, 0, 1, 1, self.width, self.height)];
+        }
+        ADAM7
+            .iter()
+            .map(|&(x, y, dx, dy)| {
+                let width = (self.width + dx - 1 - x) / dx;
+                let height = (self.height + dy - 1 - y) / dy;
+                (x, y, dx, dy, width, height)
+            })
+            .collect()
+    }
+}
+
+fn read_header(data: &[u8]) -> Result<Header, String> {
+    if data.len() != 13 {
+        return Err("IHDR chunk has the wrong size".to_string());
+    }
+    let width = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+    let height = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as usize;
+    let depth = data[8];
+    let color_type = ColorType::from_code(data[9])
+        .ok_or_else(|| format!("unknown PNG color type {}", data[9]))?;
+    if !color_type.allows_depth(depth) {
+        return Err(format!(
+            "invalid PNG bit depth {} for color type {}",
+            depth, data[9]
+        ));
+    }
+    if data[10] != 0 || data[11] != 0 {
+        return Err("unknown PNG compression or filter method".to_string());
+    }
+    if data[12] > 1 {
+        return Err(format!("unknown PNG interlace method {}", data[12]));
+    }
+    Ok(Header {
+        width,
+        height,
+        depth,
+        color_type,
+        interlaced: data[12] == 1,
+    })
+}
+
+/// Decodes a PNG image of any color type and bit depth, interlaced or not.
+/// 16-bit samples are reduced to 8 bits; `tRNS` transparency becomes alpha.
+/// Gamma and color space chunks are ignored.
+pub fn decode(bytes: &[u8]) -> Result<Image, String> {
+    if !is_png(bytes) {
+        return Err("not a PNG file".to_string());
+    }
+    let mut header = None;
+    let mut palette: Vec<[u8; 4]> = Vec::new();
+    let mut transparency = None;
+    let mut compressed = Vec::new();
+
+    let mut at = SIGNATURE.len();
+    loop {
+        let length = bytes
+            .get(at..at + 4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize)
+            .ok_or_else(|| "PNG is truncated before its IEND chunk".to_string())?;
+        let chunk = bytes
+            .get(at + 4..at + 8 + length)
+            .ok_or_else(|| "PNG chunk is truncated".to_string())?;
+        let crc = bytes
+            .get(at + 8 + length..at + 12 + length)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or_else(|| "PNG chunk is truncated".to_string())?;
+        let (kind, data) = chunk.split_at(4);
+        let name = String::from_utf8_lossy(kind);
+        if crc32(chunk) != crc {
+            return Err(format!("PNG {} chunk is corrupt (CRC mismatch)", name));
+        }
+        at += 12 + length;
+
+        if header.is_none() && kind != b"IHDR" {
+            return Err("PNG doesn't start with an IHDR chunk".to_string());
+        }
+        match kind {
+            b"IHDR" => header = Some(read_header(data)?),
+            b"PLTE" => {
+                if !data.len().is_multiple_of(3) || data.len() > 256 * 3 {
+                    return Err("PLTE chunk has the wrong size".to_string());
+                }
+                palette = data
+                    .chunks_exact(3)
+                    .map(|c| [c[0], c[1], c[2], 255])
+                    .collect();
+            }
+            b"tRNS" => transparency = Some(data.to_vec()),
+            b"IDAT" => compressed.extend_from_slice(data),
+            b"IEND" => break,
+            // Ancillary chunks have a lowercase first letter and may be skipped
+            _ if kind[0].is_ascii_lowercase() => {}
+            _ => return Err(format!("unsupported critical PNG chunk {}", name)),
+        }
+    }
+
+    let header = header.ok_or_else(|| "PNG has no IHDR chunk".to_string())?;
+    let mut image = Image::new(header.width, header.height)?;
+    if header.color_type == ColorType::Indexed {
+        if palette.is_empty() {
+            return Err("indexed PNG has no PLTE chunk".to_string());
+        }
+        if let Some(alphas) = &transparency {
+            if alphas.len() > palette.len() {
+                return Err("tRNS chunk has more entries than the palette".to_string());
+            }
+            for (color, &alpha) in palette.iter_mut().zip(alphas) {
+                color[3] = alpha;
+            }
+        }
+    }
+    // The color, in raw samples, that's fully transparent
+    let transparent_color = match (header.color_type, &transparency) {
+        (ColorType::Gray, Some(t)) if t.len() == 2 => Some([u16::from_be_bytes([t[0], t[1]]); 3]),
+        (ColorType::Rgb, Some(t)) if t.len() == 6 => Some([
+            u16::from_be_bytes([t[0], t[1]]),
+            u16::from_be_bytes([t[2], t[3]]),
+            u16::from_be_bytes([t[4], t[5]]),
+        ]),
+        _ => None,
+    };
+
+    let passes = header.passes();
+    let expected: usize = passes
+        .iter()
+        .filter(|pass| pass.4 > 0 && pass.5 > 0)
+        .map(|pass| (header.row_size(pass.4) + 1) * pass.5)
+        .sum();
+    let mut raw = inflate::zlib_decompress(&compressed, expected)
+        .map_err(|e| format!("PNG image data: {}", e))?;
+    if raw.len() != expected {
+        return Err(format!(
+            "PNG image data has {} bytes, expected {}",
+            raw.len(),
+            expected
+        ));
+    }
+
+    let pixel_size = header.bits_per_pixel().div_ceil(8);
+    let mut offset = 0;
+    for (x0, y0, dx, dy, width, height) in passes {
+        if width == 0 || height == 0 {
+            continue;
+        }
+        let row_size = header.row_size(width);
+        for row in 0..height {
+            let (previous, rest) = raw.split_at_mut(offset);
+            let line = &mut rest[..row_size + 1];
+            let previous = if row == 0 {
+                None
+            } else {
+                Some(&previous[offset - row_size..])
+            };
+            unfilter(line, previous, pixel_size)?;
+            for x in 0..width {
+                let color = pixel(&line[1..], x, &header, &palette, transparent_color.as_ref())?;
+                image.set_pixel(x0 + x * dx, y0 + row * dy, color);
+            }
+            offset += row_size + 1;
+        }
+    }
+    Ok(image)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Undoes the filter of `line` (its filter byte then the row) in place.
+/// `previous` is the already unfiltered row above, without its filter byte.
+fn unfilter(line: &mut [u8], previous: Option<&[u8]>, pixel_size: usize) -> Result<(), String> {
+    let (filter, row) = line.split_first_mut().expect("lines have a filter byte");
+    let above = |i: usize| previous.map_or(0, |previous| previous[i]);
+    for i in 0..row.len() {
+        let left = if i >= pixel_size {
+            row[i - pixel_size]
+        } else {
+            0
+        };
+        let upper_left = if i >= pixel_size {
+            above(i - pixel_size)
+        } else {
+            0
+        };
+        let predictor = match *filter {
+            0 => 0,
+            1 => left,
+            2 => above(i),
+            3 => ((left as u16 + above(i) as u16) / 2) as u8,
+            4 => paeth(left, above(i), upper_left),
+            other => return Err(format!("unknown PNG filter type {}", other)),
+        };
+        row[i] = row[i].wrapping_add(predictor);
+    }
+    Ok(())
+}
+
+/// The raw sample of channel `channel` of pixel `x`.
+fn sample(row: &[u8], x: usize, channel: usize, header: &Header) -> u16 {
+    let depth = header.depth as usize;
+    let index = x * header.color_type.channels() + channel;
+    match depth {
+        16 => u16::from_be_bytes([row[index * 2], row[index * 2 + 1]]),
+        8 => row[index] as u16,
+        _ => {
+            let bit = index * depth;
+            let shift = 8 - depth - bit % 8;
+            ((row[bit / 8] >> shift) & ((1 << depth) - 1)) as u16
+        }
+    }
+}
+
+fn pixel(
+    row: &[u8],
+    x: usize,
+    header: &Header,
+    palette: &[[u8; 4]],
+    transparent_color: Option<&[u16; 3]>,
+) -> Result<[u8; 4], String> {
+    let to_8_bits = |value: u16| -> u8 {
+        match header.depth {
+            16 => (value >> 8) as u8,
+            depth => (value as u32 * 255 / ((1 << depth) - 1)) as u8,
+        }
+    };
+    let sample = |channel| sample(row, x, channel, header);
+    let color = match header.color_type {
+        ColorType::Indexed => {
+            let index = sample(0) as usize;
+            return palette
+                .get(index)
+                .copied()
+                .ok_or_else(|| format!("PNG palette index {} is out of range", index));
+        }
+        ColorType::Gray => {
+            let gray = sample(0);
+            let alpha = if transparent_color == Some(&[gray; 3]) {
+                0
+            } else {
+                255
+            };
+            let gray = to_8_bits(gray);
+            [gray, gray, gray, alpha]
+        }
+        ColorType::GrayAlpha => {
+            let gray = to_8_bits(sample(0));
+            [gray, gray, gray, to_8_bits(sample(1))]
+        }
+        ColorType::Rgb => {
+            let rgb = [sample(0), sample(1), sample(2)];
+            let alpha = if transparent_color == Some(&rgb) {
+                0
+            } else {
+                255
+            };
+            [
+                to_8_bits(rgb[0]),
+                to_8_bits(rgb[1]),
+                to_8_bits(rgb[2]),
+                alpha,
+            ]
+        }
+        ColorType::Rgba => [
+            to_8_bits(sample(0)),
+            to_8_bits(sample(1)),
+            to_8_bits(sample(2)),
+            to_8_bits(sample(3)),
+        ],
+    };
+    Ok(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut body = kind.to_vec();
+        body.extend(data);
+        let mut bytes = (data.len() as u32).to_be_bytes().to_vec();
+        bytes.extend(&body);
+        bytes.extend(crc32(&body).to_be_bytes());
+        bytes
+    }
+
+    /// Wraps already filtered scanlines in a zlib stream of stored blocks.
+    fn zlib_stored(raw: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0x78, 0x01];
+        let blocks: Vec<_> = raw.chunks(65535).collect();
+        for (i, block) in blocks.iter().enumerate() {
+            bytes.push((i + 1 == blocks.len()) as u8);
+            bytes.extend((block.len() as u16).to_le_bytes());
+            bytes.extend((!(block.len() as u16)).to_le_bytes());
+            bytes.extend(*block);
+        }
+        if raw.is_empty() {
+            bytes.extend([1, 0, 0, 0xff, 0xff]);
+        }
+        bytes.extend(inflate::adler32(raw).to_be_bytes());
+        bytes
+    }
+
+    fn png(
+        width: u32,
+        height: u32,
+        depth: u8,
+        color_type: u8,
+        interlace: u8,
+        extra: &[Vec<u8>],
+        raw: &[u8],
+    ) -> Vec<u8> {
+        let mut ihdr = width.to_be_bytes().to_vec();
+        ihdr.extend(height.to_be_bytes());
+        ihdr.extend([depth, color_type, 0, 0, interlace]);
+        let mut bytes = SIGNATURE.to_vec();
+        bytes.extend(chunk(b"IHDR", &ihdr));
+        for extra in extra {
+            bytes.extend(extra);
+        }
+        // Split the data over two IDAT chunks
+        let data = zlib_stored(raw);
+        let (first, second) = data.split_at(data.len() / 2);
+        bytes.extend(chunk(b"IDAT", first));
+        bytes.extend(chunk(b"IDAT", second));
+        bytes.extend(chunk(b"IEND", &[]));
+        bytes
+    }
+
+    fn row(image: &Image, y: usize) -> Vec<[u8; 4]> {
+        (0..image.width).map(|x| image.pixel(x, y)).collect()
+    }
+
+    #[test]
+    fn color_types_and_bit_depths_are_expanded() {
+        // 1-bit gray: 10 pixels, 0b10100000_01000000
+        let image = png(10, 1, 1, 0, 0, &[], &[0, 0xa0, 0x40]);
+        let image = decode(&image).unwrap();
+        assert_eq!(image.pixel(0, 0), [255, 255, 255, 255]);
+        assert_eq!(image.pixel(1, 0), [0, 0, 0, 255]);
+        assert_eq!(image.pixel(9, 0), [255, 255, 255, 255]);
+
+        // 2-bit palette with tRNS on the second entry
+        let palette = chunk(b"PLTE", &[255, 0, 0, 0, 255, 0, 0, 0, 255]);
+        let trns = chunk(b"tRNS", &[255, 0]);
+        let image = png(3, 1, 2, 3, 0, &[palette.clone(), trns], &[0, 0b00_01_10_00]);
+        assert_eq!(
+            row(&decode(&image).unwrap(), 0),
+            [[255, 0, 0, 255], [0, 255, 0, 0], [0, 0, 255, 255]]
+        );
+        let image = png(1, 1, 2, 3, 0, &[palette], &[0, 0b11_000000]);
+        assert!(decode(&image).is_err());
+
+        // 4-bit gray with a transparent level
+        let trns = chunk(b"tRNS", &[0, 5]);
+        let image = png(2, 1, 4, 0, 0, &[trns], &[0, 0x5f]);
+        assert_eq!(
+            row(&decode(&image).unwrap(), 0),
+            [[85, 85, 85, 0], [255, 255, 255, 255]]
+        );
+
+        // 16-bit gray + alpha, 8-bit RGB with tRNS, 16-bit RGBA
+        let image = png(1, 1, 16, 4, 0, &[], &[0, 0x80, 0x01, 0x40, 0x00]);
+        assert_eq!(decode(&image).unwrap().pixel(0, 0), [128, 128, 128, 64]);
+        let trns = chunk(b"tRNS", &[0, 1, 0, 2, 0, 3]);
+        let image = png(2, 1, 8, 2, 0, &[trns], &[0, 1, 2, 3, 1, 2, 4]);
+        assert_eq!(
+            row(&decode(&image).unwrap(), 0),
+            [[1, 2, 3, 0], [1, 2, 4, 255]]
+        );
+        let image = png(1, 1, 16, 6, 0, &[], &[0, 1, 0, 2, 0, 3, 0, 4, 0]);
+        assert_eq!(decode(&image).unwrap().pixel(0, 0), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn filters_are_undone() {
+        // 2x3 gray + alpha, 8-bit, so filters look one pixel (2 bytes) back
+        #[rustfmt::skip]
+        let raw = [
+            1, 10, 20, 5, 5,         // sub: [10 20] [15 25]
+            2, 1, 1, 1, 1,           // up: [11 21] [16 26]
+            3, 1, 2, 3, 4,           // average: [6 12] [14 23]
+        ];
+        let image = decode(&png(2, 3, 8, 4, 0, &[], &raw)).unwrap();
+        assert_eq!(row(&image, 0), [[10, 10, 10, 20], [15, 15, 15, 25]]);
+        assert_eq!(row(&image, 1), [[11, 11, 11, 21], [16, 16, 16, 26]]);
+        assert_eq!(row(&image, 2), [[6, 6, 6, 12], [14, 14, 14, 23]]);
+
+        // Paeth picks the left, upper or upper left neighbor
+        #[rustfmt::skip]
+        let raw = [
+            0, 10, 20, 0,
+            4, 30, 1, 1,  // [40 (upper)] [41 (left)] [21 (upper left)]
+        ];
+        let image = decode(&png(3, 2, 8, 0, 0, &[], &raw)).unwrap();
+        assert_eq!(image.pixel(0, 1)[0], 40);
+        assert_eq!(image.pixel(1, 1)[0], 41);
+        assert_eq!(image.pixel(2, 1)[0], 21);
+
+        assert!(decode(&png(1, 1, 8, 0, 0, &[], &[5, 0])).is_err());
+    }
+
+    #[test]
+    fn interlaced_images_match_progressive_ones() {
+        let (width, height) = (11, 9);
+        let color = |x: usize, y: usize| [x as u8 * 20, y as u8 * 25, (x * y) as u8];
+        let mut progressive = Vec::new();
+        for y in 0..height {
+            progressive.push(0);
+            for x in 0..width {
+                progressive.extend(color(x, y));
+            }
+        }
+        let mut interlaced = Vec::new();
+        for (x0, y0, dx, dy) in ADAM7 {
+            if x0 >= width || y0 >= height {
+                continue;
+            }
+            for y in (y0..height).step_by(dy) {
+                interlaced.push(0);
+                for x in (x0..width).step_by(dx) {
+                    interlaced.extend(color(x, y));
+                }
+            }
+        }
+        let expected = decode(&png(11, 9, 8, 2, 0, &[], &progressive)).unwrap();
+        let image = decode(&png(11, 9, 8, 2, 1, &[], &interlaced)).unwrap();
+        assert_eq!(image, expected);
+        assert_eq!(image.pixel(10, 8), [200, 200, 80, 255]);
+
+        // Passes that are empty for a tiny image have no scanlines at all
+        let image = decode(&png(1, 1, 8, 0, 1, &[], &[0, 7])).unwrap();
+        assert_eq!(image.pixel(0, 0), [7, 7, 7, 255]);
+    }
+
+    #[test]
+    fn malformed_files_are_rejected() {
+        let valid = png(1, 1, 8, 0, 0, &[], &[0, 7]);
+        assert!(decode(&valid).is_ok());
+        assert!(decode(&valid[..valid.len() - 12]).is_err());
+        let mut corrupt = valid.clone();
+        corrupt[20] ^= 1;
+        assert!(decode(&corrupt).is_err());
+        // Too little image data, bad depth, unknown critical chunk,
+        // indexed without a palette, zero size
+        assert!(decode(&png(2, 1, 8, 0, 0, &[], &[0, 7])).is_err());
+        assert!(decode(&png(1, 1, 4, 2, 0, &[], &[0, 7])).is_err());
+        assert!(decode(&png(1, 1, 8, 0, 0, &[chunk(b"ABCD", &[])], &[0, 7])).is_err());
+        assert!(decode(&png(1, 1, 8, 3, 0, &[], &[0, 0])).is_err());
+        assert!(decode(&png(0, 1, 8, 0, 0, &[], &[])).is_err());
+    }
+}
@@ -0,0 +1,228 @@
+use crate::vectors::Vec3;
+use std::ops::Mul;
+
+/// Rotation quaternion; the viewer keeps these normalized.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quat {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Quat {
+    pub const IDENTITY: Quat = Quat {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+        w: 1.0,
+    };
+
+    pub const fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Quat { x, y, z, w }
+    }
+
+    pub fn from_axis_angle(axis: Vec3, angle: f32) -> Self {
+        let axis = axis.normalize();
+        let (sin_half, cos_half) = (angle / 2.0).sin_cos();
+        Quat::new(
+            axis.x * sin_half,
+            axis.y * sin_half,
+            axis.z * sin_half,
+            cos_half,
+        )
+    }
+
+    /// Shortest rotation taking the unit vector `from` onto the unit vector `to`.
+    pub fn between(from: Vec3, to: Vec3) -> Self {
+        let axis = from.cross(to);
+        let w = 1.0 + from.dot(to);
+        if w < 1e-6 {
+            // Opposite vectors: any perpendicular axis works
+            let perpendicular = if from.x.abs() > from.z.abs() {
+                Vec3::new(-from.y, from.x, 0.0)
+            } else {
+                Vec3::new(0.0, -from.z, from.y)
+            };
+            return Quat::from_axis_angle(perpendicular, std::f32::consts::PI);
+        }
+        Quat::new(axis.x, axis.y, axis.z, w).normalize()
+    }
+
+    pub fn vector(self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
+    }
+
+    pub fn dot(self, other: Quat) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(self) -> Quat {
+        let length = self.length();
+        if length > 0.0 {
+            Quat::new(
+                self.x / length,
+                self.y / length,
+                self.z / length,
+                self.w / length,
+            )
+        } else {
+            Quat::IDENTITY
+        }
+    }
+
+    pub fn conjugate(self) -> Quat {
+        Quat::new(-self.x, -self.y, -self.z, self.w)
+    }
+
+    #[allow(dead_code)]
+    pub fn inverse(self) -> Quat {
+        let length_squared = self.dot(self);
+        let c = self.conjugate();
+        Quat::new(
+            c.x / length_squared,
+            c.y / length_squared,
+            c.z / length_squared,
+            c.w / length_squared,
+        )
+    }
+
+    pub fn rotate(self, v: Vec3) -> Vec3 {
+        // v' = v + 2w(u x v) + 2u x (u x v), with u the vector part
+        let u = self.vector();
+        let uv = u.cross(v);
+        let uuv = u.cross(uv);
+        v + (uv * self.w + uuv) * 2.0
+    }
+
+    /// Spherical interpolation along the shortest arc.
+    #[allow(dead_code)]
+    pub fn slerp(self, other: Quat, t: f32) -> Quat {
+        let mut cos_theta = self.dot(other);
+        let mut end = other;
+        if cos_theta < 0.0 {
+            cos_theta = -cos_theta;
+            end = Quat::new(-other.x, -other.y, -other.z, -other.w);
+        }
+
+        let (a, b) = if cos_theta > 0.9995 {
+            // Nearly parallel: fall back to a normalized lerp
+            (1.0 - t, t)
+        } else {
+            let theta = cos_theta.acos();
+            let sin_theta = theta.sin();
+            (
+                ((1.0 - t) * theta).sin() / sin_theta,
+                (t * theta).sin() / sin_theta,
+            )
+        };
+        Quat::new(
+            self.x * a + end.x * b,
+            self.y * a + end.y * b,
+            self.z * a + end.z * b,
+            self.w * a + end.w * b,
+        )
+        .normalize()
+    }
+}
+
+/// Hamilton product: `a * b` applies `b` first, then `a`.
+impl Mul for Quat {
+    type Output = Quat;
+
+    fn mul(self, b: Quat) -> Quat {
+        let a = self;
+        Quat::new(
+            a.w * b.x + a.x * b.w + a.y * b.z - a.z * b.y,
+            a.w * b.y - a.x * b.z + a.y * b.w + a.z * b.x,
+            a.w * b.z + a.x * b.y - a.y * b.x + a.z * b.w,
+            a.w * b.w - a.x * b.x - a.y * b.y - a.z * b.z,
+        )
+    }
+}
+
+impl Mul<Vec3> for Quat {
+    type Output = Vec3;
+
+    fn mul(self, v: Vec3) -> Vec3 {
+        self.rotate(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrices::Mat4;
+    use crate::vectors::tests::{assert_vec3_eq, Rng, EPSILON};
+    use std::f32::consts::FRAC_PI_2;
+
+    fn random_quat(rng: &mut Rng) -> Quat {
+        Quat::from_axis_angle(rng.vec3(1.0) + Vec3::splat(0.01), rng.next_f32() * 3.0)
+    }
+
+    #[test]
+    fn axis_angle_rotates_axes() {
+        let q = Quat::from_axis_angle(Vec3::Z, FRAC_PI_2);
+        assert_vec3_eq(q * Vec3::X, Vec3::Y);
+        assert_vec3_eq(q * Vec3::Y, -Vec3::X);
+        assert_vec3_eq(q * Vec3::Z, Vec3::Z);
+    }
+
+    #[test]
+    fn multiplication_composes_right_to_left() {
+        let a = Quat::from_axis_angle(Vec3::Z, FRAC_PI_2);
+        let b = Quat::from_axis_angle(Vec3::X, FRAC_PI_2);
+        // b takes Y to Z, a leaves Z alone
+        assert_vec3_eq((a * b) * Vec3::Y, Vec3::Z);
+        // a takes Y to -X, b leaves X axis alone
+        assert_vec3_eq((b * a) * Vec3::Y, -Vec3::X);
+    }
+
+    #[test]
+    fn between_maps_from_onto_to() {
+        let mut rng = Rng::new(3);
+        for _ in 0..200 {
+            let from = rng.vec3(1.0).normalize();
+            let to = rng.vec3(1.0).normalize();
+            assert_vec3_eq(Quat::between(from, to) * from, to);
+        }
+        assert_vec3_eq(Quat::between(Vec3::X, -Vec3::X) * Vec3::X, -Vec3::X);
+    }
+
+    #[test]
+    fn rotation_preserves_length_and_inverse_undoes_it() {
+        let mut rng = Rng::new(11);
+        for _ in 0..200 {
+            let q = random_quat(&mut rng);
+            let v = rng.vec3(10.0);
+            let rotated = q * v;
+            assert!((rotated.length() - v.length()).abs() < EPSILON * 10.0);
+            assert_vec3_eq(q.inverse() * rotated, v);
+            assert_vec3_eq(q.conjugate() * rotated, v);
+        }
+    }
+
+    #[test]
+    fn matrix_matches_quaternion_rotation() {
+        let mut rng = Rng::new(19);
+        for _ in 0..200 {
+            let q = random_quat(&mut rng);
+            let v = rng.vec3(5.0);
+            assert_vec3_eq(Mat4::from_quat(q).transform_vector(v), q * v);
+        }
+    }
+
+    #[test]
+    fn slerp_hits_endpoints_and_midpoint() {
+        let a = Quat::IDENTITY;
+        let b = Quat::from_axis_angle(Vec3::Y, FRAC_PI_2);
+        assert!((a.slerp(b, 0.0).dot(a) - 1.0).abs() < EPSILON);
+        assert!((a.slerp(b, 1.0).dot(b) - 1.0).abs() < EPSILON);
+        let half = Quat::from_axis_angle(Vec3::Y, FRAC_PI_2 / 2.0);
+        assert!((a.slerp(b, 0.5).dot(half) - 1.0).abs() < EPSILON);
+    }
+}
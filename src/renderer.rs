@@ -1,29 +1,162 @@
 use crate::camera::Camera;
-use crate::matrices;
+use crate::light::{Light, LightKind, MAX_LIGHTS};
+use crate::material::{AlphaUsage, Material};
+use crate::matrices::{Mat3, Mat4};
 use crate::model::ModelPosition;
 use crate::model::ModelRotation;
+use crate::parser::{ObjData, Part};
+use crate::shaders;
 use crate::texture::Texture;
-use gl;
+use crate::vectors::Vec3;
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum NormalSpace {
+    Object,
+    World,
+}
 
 #[derive(PartialEq, Copy, Clone)]
 pub enum RenderMode {
     Vertex,
     Face,
     Texture,
+    Lit,
+    /// Per-vertex colors from the model file.
+    Color,
+}
+
+/// How texture and material alpha is applied.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum AlphaMode {
+    /// Alpha is ignored; everything is drawn opaque.
+    Opaque,
+    /// Mostly transparent fragments are cut out, the rest drawn opaque.
+    Test,
+    /// Translucent draws are blended over the opaque ones, farthest first.
+    Blend,
+}
+
+impl AlphaMode {
+    /// How a material is drawn in this mode, with the alpha test's cutoff.
+    /// Materials can opt out of blending, or cut out with their own cutoff.
+    fn for_material(self, usage: AlphaUsage) -> (AlphaMode, f32) {
+        match (self, usage) {
+            (AlphaMode::Opaque, _) | (_, AlphaUsage::Opaque) => (AlphaMode::Opaque, ALPHA_CUTOFF),
+            (_, AlphaUsage::Mask(cutoff)) => (AlphaMode::Test, cutoff),
+            (mode, AlphaUsage::Blend) => (mode, ALPHA_CUTOFF),
+        }
+    }
+
+    /// The value of the matching `ALPHA_` define in `shaders/fragment.glsl`.
+    fn shader_value(self) -> i32 {
+        match self {
+            AlphaMode::Opaque => 0,
+            AlphaMode::Test => 1,
+            AlphaMode::Blend => 2,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AlphaMode::Opaque => "opaque",
+            AlphaMode::Test => "alpha test",
+            AlphaMode::Blend => "alpha blend",
+        }
+    }
+}
+
+struct LightUniforms {
+    kind: i32,
+    position: i32,
+    color: i32,
+    attenuation: i32,
+}
+
+struct MaterialUniforms {
+    ambient: i32,
+    diffuse: i32,
+    specular: i32,
+    shininess: i32,
+    opacity: i32,
+    illumination: i32,
+    has_diffuse_map: i32,
+    has_bump_map: i32,
+    bump_multiplier: i32,
+    has_alpha_map: i32,
+}
+
+/// The alpha test's cutoff for materials without their own.
+const ALPHA_CUTOFF: f32 = 0.5;
+
+/// Texture units shared with `shaders/fragment.glsl`.
+const DIFFUSE_UNIT: u32 = 0;
+const BUMP_UNIT: u32 = 1;
+const ALPHA_UNIT: u32 = 2;
+
+/// Viewer-side state of one `o`/`g` sub-mesh.
+struct PartState {
+    name: String,
+    visible: bool,
+    /// Moves the part relative to the rest of the model.
+    offset: Vec3,
+    color: Vec3,
+}
+
+/// One `DrawElements` call: a material and the faces of one part that use it.
+struct MaterialDraw {
+    part: usize,
+    material: Material,
+    first_index: usize,
+    index_count: i32,
+    /// Average of the faces' corners in object space, for sorting.
+    center: Vec3,
+    /// Center of each face in object space, for sorting them when blended.
+    triangle_centers: Vec<Vec3>,
+    diffuse_map: Option<gl::types::GLuint>,
+    bump_map: Option<gl::types::GLuint>,
+    alpha_map: Option<gl::types::GLuint>,
 }
 
 pub struct Renderer {
     shader_program: gl::types::GLuint,
     vao: gl::types::GLuint,
     model_loc: i32,
-    pos_offset_loc: i32,
-    model_center_loc: i32,
-    model_position_loc: i32,
-    num_indices: i32,
+    view_loc: i32,
+    normal_matrix_loc: i32,
+    world_space_faces_loc: i32,
+    face_color_space: NormalSpace,
+    lights: Vec<Light>,
+    active_light: usize,
+    light_locs: Vec<LightUniforms>,
+    num_lights_loc: i32,
+    view_position_loc: i32,
+    material_locs: MaterialUniforms,
+    draws: Vec<MaterialDraw>,
+    parts: Vec<PartState>,
+    selected_part: usize,
+    /// Only draw the selected part.
+    isolate_part: bool,
+    color_parts: bool,
+    color_parts_loc: i32,
+    part_color_loc: i32,
+    /// The triangle section of the index buffer, in model order.
+    triangle_indices: Vec<u32>,
+    /// Where `l` segments start in the index buffer, after the triangles;
+    /// `p` points follow them.
+    line_first_index: usize,
+    line_index_count: i32,
+    point_index_count: i32,
+    unlit_primitives_loc: i32,
+    has_vertex_colors: bool,
+    has_vertex_colors_loc: i32,
+    /// Material textures by path or embedded image name, each loaded once.
+    material_textures: Vec<(String, Texture)>,
     texture: Option<Texture>,
     texture_blend_loc: i32,
-    texture_sampler_loc: i32,
     texture_blend: f32,
+    alpha_mode: AlphaMode,
+    alpha_mode_loc: i32,
+    alpha_cutoff_loc: i32,
     render_mode: RenderMode,
 	current_mode: RenderMode,
     target_mode: RenderMode,
@@ -35,70 +168,139 @@ fn lerp(start: f32, end: f32, t: f32) -> f32 {
     start + (end - start) * t
 }
 
+/// The order to blend faces in given their depths along the view direction:
+/// farthest first, so nearer faces are blended over them.
+fn back_to_front(depths: impl Iterator<Item = f32>) -> Vec<usize> {
+    let mut order: Vec<(usize, f32)> = depths.enumerate().collect();
+    order.sort_by(|a, b| b.1.total_cmp(&a.1));
+    order.into_iter().map(|(index, _)| index).collect()
+}
+
+/// Distinct, evenly spread hues for part colors (golden-ratio hue steps).
+fn part_color(index: usize) -> Vec3 {
+    let hue = (index as f32 * 0.618_034).fract() * 6.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+    let (r, g, b) = match hue as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    // Soften towards white so the colors don't hide the shading
+    Vec3::new(r, g, b) * 0.75 + Vec3::splat(0.2)
+}
+
 impl Renderer {
     pub fn new(
         shader_program: gl::types::GLuint,
         vao: gl::types::GLuint,
         model_loc: i32,
-        num_indices: i32,
     ) -> Self {
         unsafe {
             gl::Enable(gl::DEPTH_TEST);
             gl::DepthFunc(gl::LESS);
+            gl::PointSize(4.0);
         }
 
-        let pos_offset_loc = unsafe {
-            gl::GetUniformLocation(
-                shader_program,
-                std::ffi::CString::new("posOffset").unwrap().as_ptr(),
-            )
-        };
-        let model_center_loc = unsafe {
-            gl::GetUniformLocation(
-                shader_program,
-                std::ffi::CString::new("modelCenter").unwrap().as_ptr(),
-            )
-        };
-        let model_position_loc = unsafe {
-            gl::GetUniformLocation(
-                shader_program,
-                std::ffi::CString::new("modelPosition").unwrap().as_ptr(),
-            )
-        };
+        let view_loc = shaders::uniform_location(shader_program, "view");
 
-        let texture_blend_loc = unsafe {
-            gl::GetUniformLocation(
-                shader_program,
-                std::ffi::CString::new("textureBlend").unwrap().as_ptr(),
-            )
-        };
-        let texture_sampler_loc = unsafe {
-            gl::GetUniformLocation(
-                shader_program,
-                std::ffi::CString::new("textureSampler").unwrap().as_ptr(),
-            )
+        let normal_matrix_loc = shaders::uniform_location(shader_program, "normalMatrix");
+        let world_space_faces_loc = shaders::uniform_location(shader_program, "worldSpaceFaces");
+
+        let light_uniform = |i: usize, field: &str| {
+            shaders::uniform_location(shader_program, &format!("lights[{}].{}", i, field))
         };
+        let light_locs = (0..MAX_LIGHTS)
+            .map(|i| LightUniforms {
+                kind: light_uniform(i, "kind"),
+                position: light_uniform(i, "position"),
+                color: light_uniform(i, "color"),
+                attenuation: light_uniform(i, "attenuation"),
+            })
+            .collect();
+        let num_lights_loc = shaders::uniform_location(shader_program, "numLights");
+        let view_position_loc = shaders::uniform_location(shader_program, "viewPosition");
 
-        let projection_loc = unsafe {
-            gl::GetUniformLocation(
-                shader_program,
-                std::ffi::CString::new("projection").unwrap().as_ptr(),
-            )
+        let material_uniform =
+            |field: &str| shaders::uniform_location(shader_program, &format!("material.{}", field));
+        let material_locs = MaterialUniforms {
+            ambient: material_uniform("ambient"),
+            diffuse: material_uniform("diffuse"),
+            specular: material_uniform("specular"),
+            shininess: material_uniform("shininess"),
+            opacity: material_uniform("opacity"),
+            illumination: material_uniform("illumination"),
+            has_diffuse_map: material_uniform("hasDiffuseMap"),
+            has_bump_map: material_uniform("hasBumpMap"),
+            bump_multiplier: material_uniform("bumpMultiplier"),
+            has_alpha_map: material_uniform("hasAlphaMap"),
         };
 
+        let texture_blend_loc = shaders::uniform_location(shader_program, "textureBlend");
+        let color_parts_loc = shaders::uniform_location(shader_program, "colorParts");
+        let part_color_loc = shaders::uniform_location(shader_program, "partColor");
+        let unlit_primitives_loc = shaders::uniform_location(shader_program, "unlitPrimitives");
+        let has_vertex_colors_loc = shaders::uniform_location(shader_program, "hasVertexColors");
+        let alpha_mode_loc = shaders::uniform_location(shader_program, "alphaMode");
+        let alpha_cutoff_loc = shaders::uniform_location(shader_program, "alphaCutoff");
+
+        unsafe {
+            // Samplers never change unit, so they can be set once
+            gl::UseProgram(shader_program);
+            gl::Uniform1i(
+                shaders::uniform_location(shader_program, "textureSampler"),
+                DIFFUSE_UNIT as i32,
+            );
+            gl::Uniform1i(
+                shaders::uniform_location(shader_program, "bumpSampler"),
+                BUMP_UNIT as i32,
+            );
+            gl::Uniform1i(
+                shaders::uniform_location(shader_program, "alphaSampler"),
+                ALPHA_UNIT as i32,
+            );
+        }
+
+        let projection_loc = shaders::uniform_location(shader_program, "projection");
+
         Renderer {
             shader_program,
             vao,
             model_loc,
             projection_loc,
-            pos_offset_loc,
-            model_center_loc,
-            model_position_loc,
-            num_indices,
+            view_loc,
+            normal_matrix_loc,
+            world_space_faces_loc,
+            face_color_space: NormalSpace::World,
+            lights: Vec::new(),
+            active_light: 0,
+            light_locs,
+            num_lights_loc,
+            view_position_loc,
+            material_locs,
+            draws: Vec::new(),
+            parts: Vec::new(),
+            selected_part: 0,
+            isolate_part: false,
+            color_parts: false,
+            color_parts_loc,
+            part_color_loc,
+            triangle_indices: Vec::new(),
+            line_first_index: 0,
+            line_index_count: 0,
+            point_index_count: 0,
+            unlit_primitives_loc,
+            has_vertex_colors: false,
+            has_vertex_colors_loc,
+            material_textures: Vec::new(),
             texture: None,
             texture_blend_loc,
-            texture_sampler_loc,
             texture_blend: 0.0,
+            alpha_mode: AlphaMode::Blend,
+            alpha_mode_loc,
+            alpha_cutoff_loc,
             render_mode: RenderMode::Vertex,
 			current_mode: RenderMode::Vertex,
 			target_mode: RenderMode::Vertex,
@@ -109,7 +311,7 @@ impl Renderer {
     pub fn render(
         &self,
         model_rotation: &ModelRotation,
-        model_center: [f32; 3],
+        model_center: Vec3,
         camera: &Camera,
         model_position: &ModelPosition,
     ) {
@@ -120,79 +322,416 @@ impl Renderer {
             gl::UseProgram(self.shader_program);
             gl::BindVertexArray(self.vao);
 
-			gl::UniformMatrix4fv(self.projection_loc, 1, gl::FALSE, camera.projection.as_ptr() as *const f32);
+			// Our matrices are row-major, so let GL transpose them on upload
+			gl::UniformMatrix4fv(self.projection_loc, 1, gl::TRUE, camera.projection.as_ptr());
+			gl::UniformMatrix4fv(self.view_loc, 1, gl::TRUE, camera.view.as_ptr());
 
-            let model = matrices::mat4_mul(
-                matrices::mat4_rotation_y(model_rotation.y_angle),
-                matrices::mat4_rotation_x(model_rotation.x_angle),
+            let model = Mat4::model(
+                model_rotation.orientation,
+                model_center,
+                Vec3::new(model_position.x, model_position.y, model_position.z),
             );
-			
-			gl::UniformMatrix4fv(self.model_loc, 1, gl::FALSE, model.as_ptr() as *const f32);
 
-            // Handle texture if available
-            if let Some(ref texture) = self.texture {
-                gl::ActiveTexture(gl::TEXTURE0);
-                gl::BindTexture(gl::TEXTURE_2D, texture.id);
+            if self.normal_matrix_loc >= 0 {
+                let normal_matrix = Mat3::normal_matrix(&model);
+                gl::UniformMatrix3fv(self.normal_matrix_loc, 1, gl::TRUE, normal_matrix.as_ptr());
+            }
 
-                if self.texture_sampler_loc >= 0 {
-                    gl::Uniform1i(self.texture_sampler_loc, 0);
-                }
+            if self.world_space_faces_loc >= 0 {
+                gl::Uniform1i(
+                    self.world_space_faces_loc,
+                    (self.face_color_space == NormalSpace::World) as i32,
+                );
+            }
 
-                if self.texture_blend_loc >= 0 {
-                    gl::Uniform1f(self.texture_blend_loc, self.texture_blend);
-                }
+            self.upload_lights(camera);
+
+            if self.texture_blend_loc >= 0 {
+                gl::Uniform1f(self.texture_blend_loc, self.texture_blend);
             }
 
             match self.render_mode {
                 RenderMode::Vertex => gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE),
-                RenderMode::Face | RenderMode::Texture => {
+                RenderMode::Face | RenderMode::Texture | RenderMode::Lit | RenderMode::Color => {
                     gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL)
                 }
             }
 
-            if self.pos_offset_loc >= 0 {
-                gl::Uniform1f(self.pos_offset_loc, camera.position[2] - model_position.z);
-            }
+            gl::Uniform1i(self.color_parts_loc, self.color_parts as i32);
+            gl::Uniform1i(self.has_vertex_colors_loc, self.has_vertex_colors as i32);
 
-            if self.model_center_loc >= 0 {
-                gl::Uniform3f(
-                    self.model_center_loc,
-                    model_center[0],
-                    model_center[1],
-                    model_center[2],
-                );
+            // Opaque draws go first; translucent ones wait to be blended over them
+            let forward = (camera.target - camera.position).normalize();
+            let depth = |point: Vec3| (point - camera.position).dot(forward);
+            let mut translucent = Vec::new();
+            for draw in &self.draws {
+                let part = &self.parts[draw.part];
+                if !part.visible || (self.isolate_part && draw.part != self.selected_part) {
+                    continue;
+                }
+                // Offsets are pure translations, so the normal matrix still applies
+                let part_model = Mat4::translation(part.offset) * model;
+                if self.is_translucent(draw) {
+                    let center = part_model.transform_point(draw.center);
+                    translucent.push((depth(center), draw, part_model));
+                    continue;
+                }
+                self.draw_material(draw, &part_model);
             }
 
-            if self.model_position_loc >= 0 {
-                gl::Uniform3f(
-                    self.model_position_loc,
-                    model_position.x,
-                    model_position.y,
-                    0.0,
-                );
+            if !translucent.is_empty() {
+                // Farthest first, draw by draw and then face by face, without
+                // hiding what's behind in the depth buffer
+                translucent.sort_by(|a, b| b.0.total_cmp(&a.0));
+                gl::Enable(gl::BLEND);
+                gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+                gl::DepthMask(gl::FALSE);
+                for (_, draw, part_model) in &translucent {
+                    let depths = draw
+                        .triangle_centers
+                        .iter()
+                        .map(|&center| depth(part_model.transform_point(center)));
+                    self.reorder_triangles(draw, &back_to_front(depths));
+                    self.draw_material(draw, part_model);
+                }
+                gl::DepthMask(gl::TRUE);
+                gl::Disable(gl::BLEND);
             }
 
-            gl::DrawElements(
-                gl::TRIANGLES,
-                self.num_indices,
-                gl::UNSIGNED_INT,
-                std::ptr::null(),
-            );
+            if self.line_index_count > 0 || self.point_index_count > 0 {
+                gl::UniformMatrix4fv(self.model_loc, 1, gl::TRUE, model.as_ptr());
+                gl::Uniform1i(self.unlit_primitives_loc, 1);
+                let point_first_index = self.line_first_index + self.line_index_count as usize;
+                for (mode, first_index, count) in [
+                    (gl::LINES, self.line_first_index, self.line_index_count),
+                    (gl::POINTS, point_first_index, self.point_index_count),
+                ] {
+                    gl::DrawElements(
+                        mode,
+                        count,
+                        gl::UNSIGNED_INT,
+                        (first_index * std::mem::size_of::<u32>()) as *const _,
+                    );
+                }
+                gl::Uniform1i(self.unlit_primitives_loc, 0);
+            }
 
             gl::BindVertexArray(0);
         }
     }
 
+    unsafe fn draw_material(&self, draw: &MaterialDraw, part_model: &Mat4) {
+        let part = &self.parts[draw.part];
+        gl::UniformMatrix4fv(self.model_loc, 1, gl::TRUE, part_model.as_ptr());
+        gl::Uniform3f(self.part_color_loc, part.color.x, part.color.y, part.color.z);
+
+        self.bind_material(draw);
+        gl::DrawElements(
+            gl::TRIANGLES,
+            draw.index_count,
+            gl::UNSIGNED_INT,
+            (draw.first_index * std::mem::size_of::<u32>()) as *const _,
+        );
+    }
+
+    /// Rewrites a draw's section of the index buffer with its faces in
+    /// `order`. The buffer is the one bound to the vertex array.
+    unsafe fn reorder_triangles(&self, draw: &MaterialDraw, order: &[usize]) {
+        let section = draw.first_index..draw.first_index + draw.index_count as usize;
+        let Some(triangles) = self.triangle_indices.get(section) else {
+            return;
+        };
+        let indices: Vec<u32> = order
+            .iter()
+            .flat_map(|&triangle| &triangles[triangle * 3..triangle * 3 + 3])
+            .copied()
+            .collect();
+        gl::BufferSubData(
+            gl::ELEMENT_ARRAY_BUFFER,
+            (draw.first_index * std::mem::size_of::<u32>()) as gl::types::GLintptr,
+            (indices.len() * std::mem::size_of::<u32>()) as gl::types::GLsizeiptr,
+            indices.as_ptr() as *const _,
+        );
+    }
+
+    /// Whether a draw is blended: it can come out less than opaque because
+    /// its material isn't fully opaque, it has an alpha map, or the mode
+    /// shows a diffuse texture with alpha.
+    fn is_translucent(&self, draw: &MaterialDraw) -> bool {
+        let (mode, _) = self.alpha_mode.for_material(draw.material.alpha);
+        if mode != AlphaMode::Blend {
+            return false;
+        }
+        let texture = match draw.diffuse_map {
+            Some(id) => self
+                .material_textures
+                .iter()
+                .map(|(_, texture)| texture)
+                .find(|texture| texture.id == id),
+            None => self.texture.as_ref(),
+        };
+        let texture_has_alpha =
+            texture.is_some_and(|texture| texture.has_alpha) && self.shows_texture_alpha(draw);
+        draw.material.opacity < 1.0 || draw.alpha_map.is_some() || texture_has_alpha
+    }
+
+    /// Whether the colors shown, during a transition on either side of it,
+    /// carry the diffuse texture's alpha. Lit mode only samples a material's
+    /// own map; the other modes ignore textures.
+    fn shows_texture_alpha(&self, draw: &MaterialDraw) -> bool {
+        [self.current_mode, self.render_mode]
+            .iter()
+            .any(|mode| match mode {
+                RenderMode::Texture => true,
+                RenderMode::Lit => draw.diffuse_map.is_some(),
+                RenderMode::Vertex | RenderMode::Face | RenderMode::Color => false,
+            })
+    }
+
+    unsafe fn bind_material(&self, draw: &MaterialDraw) {
+        let locs = &self.material_locs;
+        let material = &draw.material;
+        let [r, g, b] = material.ambient;
+        gl::Uniform3f(locs.ambient, r, g, b);
+        let [r, g, b] = material.diffuse;
+        gl::Uniform3f(locs.diffuse, r, g, b);
+        let [r, g, b] = material.specular;
+        gl::Uniform3f(locs.specular, r, g, b);
+        gl::Uniform1f(locs.shininess, material.shininess);
+        gl::Uniform1f(locs.opacity, material.opacity);
+        gl::Uniform1i(locs.illumination, material.illumination as i32);
+        gl::Uniform1f(locs.bump_multiplier, material.bump_multiplier);
+        let (alpha_mode, alpha_cutoff) = self.alpha_mode.for_material(material.alpha);
+        gl::Uniform1i(self.alpha_mode_loc, alpha_mode.shader_value());
+        gl::Uniform1f(self.alpha_cutoff_loc, alpha_cutoff);
+
+        // Without its own map, a material shows the command-line texture
+        let diffuse_map = draw.diffuse_map.or(self.texture.as_ref().map(|t| t.id));
+        gl::Uniform1i(locs.has_diffuse_map, draw.diffuse_map.is_some() as i32);
+        gl::Uniform1i(locs.has_bump_map, draw.bump_map.is_some() as i32);
+        gl::Uniform1i(locs.has_alpha_map, draw.alpha_map.is_some() as i32);
+        for (unit, texture) in [
+            (DIFFUSE_UNIT, diffuse_map),
+            (BUMP_UNIT, draw.bump_map),
+            (ALPHA_UNIT, draw.alpha_map),
+        ] {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D, texture.unwrap_or(0));
+        }
+        gl::ActiveTexture(gl::TEXTURE0);
+    }
+
+    /// Sections of the index buffer built by `build_vertex_buffer`. Triangle
+    /// indices are kept to re-sort translucent faces.
+    pub fn set_primitives(
+        &mut self,
+        indices: &[u32],
+        face_count: usize,
+        line_count: usize,
+        point_count: usize,
+    ) {
+        self.triangle_indices = indices[..face_count * 3].to_vec();
+        self.line_first_index = face_count * 3;
+        self.line_index_count = (line_count * 2) as i32;
+        self.point_index_count = point_count as i32;
+    }
+
+    /// Models with vertex colors get an extra render mode showing them.
+    pub fn set_has_vertex_colors(&mut self, has_vertex_colors: bool) {
+        self.has_vertex_colors = has_vertex_colors;
+    }
+
+    pub fn set_parts(&mut self, parts: &[Part]) {
+        self.parts = parts
+            .iter()
+            .enumerate()
+            .map(|(index, part)| PartState {
+                name: part.name.clone(),
+                visible: true,
+                offset: Vec3::ZERO,
+                color: part_color(index),
+            })
+            .collect();
+        self.selected_part = 0;
+        self.isolate_part = false;
+    }
+
+    pub fn select_next_part(&mut self) {
+        if !self.parts.is_empty() {
+            self.selected_part = (self.selected_part + 1) % self.parts.len();
+        }
+    }
+
+    pub fn toggle_selected_part(&mut self) {
+        if let Some(part) = self.parts.get_mut(self.selected_part) {
+            part.visible = !part.visible;
+        }
+    }
+
+    pub fn toggle_isolate_part(&mut self) {
+        self.isolate_part = !self.isolate_part;
+    }
+
+    pub fn toggle_part_colors(&mut self) {
+        self.color_parts = !self.color_parts;
+    }
+
+    pub fn move_selected_part(&mut self, delta: Vec3) {
+        if let Some(part) = self.parts.get_mut(self.selected_part) {
+            part.offset += delta;
+        }
+    }
+
+    /// Window title describing the selected part, e.g. `part 2/3: Cube (hidden)`.
+    pub fn part_title(&self) -> String {
+        match self.parts.get(self.selected_part) {
+            Some(part) => format!(
+                "SCOP - part {}/{}: {}{}{}",
+                self.selected_part + 1,
+                self.parts.len(),
+                part.name,
+                if part.visible { "" } else { " (hidden)" },
+                if self.isolate_part { " (isolated)" } else { "" },
+            ),
+            None => "SCOP".to_string(),
+        }
+    }
+
+    /// Prepares one draw per material range, loading the texture maps the
+    /// materials reference. Maps that fail to load are left out and reported.
+    pub fn set_materials(&mut self, model_data: &ObjData) -> Vec<String> {
+        let mut errors = Vec::new();
+        let mut draws = Vec::with_capacity(model_data.material_ranges.len());
+        for range in &model_data.material_ranges {
+            let material = range
+                .material
+                .map_or_else(Material::default, |index| model_data.materials[index].clone());
+            let faces = &model_data.faces[range.first_face..range.first_face + range.face_count];
+            let triangle_centers: Vec<Vec3> = faces
+                .iter()
+                .map(|face| {
+                    let corners = face
+                        .vertex_indices
+                        .map(|index| Vec3::from(model_data.vertices[index as usize]));
+                    (corners[0] + corners[1] + corners[2]) / 3.0
+                })
+                .collect();
+            let center = triangle_centers
+                .iter()
+                .fold(Vec3::ZERO, |sum, &center| sum + center)
+                / triangle_centers.len().max(1) as f32;
+            let mut load = |name: Option<&str>, bytes: Option<&[u8]>| {
+                let name = name?;
+                self.load_material_texture(name, bytes)
+                    .map_err(|e| errors.push(format!("{}: {}", name, e)))
+                    .ok()
+            };
+            let diffuse_map = match &material.diffuse_image {
+                Some(image) => load(Some(&image.name), Some(&image.bytes)),
+                None => load(material.diffuse_map.as_deref(), None),
+            };
+            draws.push(MaterialDraw {
+                part: range.part,
+                first_index: range.first_face * 3,
+                index_count: (range.face_count * 3) as i32,
+                center,
+                triangle_centers,
+                diffuse_map,
+                bump_map: load(material.bump_map.as_deref(), None),
+                alpha_map: load(material.alpha_map.as_deref(), None),
+                material,
+            });
+        }
+        self.draws = draws;
+        errors
+    }
+
+    /// Loads a map from the file `name`, or from `bytes` when the model
+    /// carries the image itself.
+    fn load_material_texture(
+        &mut self,
+        name: &str,
+        bytes: Option<&[u8]>,
+    ) -> Result<gl::types::GLuint, String> {
+        if let Some((_, texture)) = self.material_textures.iter().find(|(n, _)| n == name) {
+            return Ok(texture.id);
+        }
+        let texture = match bytes {
+            Some(bytes) => Texture::from_bytes(bytes)?,
+            None => Texture::new(name)?,
+        };
+        let id = texture.id;
+        self.material_textures.push((name.to_string(), texture));
+        Ok(id)
+    }
+
+    unsafe fn upload_lights(&self, camera: &Camera) {
+        if self.num_lights_loc >= 0 {
+            gl::Uniform1i(self.num_lights_loc, self.lights.len() as i32);
+        }
+        if self.view_position_loc >= 0 {
+            let p = camera.position;
+            gl::Uniform3f(self.view_position_loc, p.x, p.y, p.z);
+        }
+
+        for (light, locs) in self.lights.iter().zip(&self.light_locs) {
+            let kind = match light.kind {
+                LightKind::Directional => 0,
+                LightKind::Point => 1,
+            };
+            gl::Uniform1i(locs.kind, kind);
+            gl::Uniform3f(locs.position, light.position.x, light.position.y, light.position.z);
+            gl::Uniform3f(locs.color, light.color.x, light.color.y, light.color.z);
+            gl::Uniform1f(locs.attenuation, light.attenuation);
+        }
+    }
+
+    pub fn set_lights(&mut self, mut lights: Vec<Light>) {
+        lights.truncate(MAX_LIGHTS);
+        self.lights = lights;
+        self.active_light = 0;
+    }
+
+    pub fn select_next_light(&mut self) {
+        if !self.lights.is_empty() {
+            self.active_light = (self.active_light + 1) % self.lights.len();
+        }
+    }
+
+    pub fn move_active_light(&mut self, delta: Vec3) {
+        if let Some(light) = self.lights.get_mut(self.active_light) {
+            light.move_by(delta);
+        }
+    }
+
     pub fn load_texture(&mut self, path: &str) -> Result<(), String> {
         self.texture = Some(Texture::new(path)?);
         Ok(())
     }
 
+    pub fn cycle_alpha_mode(&mut self) -> AlphaMode {
+        self.alpha_mode = match self.alpha_mode {
+            AlphaMode::Opaque => AlphaMode::Test,
+            AlphaMode::Test => AlphaMode::Blend,
+            AlphaMode::Blend => AlphaMode::Opaque,
+        };
+        self.alpha_mode
+    }
+
+    pub fn toggle_face_color_space(&mut self) {
+        self.face_color_space = match self.face_color_space {
+            NormalSpace::Object => NormalSpace::World,
+            NormalSpace::World => NormalSpace::Object,
+        };
+    }
+
     pub fn cycle_render_mode(&mut self) {
         self.target_mode = match self.current_mode {
             RenderMode::Vertex => RenderMode::Face,
             RenderMode::Face => RenderMode::Texture,
-            RenderMode::Texture => RenderMode::Vertex,
+            RenderMode::Texture => RenderMode::Lit,
+            RenderMode::Lit if self.has_vertex_colors => RenderMode::Color,
+            RenderMode::Lit | RenderMode::Color => RenderMode::Vertex,
         };
         self.transition_progress = 0.0;
     }
@@ -205,7 +744,11 @@ impl Renderer {
         self.texture_blend = match (self.current_mode, self.target_mode) {
             (RenderMode::Vertex, RenderMode::Face) => lerp(0.0, 0.5, self.transition_progress),
             (RenderMode::Face, RenderMode::Texture) => lerp(0.5, 1.0, self.transition_progress),
-            (RenderMode::Texture, RenderMode::Vertex) => lerp(1.0, 2.0, self.transition_progress),
+            (RenderMode::Texture, RenderMode::Lit) => lerp(1.0, 1.5, self.transition_progress),
+            (RenderMode::Lit, RenderMode::Vertex) | (RenderMode::Lit, RenderMode::Color) => {
+                lerp(1.5, 2.0, self.transition_progress)
+            }
+            (RenderMode::Color, RenderMode::Vertex) => lerp(2.0, 2.5, self.transition_progress),
             _ => self.texture_blend,
         };
 
@@ -215,3 +758,44 @@ impl Renderer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn faces_are_blended_back_to_front() {
+        // Looking down -z from z = 5
+        let eye = Vec3::new(0.0, 0.0, 5.0);
+        let forward = Vec3::new(0.0, 0.0, -1.0);
+        let centers = [
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 0.0, -2.0),
+            Vec3::new(3.0, 0.0, 0.0),
+        ];
+        let order = |model: Mat4| {
+            back_to_front(
+                centers
+                    .iter()
+                    .map(|&center| (model.transform_point(center) - eye).dot(forward)),
+            )
+        };
+        assert_eq!(order(Mat4::IDENTITY), [1, 2, 0]);
+        // Turning the model around brings the far face to the front
+        assert_eq!(order(Mat4::rotation_y(std::f32::consts::PI)), [0, 2, 1]);
+        assert_eq!(back_to_front([1.0, 1.0].into_iter()), [0, 1]);
+    }
+
+    #[test]
+    fn materials_choose_how_alpha_is_applied() {
+        let blend = AlphaMode::Blend;
+        assert_eq!(blend.for_material(AlphaUsage::Blend), (AlphaMode::Blend, ALPHA_CUTOFF));
+        assert_eq!(blend.for_material(AlphaUsage::Mask(0.3)), (AlphaMode::Test, 0.3));
+        assert_eq!(blend.for_material(AlphaUsage::Opaque), (AlphaMode::Opaque, ALPHA_CUTOFF));
+        let test = AlphaMode::Test;
+        assert_eq!(test.for_material(AlphaUsage::Blend), (AlphaMode::Test, ALPHA_CUTOFF));
+        assert_eq!(test.for_material(AlphaUsage::Mask(0.3)), (AlphaMode::Test, 0.3));
+        let opaque = AlphaMode::Opaque;
+        assert_eq!(opaque.for_material(AlphaUsage::Mask(0.3)).0, AlphaMode::Opaque);
+    }
+}
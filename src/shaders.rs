@@ -1,4 +1,3 @@
-use gl;
 
 pub fn compile_shader(
     src: &str,
@@ -62,3 +61,8 @@ pub fn link_program(
     }
     Ok(program)
 }
+
+pub fn uniform_location(program: gl::types::GLuint, name: &str) -> i32 {
+    let c_name = std::ffi::CString::new(name).unwrap();
+    unsafe { gl::GetUniformLocation(program, c_name.as_ptr()) }
+}
@@ -2,10 +2,54 @@
 
 in vec3 FragPos;
 in vec3 Normal;
+in vec3 ObjectNormal;
 in vec2 TexCoord;
+in vec3 VertexColor;
 
-uniform sampler2D textureSampler;
-uniform float textureBlend;  // 0.0 = vertex/face, 1.0 = texture
+uniform sampler2D textureSampler;  // The material's diffuse map, or the default texture
+uniform sampler2D bumpSampler;
+uniform sampler2D alphaSampler;
+uniform float textureBlend;  // 0.0 = vertex, 0.5 = face, 1.0 = texture, 1.5 = lit, 2.0 = vertex colors
+uniform bool worldSpaceFaces;  // Color faces by world or object space normals
+uniform bool colorParts;       // Replace every mode's color with the part's own
+uniform vec3 partColor;
+uniform bool hasVertexColors;  // Whether the model has per-vertex colors
+uniform bool unlitPrimitives;  // Drawing OBJ lines and points, which have no normals
+uniform int alphaMode;         // One of the ALPHA_ modes below
+uniform float alphaCutoff;     // Fragments below it are discarded by ALPHA_TEST
+
+#define ALPHA_OPAQUE 0  // Alpha is ignored
+#define ALPHA_TEST 1    // Fragments below the cutoff are discarded, the rest are opaque
+#define ALPHA_BLEND 2   // Alpha is written out for blending
+
+#define MAX_LIGHTS 4
+#define DIRECTIONAL_LIGHT 0
+#define POINT_LIGHT 1
+
+struct Light {
+    int kind;
+    vec3 position;     // Direction towards the light for directional lights
+    vec3 color;
+    float attenuation; // Quadratic falloff for point lights
+};
+
+struct Material {
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+    float shininess;
+    float opacity;
+    int illumination;  // 0 = color only, 1 = no specular, 2+ = Blinn-Phong
+    bool hasDiffuseMap;
+    bool hasBumpMap;
+    float bumpMultiplier;
+    bool hasAlphaMap;
+};
+
+uniform Light lights[MAX_LIGHTS];
+uniform Material material;
+uniform int numLights;
+uniform vec3 viewPosition;
 
 out vec4 FragColor;
 
@@ -38,6 +82,61 @@ vec3 getFaceColor(vec3 normal) {
     }
 }
 
+// Tilts the normal along the bump map's slope using screen-space derivatives,
+// so no tangents are needed
+vec3 perturbNormal(vec3 normal) {
+    vec3 dPdx = dFdx(FragPos);
+    vec3 dPdy = dFdy(FragPos);
+    float height = texture(bumpSampler, TexCoord).r * material.bumpMultiplier;
+    vec3 r1 = cross(dPdy, normal);
+    vec3 r2 = cross(normal, dPdx);
+    float det = dot(dPdx, r1);
+    vec3 gradient = sign(det) * (dFdx(height) * r1 + dFdy(height) * r2);
+    return normalize(abs(det) * normal - gradient);
+}
+
+// Blinn-Phong shading of the current material by every active light, keeping
+// the diffuse map's alpha
+vec4 getLitColor(vec3 normal) {
+    vec4 texel = material.hasDiffuseMap ? texture(textureSampler, TexCoord) : vec4(1.0);
+    vec3 albedo = material.diffuse * texel.rgb;
+    if (material.illumination == 0) {
+        return vec4(albedo, texel.a);
+    }
+    if (material.hasBumpMap) {
+        normal = perturbNormal(normal);
+    }
+
+    vec3 viewDir = normalize(viewPosition - FragPos);
+    // Light both sides so open meshes don't turn black from behind
+    if (dot(normal, viewDir) < 0.0) {
+        normal = -normal;
+    }
+
+    vec3 color = 0.15 * material.ambient * texel.rgb; // ambient
+    for (int i = 0; i < numLights; i++) {
+        vec3 lightDir;
+        float attenuation = 1.0;
+        if (lights[i].kind == DIRECTIONAL_LIGHT) {
+            lightDir = normalize(lights[i].position);
+        } else {
+            vec3 toLight = lights[i].position - FragPos;
+            float distance = length(toLight);
+            lightDir = toLight / distance;
+            attenuation = 1.0 / (1.0 + lights[i].attenuation * distance * distance);
+        }
+
+        float diffuse = max(dot(normal, lightDir), 0.0);
+        vec3 halfway = normalize(lightDir + viewDir);
+        float specular = diffuse > 0.0 && material.illumination >= 2
+            ? pow(max(dot(normal, halfway), 0.0), material.shininess)
+            : 0.0;
+
+        color += attenuation * lights[i].color * (diffuse * albedo + specular * material.specular);
+    }
+    return vec4(color, texel.a);
+}
+
 void main() {
     // Compute all three color modes
     vec4 vertexColor = vec4(
@@ -46,8 +145,15 @@ void main() {
         0.8 + (FragPos.z + 1.0) * 0.2,
         1.0
     );
-    vec4 faceColor = vec4(getFaceColor(normalize(Normal)), 1.0);
+    if (unlitPrimitives) {
+        FragColor = hasVertexColors ? vec4(VertexColor, 1.0) : vertexColor;
+        return;
+    }
+    vec3 faceNormal = worldSpaceFaces ? Normal : ObjectNormal;
+    vec4 faceColor = vec4(getFaceColor(normalize(faceNormal)), 1.0);
     vec4 textureColor = texture(textureSampler, TexCoord);
+    vec4 litColor = getLitColor(normalize(Normal));
+    vec4 colorAttribute = vec4(VertexColor, 1.0);
 
     vec4 finalColor;
 
@@ -60,11 +166,38 @@ void main() {
         // Face <-> Texture
         float t = (textureBlend - 0.5) / 0.5;
         finalColor = mix(faceColor, textureColor, t);
+    } else if (textureBlend < 1.5) {
+        // Texture <-> Lit
+        float t = (textureBlend - 1.0) / 0.5;
+        finalColor = mix(textureColor, litColor, t);
+    } else if (textureBlend < 2.0) {
+        // Lit <-> Vertex colors, or Lit -> Vertex for models without colors
+        float t = (textureBlend - 1.5) / 0.5;
+        finalColor = mix(litColor, hasVertexColors ? colorAttribute : vertexColor, t);
     } else {
-        // Direct Texture->Vertex (no Face)
-        float t = textureBlend - 1.0;
-        finalColor = mix(textureColor, vertexColor, t);
+        // Vertex colors -> Vertex, or the end of Lit -> Vertex
+        float t = hasVertexColors ? (textureBlend - 2.0) / 0.5 : 1.0;
+        finalColor = mix(colorAttribute, vertexColor, t);
+    }
+
+    if (colorParts) {
+        // Simple headlight shading keeps the shape readable
+        float facing = abs(dot(normalize(Normal), normalize(viewPosition - FragPos)));
+        finalColor.rgb = partColor * (0.35 + 0.65 * facing);
     }
 
-    FragColor = finalColor;
+    float opacity = material.opacity;
+    if (material.hasAlphaMap) {
+        opacity *= texture(alphaSampler, TexCoord).r;
+    }
+    float alpha = finalColor.a * opacity;
+    if (alphaMode == ALPHA_OPAQUE) {
+        alpha = 1.0;
+    } else if (alphaMode == ALPHA_TEST) {
+        if (alpha < alphaCutoff) {
+            discard;
+        }
+        alpha = 1.0;
+    }
+    FragColor = vec4(finalColor.rgb, alpha);
 }
@@ -3,38 +3,30 @@
 layout(location = 0) in vec3 aPos;       // Position
 layout(location = 1) in vec2 aTexCoord;  // Texture coordinates
 layout(location = 2) in vec3 aNormal;    // Normal
+layout(location = 3) in vec3 aColor;     // Vertex color
 
 uniform mat4 projection;
+uniform mat4 view;
 uniform mat4 model;
-uniform float posOffset;
-uniform vec3 modelCenter;
-uniform vec3 modelPosition;
+uniform mat3 normalMatrix;
 
 out vec3 FragPos;
-out vec3 Normal;
+out vec3 Normal;        // World space
+out vec3 ObjectNormal;  // As stored in the mesh
 out vec2 TexCoord;
+out vec3 VertexColor;
 
 void main() {
-    // Center the model
-    vec3 centered = aPos - modelCenter;
-    
-    // Apply rotation
-    vec4 rotated = model * vec4(centered, 1.0);
-    
-    // Move back and apply translation
-    vec3 finalPos = rotated.xyz + modelCenter + modelPosition;
-    
-    // Apply camera offset
-    finalPos.z -= posOffset;
-    
-    // Use the projection matrix from camera
-    vec4 clipPos = projection * vec4(finalPos, 1.0);
-    
-    // Adjust final position
-    gl_Position = clipPos;
-    
+    // Place the vertex in world space
+    vec4 worldPos = model * vec4(aPos, 1.0);
+
+    // Project to clip space (perspective or orthographic, w holds the divide)
+    gl_Position = projection * view * worldPos;
+
     // Pass values to fragment shader
-    FragPos = finalPos;
-    Normal = aNormal;
+    FragPos = worldPos.xyz;
+    Normal = normalize(normalMatrix * aNormal);
+    ObjectNormal = aNormal;
     TexCoord = aTexCoord;
+    VertexColor = aColor;
 }
@@ -0,0 +1,304 @@
+use crate::normals;
+use crate::parser::{self, FaceVertex, MeshBuilder, ObjData, ObjError, ParseOptions};
+use std::collections::HashMap;
+
+const HEADER_SIZE: usize = 80;
+/// Normal, three corners and a 2-byte attribute count.
+const TRIANGLE_SIZE: usize = 50;
+
+/// Binary files are recognized by their size matching the triangle count,
+/// since plenty of them also start with "solid".
+pub fn is_binary(bytes: &[u8]) -> bool {
+    triangle_count(bytes)
+        .is_some_and(|count| HEADER_SIZE + 4 + count * TRIANGLE_SIZE == bytes.len())
+}
+
+/// Binary files can start with "solid" too, but their triangle count (and
+/// usually their header padding) contains zero bytes, which text never does.
+pub fn is_ascii(bytes: &[u8]) -> bool {
+    bytes.trim_ascii_start().starts_with(b"solid") && !bytes.contains(&0)
+}
+
+fn triangle_count(bytes: &[u8]) -> Option<usize> {
+    let count = bytes.get(HEADER_SIZE..HEADER_SIZE + 4)?;
+    Some(u32::from_le_bytes(count.try_into().unwrap()) as usize)
+}
+
+pub fn parse(path: &str, options: &ParseOptions) -> Result<ObjData, ObjError> {
+    let bytes = parser::read_file(path)?;
+    parse_bytes(&bytes, path, options)
+}
+
+/// Parses an ASCII or binary STL file. Identical corner positions are welded
+/// into one vertex; faces keep their facet normals unless a crease angle asks
+/// for smoothing.
+pub fn parse_bytes(
+    bytes: &[u8],
+    file_name: &str,
+    options: &ParseOptions,
+) -> Result<ObjData, ObjError> {
+    let mut builder = StlBuilder {
+        mesh: MeshBuilder::default(),
+        welded: HashMap::new(),
+        welded_normals: HashMap::new(),
+        smooth: options.normals.crease_angle.is_some(),
+    };
+    let mut warnings = Vec::new();
+
+    if !is_binary(bytes) && is_ascii(bytes) {
+        let mut state = AsciiState::default();
+        parser::read_lines(bytes, file_name, options, &mut warnings, |line, _| {
+            builder.parse_line(line, &mut state)
+        })?;
+    } else {
+        let expected = triangle_count(bytes).ok_or_else(|| ObjError::InvalidData {
+            file: file_name.to_string(),
+            message: "too short for an STL file".to_string(),
+        })?;
+        let available = (bytes.len() - HEADER_SIZE - 4) / TRIANGLE_SIZE;
+        if available != expected {
+            let error = ObjError::InvalidData {
+                file: file_name.to_string(),
+                message: format!(
+                    "header announces {} triangles but the file holds {}",
+                    expected, available
+                ),
+            };
+            parser::report(error, options, &mut warnings)?;
+        }
+
+        let part = builder.mesh.part("default");
+        for triangle in bytes[HEADER_SIZE + 4..]
+            .chunks_exact(TRIANGLE_SIZE)
+            .take(expected)
+        {
+            let value =
+                |i: usize| f32::from_le_bytes(triangle[i * 4..i * 4 + 4].try_into().unwrap());
+            let vector = |i: usize| [value(i), value(i + 1), value(i + 2)];
+            builder.add_facet(part, vector(0), &[vector(3), vector(6), vector(9)]);
+        }
+    }
+
+    Ok(builder.mesh.finish(&options.normals, warnings))
+}
+
+struct StlBuilder {
+    mesh: MeshBuilder,
+    /// Vertex index by position bits, which is how STL's separate triangles
+    /// are stitched back into a connected mesh.
+    welded: HashMap<[u32; 3], u32>,
+    welded_normals: HashMap<[u32; 3], u32>,
+    /// Ignore facet normals and smooth within the crease angle.
+    smooth: bool,
+}
+
+/// The facet being read from an ASCII file.
+#[derive(Default)]
+struct AsciiState {
+    part: usize,
+    facet: Option<([f32; 3], Vec<[f32; 3]>)>,
+}
+
+impl StlBuilder {
+    fn weld(map: &mut HashMap<[u32; 3], u32>, values: &mut Vec<[f32; 3]>, value: [f32; 3]) -> u32 {
+        // Adding zero turns -0.0 into 0.0 so both weld together
+        let value = value.map(|v| v + 0.0);
+        *map.entry(value.map(f32::to_bits)).or_insert_with(|| {
+            values.push(value);
+            (values.len() - 1) as u32
+        })
+    }
+
+    fn add_facet(&mut self, part: usize, normal: [f32; 3], corners: &[[f32; 3]]) {
+        // Many exporters write zero normals, which are better regenerated
+        let has_normal = !self.smooth
+            && normal.iter().all(|v| v.is_finite())
+            && normal.iter().any(|&v| v != 0.0);
+        let normal = has_normal
+            .then(|| Self::weld(&mut self.welded_normals, &mut self.mesh.normals, normal));
+        let corners: Vec<FaceVertex> = corners
+            .iter()
+            .map(|&position| FaceVertex {
+                normal,
+                ..FaceVertex::at(Self::weld(
+                    &mut self.welded,
+                    &mut self.mesh.vertices,
+                    position,
+                ))
+            })
+            .collect();
+        let smoothing_group = if self.smooth { 1 } else { normals::FLAT };
+        self.mesh.add_face(&corners, part, None, smoothing_group);
+    }
+
+    fn parse_line(
+        &mut self,
+        line: &parser::LineContext,
+        state: &mut AsciiState,
+    ) -> Result<(), ObjError> {
+        let tokens = line.tokens();
+        let Some((keyword, values)) = tokens.split_first() else {
+            return Ok(());
+        };
+
+        match (keyword.text, &mut state.facet) {
+            ("solid", None) => {
+                let name: Vec<&str> = values.iter().map(|v| v.text).collect();
+                state.part = match name.is_empty() {
+                    true => self.mesh.part("default"),
+                    false => self.mesh.part(&name.join(" ")),
+                };
+            }
+            ("endsolid", None) => {}
+            ("facet", None) => match values {
+                [normal, rest @ ..] if normal.text == "normal" && rest.len() == 3 => {
+                    state.facet = Some((parser::parse_floats(rest, line)?, Vec::new()));
+                }
+                _ => return Err(line.malformed_statement(keyword.column)),
+            },
+            ("outer", Some(_)) if values.len() == 1 && values[0].text == "loop" => {}
+            ("endloop", Some(_)) => {}
+            ("vertex", Some((_, corners))) if values.len() == 3 => {
+                corners.push(parser::parse_floats(values, line)?);
+            }
+            ("endfacet", Some(_)) => {
+                let (normal, corners) = state.facet.take().unwrap();
+                self.add_facet(state.part, normal, &corners);
+            }
+            ("solid" | "endsolid" | "facet" | "outer" | "endloop" | "vertex" | "endfacet", _) => {
+                return Err(line.malformed_statement(keyword.column))
+            }
+            _ => {
+                return Err(ObjError::UnsupportedStatement {
+                    location: line.at(keyword.column),
+                    text: keyword.text.to_string(),
+                })
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two triangles of a unit square sharing the diagonal.
+    const ASCII: &str = "solid square
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 1 1 0
+    endloop
+  endfacet
+  facet normal 0 0 0
+    outer loop
+      vertex 0 0 -0
+      vertex 1 1 0
+      vertex 0 1 0
+    endloop
+  endfacet
+endsolid square
+";
+
+    fn binary(triangles: &[[[f32; 3]; 4]], count: u32) -> Vec<u8> {
+        // Header deliberately starts like an ASCII file
+        let mut bytes = b"solid exported by some CAD tool".to_vec();
+        bytes.resize(HEADER_SIZE, 0);
+        bytes.extend(count.to_le_bytes());
+        for triangle in triangles {
+            for value in triangle.iter().flatten() {
+                bytes.extend(value.to_le_bytes());
+            }
+            bytes.extend([0, 0]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn ascii_files_are_welded() {
+        let obj = parse_bytes(ASCII.as_bytes(), "square.stl", &ParseOptions::strict()).unwrap();
+        assert_eq!(obj.vertices.len(), 4);
+        assert_eq!(obj.faces.len(), 2);
+        assert_eq!(obj.faces[1].vertex_indices, [0, 2, 3]);
+        assert_eq!(obj.parts[0].name, "square");
+        // The zero normal was regenerated
+        let normal = obj.faces[1].normal_indices[0] as usize;
+        assert_eq!(obj.normals[normal], [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn binary_files_are_detected_by_size() {
+        let square = [
+            [
+                [0.0, 0.0, 1.0],
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [1.0, 1.0, 0.0],
+            ],
+            [
+                [0.0, 0.0, 1.0],
+                [0.0, 0.0, 0.0],
+                [1.0, 1.0, 0.0],
+                [0.0, 1.0, 0.0],
+            ],
+        ];
+        let bytes = binary(&square, 2);
+        assert!(is_binary(&bytes) && !is_ascii(&bytes));
+
+        let obj = parse_bytes(&bytes, "square.stl", &ParseOptions::strict()).unwrap();
+        assert_eq!(obj.vertices.len(), 4);
+        assert_eq!(obj.faces.len(), 2);
+        assert_eq!(obj.faces[0].normal_indices, obj.faces[1].normal_indices);
+    }
+
+    #[test]
+    fn truncated_binary_files_are_reported() {
+        let triangle = [
+            [0.0, 0.0, 1.0],
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+        ];
+        let bytes = binary(&[triangle], 3);
+        assert!(matches!(
+            parse_bytes(&bytes, "short.stl", &ParseOptions::strict()),
+            Err(ObjError::InvalidData { .. })
+        ));
+
+        let obj = parse_bytes(&bytes, "short.stl", &ParseOptions::default()).unwrap();
+        assert_eq!(obj.faces.len(), 1);
+        assert_eq!(obj.warnings.len(), 1);
+        assert!(parse_bytes(b"nothing", "tiny.stl", &ParseOptions::default()).is_err());
+    }
+
+    #[test]
+    fn malformed_ascii_facets_are_dropped() {
+        let source = ASCII.replace("vertex 1 0 0", "vertex 1 0");
+        match parse_bytes(source.as_bytes(), "bad.stl", &ParseOptions::strict()) {
+            Err(ObjError::MalformedStatement { location, .. }) => assert_eq!(location.line, 5),
+            other => panic!("unexpected result: {:?}", other.map(|o| o.faces.len())),
+        }
+
+        let obj = parse_bytes(source.as_bytes(), "bad.stl", &ParseOptions::default()).unwrap();
+        // The facet is left with two corners
+        assert_eq!(obj.warnings.len(), 1);
+        assert_eq!(obj.faces.len(), 1);
+        assert_eq!(obj.face_stats.dropped, 1);
+    }
+
+    #[test]
+    fn crease_angle_smooths_across_facets() {
+        let options = ParseOptions {
+            normals: normals::NormalOptions {
+                crease_angle: Some(30.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let obj = parse_bytes(ASCII.as_bytes(), "square.stl", &options).unwrap();
+        // Coplanar facets share their generated normal
+        assert_eq!(obj.normals.len(), 1);
+    }
+}
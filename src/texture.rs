@@ -1,32 +1,123 @@
-use gl;
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
 
+use crate::{bmp, netpbm, png, tga};
+
+/// Images wider or taller than this are rejected before anything is
+/// allocated for them.
+pub const MAX_DIMENSION: usize = 16384;
+
+/// Image file formats textures can be loaded from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Bmp,
+    Png,
+    Netpbm,
+    Tga,
+}
+
+impl ImageFormat {
+    /// Works the format out from the first bytes of a file. TGA has no
+    /// signature, so it's tried last.
+    pub fn sniff(bytes: &[u8]) -> Option<Self> {
+        if bmp::is_bmp(bytes) {
+            Some(ImageFormat::Bmp)
+        } else if png::is_png(bytes) {
+            Some(ImageFormat::Png)
+        } else if netpbm::is_netpbm(bytes) {
+            Some(ImageFormat::Netpbm)
+        } else if tga::is_tga(bytes) {
+            Some(ImageFormat::Tga)
+        } else {
+            None
+        }
+    }
+}
+
+/// A decoded image: 8-bit RGBA pixels, rows from top to bottom.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    /// A transparent black image.
+    pub fn new(width: usize, height: usize) -> Result<Self, String> {
+        if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+            return Err(format!("unsupported image size {}x{}", width, height));
+        }
+        Ok(Image {
+            width,
+            height,
+            pixels: vec![0; width * height * 4],
+        })
+    }
+
+    /// Decodes an image file, picking the decoder from its contents.
+    pub fn decode(bytes: &[u8]) -> Result<Self, String> {
+        match ImageFormat::sniff(bytes) {
+            Some(ImageFormat::Bmp) => bmp::decode(bytes),
+            Some(ImageFormat::Png) => png::decode(bytes),
+            Some(ImageFormat::Netpbm) => netpbm::decode(bytes),
+            Some(ImageFormat::Tga) => tga::decode(bytes),
+            None => Err("unrecognized image format".to_string()),
+        }
+    }
+
+    /// Whether any pixel is less than fully opaque.
+    pub fn has_transparency(&self) -> bool {
+        self.pixels.chunks_exact(4).any(|pixel| pixel[3] < 255)
+    }
+
+    #[cfg(test)]
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let at = (y * self.width + x) * 4;
+        [
+            self.pixels[at],
+            self.pixels[at + 1],
+            self.pixels[at + 2],
+            self.pixels[at + 3],
+        ]
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: [u8; 4]) {
+        let at = (y * self.width + x) * 4;
+        self.pixels[at..at + 4].copy_from_slice(&color);
+    }
+}
+
 pub struct Texture {
     pub id: gl::types::GLuint,
+    /// The image has transparent or translucent pixels, so surfaces using it
+    /// need blending.
+    pub has_alpha: bool,
 }
 
 impl Texture {
     pub fn new(path: &str) -> Result<Self, String> {
-        let mut file = File::open(Path::new(path)).map_err(|e| e.to_string())?;
+        let mut file = File::open(Path::new(path)).map_err(|e| format!("{}: {}", path, e))?;
         let mut bytes = Vec::new();
-        file.read_to_end(&mut bytes).map_err(|e| e.to_string())?;
-
-        if bytes.len() < 54 {
-            // BMP header size
-            return Err("Invalid BMP file".to_string());
-        }
+        file.read_to_end(&mut bytes)
+            .map_err(|e| format!("{}: {}", path, e))?;
+        Self::from_bytes(&bytes).map_err(|e| format!("{}: {}", path, e))
+    }
 
-        // Verify BMP signature
-        if bytes[0] != b'B' || bytes[1] != b'M' {
-            return Err("Not a valid BMP file".to_string());
-        }
+    /// Uploads an image file that is already in memory.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let image = Image::decode(bytes)?;
 
-        let width = i32::from_le_bytes(bytes[18..22].try_into().unwrap());
-        let height = i32::from_le_bytes(bytes[22..26].try_into().unwrap());
-        let data_offset = u32::from_le_bytes(bytes[10..14].try_into().unwrap()) as usize;
-        let _bpp = u16::from_le_bytes(bytes[28..30].try_into().unwrap()) as usize;
+        // GL expects the bottom row first
+        let row_size = image.width * 4;
+        let pixels: Vec<u8> = image
+            .pixels
+            .chunks_exact(row_size)
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
 
         let mut texture_id = 0;
 
@@ -40,21 +131,56 @@ impl Texture {
             gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
             gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
 
+            // Rows are tightly packed, whatever their width
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
             gl::TexImage2D(
                 gl::TEXTURE_2D,
                 0,
-                gl::RGB as i32,
-                width,
-                height,
+                gl::RGBA as i32,
+                image.width as i32,
+                image.height as i32,
                 0,
-                gl::BGR,
+                gl::RGBA,
                 gl::UNSIGNED_BYTE,
-                bytes[data_offset..].as_ptr() as *const _,
+                pixels.as_ptr() as *const _,
             );
 
             gl::GenerateMipmap(gl::TEXTURE_2D);
         }
 
-        Ok(Texture { id: texture_id })
+        Ok(Texture {
+            id: texture_id,
+            has_alpha: image.has_transparency(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_are_sniffed_and_alpha_is_kept() {
+        let pgm = b"P5 2 1 255\n\x00\xff";
+        assert_eq!(ImageFormat::sniff(pgm), Some(ImageFormat::Netpbm));
+        assert_eq!(ImageFormat::sniff(b"BM"), Some(ImageFormat::Bmp));
+        assert_eq!(ImageFormat::sniff(b"# model\nv 0 0 0\n"), None);
+        assert!(Image::decode(b"v 0 0 0\n").is_err());
+        assert!(!Image::decode(pgm).unwrap().has_transparency());
+
+        // A 32-bit top-down BMP with one half transparent pixel
+        let mut bmp = b"BM".to_vec();
+        bmp.extend([0; 8]);
+        bmp.extend(54u32.to_le_bytes());
+        bmp.extend(40u32.to_le_bytes());
+        bmp.extend(2i32.to_le_bytes());
+        bmp.extend((-1i32).to_le_bytes());
+        bmp.extend(1u16.to_le_bytes());
+        bmp.extend(32u16.to_le_bytes());
+        bmp.extend([0; 24]);
+        bmp.extend([0, 0, 255, 255, 0, 255, 0, 128]);
+        let image = Image::decode(&bmp).unwrap();
+        assert_eq!(image.pixel(1, 0), [0, 255, 0, 128]);
+        assert!(image.has_transparency());
     }
 }
@@ -0,0 +1,240 @@
+use crate::texture::Image;
+
+const HEADER_SIZE: usize = 18;
+
+/// Whether `bytes` start with a plausible TGA header. The format has no
+/// signature, so this checks that every header field has a valid value.
+pub fn is_tga(bytes: &[u8]) -> bool {
+    let Some(header) = bytes.get(..HEADER_SIZE) else {
+        return false;
+    };
+    let (color_map_type, image_type, depth) = (header[1], header[2], header[16]);
+    let width = u16::from_le_bytes([header[12], header[13]]);
+    let height = u16::from_le_bytes([header[14], header[15]]);
+    let depth_valid = match image_type & !8 {
+        1 => color_map_type == 1 && depth == 8,
+        2 => matches!(depth, 15 | 16 | 24 | 32),
+        3 => depth == 8,
+        _ => false,
+    };
+    color_map_type <= 1 && depth_valid && width > 0 && height > 0 && header[17] & 0xc0 == 0
+}
+
+/// Decodes a color-mapped, true-color or grayscale TGA, uncompressed or RLE
+/// compressed, in any of its four pixel orders.
+pub fn decode(bytes: &[u8]) -> Result<Image, String> {
+    if !is_tga(bytes) {
+        return Err("not a TGA file".to_string());
+    }
+    let id_length = bytes[0] as usize;
+    let color_map_type = bytes[1];
+    let image_type = bytes[2];
+    let map_first = u16::from_le_bytes([bytes[3], bytes[4]]) as usize;
+    let map_length = u16::from_le_bytes([bytes[5], bytes[6]]) as usize;
+    let map_entry_size = bytes[7];
+    let width = u16::from_le_bytes([bytes[12], bytes[13]]) as usize;
+    let height = u16::from_le_bytes([bytes[14], bytes[15]]) as usize;
+    let depth = bytes[16];
+    let descriptor = bytes[17];
+    // The low descriptor bits count the alpha bits of each pixel
+    let has_alpha = descriptor & 0x0f != 0;
+    let right_to_left = descriptor & 0x10 != 0;
+    let top_down = descriptor & 0x20 != 0;
+
+    let mut at = HEADER_SIZE + id_length;
+    let mut palette = Vec::new();
+    if color_map_type == 1 {
+        if !matches!(map_entry_size, 15 | 16 | 24 | 32) {
+            return Err(format!(
+                "unsupported TGA color map entry size {}",
+                map_entry_size
+            ));
+        }
+        let entry_size = (map_entry_size as usize).div_ceil(8);
+        let map = bytes
+            .get(at..at + map_length * entry_size)
+            .ok_or_else(|| "TGA color map is truncated".to_string())?;
+        palette = map
+            .chunks_exact(entry_size)
+            .map(|entry| color(entry, has_alpha))
+            .collect();
+        at += map.len();
+    }
+
+    let mut image = Image::new(width, height)?;
+    let pixel_size = (depth as usize).div_ceil(8);
+    let count = width * height;
+    let data = bytes.get(at..).unwrap_or_default();
+    let truncated = || "TGA pixel data is truncated".to_string();
+
+    // Expand RLE packets (which may span rows) into a plain pixel stream
+    let expanded;
+    let pixels = if image_type & 8 != 0 {
+        let mut stream = Vec::with_capacity(count * pixel_size);
+        let mut at = 0;
+        while stream.len() < count * pixel_size {
+            let packet = *data.get(at).ok_or_else(truncated)?;
+            let length = (packet & 0x7f) as usize + 1;
+            if packet & 0x80 != 0 {
+                let pixel = data
+                    .get(at + 1..at + 1 + pixel_size)
+                    .ok_or_else(truncated)?;
+                for _ in 0..length {
+                    stream.extend_from_slice(pixel);
+                }
+                at += 1 + pixel_size;
+            } else {
+                let run = data
+                    .get(at + 1..at + 1 + length * pixel_size)
+                    .ok_or_else(truncated)?;
+                stream.extend_from_slice(run);
+                at += 1 + run.len();
+            }
+        }
+        stream.truncate(count * pixel_size);
+        expanded = stream;
+        &expanded[..]
+    } else {
+        data.get(..count * pixel_size).ok_or_else(truncated)?
+    };
+
+    for (i, pixel) in pixels.chunks_exact(pixel_size).enumerate() {
+        let (column, row) = (i % width, i / width);
+        let x = if right_to_left {
+            width - 1 - column
+        } else {
+            column
+        };
+        let y = if top_down { row } else { height - 1 - row };
+        let rgba = match image_type & !8 {
+            1 => {
+                let index = (pixel[0] as usize)
+                    .checked_sub(map_first)
+                    .filter(|index| *index < palette.len())
+                    .ok_or_else(|| format!("TGA color map index {} is out of range", pixel[0]))?;
+                palette[index]
+            }
+            3 => [pixel[0], pixel[0], pixel[0], 255],
+            _ => color(pixel, has_alpha),
+        };
+        image.set_pixel(x, y, rgba);
+    }
+    Ok(image)
+}
+
+/// A 15/16-bit (A1R5G5B5), 24-bit (BGR) or 32-bit (BGRA) color.
+fn color(bytes: &[u8], has_alpha: bool) -> [u8; 4] {
+    match *bytes {
+        [low, high] => {
+            let value = u16::from_le_bytes([low, high]);
+            let scale = |bits: u16| ((bits & 0x1f) as u32 * 255 / 31) as u8;
+            let alpha = if has_alpha && value & 0x8000 == 0 {
+                0
+            } else {
+                255
+            };
+            [scale(value >> 10), scale(value >> 5), scale(value), alpha]
+        }
+        [b, g, r] => [r, g, b, 255],
+        [b, g, r, a] => [r, g, b, if has_alpha { a } else { 255 }],
+        _ => unreachable!("TGA pixels are 2, 3 or 4 bytes"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tga(
+        image_type: u8,
+        width: u16,
+        height: u16,
+        depth: u8,
+        descriptor: u8,
+        color_map: Option<(u16, u8, &[u8])>,
+        data: &[u8],
+    ) -> Vec<u8> {
+        let (map_first, entry_size, map) = color_map.unwrap_or((0, 0, &[]));
+        let map_length = match entry_size {
+            0 => 0,
+            size => map.len() / (size as usize).div_ceil(8),
+        };
+        // A 2-byte image ID, which is skipped
+        let mut bytes = vec![2, color_map.is_some() as u8, image_type];
+        bytes.extend(map_first.to_le_bytes());
+        bytes.extend((map_length as u16).to_le_bytes());
+        bytes.push(entry_size);
+        bytes.extend([0; 4]);
+        bytes.extend(width.to_le_bytes());
+        bytes.extend(height.to_le_bytes());
+        bytes.extend([depth, descriptor, b'i', b'd']);
+        bytes.extend(map);
+        bytes.extend(data);
+        bytes
+    }
+
+    fn row(image: &Image, y: usize) -> Vec<[u8; 4]> {
+        (0..image.width).map(|x| image.pixel(x, y)).collect()
+    }
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const GREEN: [u8; 4] = [0, 255, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+    const WHITE: [u8; 4] = [255, 255, 255, 255];
+
+    #[test]
+    fn pixel_orders_and_depths_are_read() {
+        // Bottom-up by default: the first row in the file is the last one
+        let data = [0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255];
+        let image = decode(&tga(2, 2, 2, 24, 0, None, &data)).unwrap();
+        assert_eq!(row(&image, 1), [RED, GREEN]);
+        assert_eq!(row(&image, 0), [BLUE, WHITE]);
+        let image = decode(&tga(2, 2, 2, 24, 0x20, None, &data)).unwrap();
+        assert_eq!(row(&image, 0), [RED, GREEN]);
+        let image = decode(&tga(2, 2, 2, 24, 0x30, None, &data)).unwrap();
+        assert_eq!(row(&image, 0), [GREEN, RED]);
+
+        // 32-bit alpha is used when the descriptor declares alpha bits
+        let data = [10, 20, 30, 40];
+        let image = decode(&tga(2, 1, 1, 32, 8, None, &data)).unwrap();
+        assert_eq!(image.pixel(0, 0), [30, 20, 10, 40]);
+        let image = decode(&tga(2, 1, 1, 32, 0, None, &data)).unwrap();
+        assert_eq!(image.pixel(0, 0), [30, 20, 10, 255]);
+
+        // 16-bit A1R5G5B5 and 8-bit grayscale
+        let data = [0x1f, 0x00, 0x00, 0xfc];
+        let image = decode(&tga(2, 2, 1, 16, 1, None, &data)).unwrap();
+        assert_eq!(row(&image, 0), [[0, 0, 255, 0], RED]);
+        let image = decode(&tga(3, 2, 1, 8, 0, None, &[7, 200])).unwrap();
+        assert_eq!(row(&image, 0), [[7, 7, 7, 255], [200, 200, 200, 255]]);
+
+        // A color map starting at index 4
+        let map = [0, 0, 255, 0, 255, 0];
+        let image = decode(&tga(1, 2, 1, 8, 0, Some((4, 24, &map)), &[5, 4])).unwrap();
+        assert_eq!(row(&image, 0), [GREEN, RED]);
+        assert!(decode(&tga(1, 1, 1, 8, 0, Some((4, 24, &map)), &[3])).is_err());
+    }
+
+    #[test]
+    fn run_length_encoded_packets_are_expanded() {
+        // A run of 3 blue pixels spanning both rows, then 1 raw red one
+        let data = [0x82, 255, 0, 0, 0x00, 0, 0, 255];
+        let image = decode(&tga(10, 2, 2, 24, 0x20, None, &data)).unwrap();
+        assert_eq!(row(&image, 0), [BLUE, BLUE]);
+        assert_eq!(row(&image, 1), [BLUE, RED]);
+        let image = decode(&tga(11, 3, 1, 8, 0, None, &[0x01, 9, 8, 0x80, 7])).unwrap();
+        assert_eq!(image.pixel(1, 0), [8, 8, 8, 255]);
+        assert_eq!(image.pixel(2, 0), [7, 7, 7, 255]);
+        assert!(decode(&tga(10, 2, 2, 24, 0, None, &data[..4])).is_err());
+    }
+
+    #[test]
+    fn malformed_files_are_rejected() {
+        assert!(!is_tga(b"v 1 2 3\nv 4 5 6\nf 1 2 3\n"));
+        assert!(!is_tga(&tga(2, 1, 1, 12, 0, None, &[0; 4])));
+        assert!(!is_tga(&tga(1, 1, 1, 8, 0, None, &[0])));
+        assert!(!is_tga(&tga(2, 0, 1, 24, 0, None, &[])));
+        assert!(decode(&tga(2, 2, 2, 24, 0, None, &[0; 11])).is_err());
+        assert!(decode(&tga(1, 1, 1, 8, 0, Some((0, 24, &[0; 2])), &[0])).is_err());
+    }
+}
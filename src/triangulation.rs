@@ -0,0 +1,198 @@
+use crate::vectors::{Vec2, Vec3};
+
+/// Splits a polygon into triangles by ear clipping, which handles concave
+/// (but simple, roughly planar) polygons. Returns `None` when the polygon is
+/// degenerate or self-intersecting, in which case callers fall back to `fan`.
+pub fn ear_clip(points: &[Vec3]) -> Option<Vec<[usize; 3]>> {
+    if points.len() < 3 {
+        return None;
+    }
+    let projected = project_to_plane(points)?;
+
+    // Work counter-clockwise so convex corners have a positive cross product
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    if signed_area(&projected) < 0.0 {
+        remaining.reverse();
+    }
+
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let ear = (0..count).find(|&i| {
+            let prev = remaining[(i + count - 1) % count];
+            let current = remaining[i];
+            let next = remaining[(i + 1) % count];
+            is_ear(&projected, &remaining, prev, current, next)
+        })?;
+
+        let prev = remaining[(ear + count - 1) % count];
+        let next = remaining[(ear + 1) % count];
+        triangles.push(ordered(points.len(), [prev, remaining[ear], next]));
+        remaining.remove(ear);
+    }
+    triangles.push(ordered(
+        points.len(),
+        [remaining[0], remaining[1], remaining[2]],
+    ));
+
+    Some(triangles)
+}
+
+/// Triangle fan around the first vertex; only correct for convex polygons.
+pub fn fan(vertex_count: usize) -> Vec<[usize; 3]> {
+    (1..vertex_count.saturating_sub(1))
+        .map(|i| [0, i, i + 1])
+        .collect()
+}
+
+/// Flattens the polygon onto the axis plane it faces the most, using the
+/// Newell normal so slightly non-planar input still projects sensibly.
+fn project_to_plane(points: &[Vec3]) -> Option<Vec<Vec2>> {
+    let mut normal = Vec3::ZERO;
+    for (i, current) in points.iter().enumerate() {
+        let next = points[(i + 1) % points.len()];
+        normal.x += (current.y - next.y) * (current.z + next.z);
+        normal.y += (current.z - next.z) * (current.x + next.x);
+        normal.z += (current.x - next.x) * (current.y + next.y);
+    }
+    if normal.length() <= f32::EPSILON {
+        return None;
+    }
+
+    let (ax, ay, az) = (normal.x.abs(), normal.y.abs(), normal.z.abs());
+    let projected = points
+        .iter()
+        .map(|p| {
+            if az >= ax && az >= ay {
+                Vec2::new(p.x, p.y)
+            } else if ax >= ay {
+                Vec2::new(p.y, p.z)
+            } else {
+                Vec2::new(p.z, p.x)
+            }
+        })
+        .collect();
+    Some(projected)
+}
+
+fn signed_area(points: &[Vec2]) -> f32 {
+    let mut area = 0.0;
+    for (i, current) in points.iter().enumerate() {
+        area += current.perp_dot(points[(i + 1) % points.len()]);
+    }
+    area / 2.0
+}
+
+fn is_ear(points: &[Vec2], remaining: &[usize], prev: usize, current: usize, next: usize) -> bool {
+    let (a, b, c) = (points[prev], points[current], points[next]);
+    if (b - a).perp_dot(c - b) <= 0.0 {
+        return false; // reflex or collinear corner
+    }
+    !remaining
+        .iter()
+        .filter(|&&i| i != prev && i != current && i != next)
+        .any(|&i| point_in_triangle(points[i], a, b, c))
+}
+
+fn point_in_triangle(p: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    let d1 = (b - a).perp_dot(p - a);
+    let d2 = (c - b).perp_dot(p - b);
+    let d3 = (a - c).perp_dot(p - c);
+    d1 >= 0.0 && d2 >= 0.0 && d3 >= 0.0
+}
+
+/// Restores the polygon's original winding when it was reversed for clipping.
+fn ordered(vertex_count: usize, triangle: [usize; 3]) -> [usize; 3] {
+    let [a, b, c] = triangle;
+    let forward = |from: usize, to: usize| (to + vertex_count - from) % vertex_count;
+    if forward(a, b) + forward(b, c) + forward(c, a) == vertex_count {
+        triangle
+    } else {
+        [a, c, b]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(points: &[Vec3], triangles: &[[usize; 3]]) -> f32 {
+        triangles
+            .iter()
+            .map(|t| {
+                (points[t[1]] - points[t[0]])
+                    .cross(points[t[2]] - points[t[0]])
+                    .length()
+                    / 2.0
+            })
+            .sum()
+    }
+
+    fn winding(points: &[Vec3], triangle: [usize; 3]) -> f32 {
+        (points[triangle[1]] - points[triangle[0]])
+            .cross(points[triangle[2]] - points[triangle[0]])
+            .z
+    }
+
+    #[test]
+    fn fan_covers_convex_polygon() {
+        assert_eq!(fan(5), vec![[0, 1, 2], [0, 2, 3], [0, 3, 4]]);
+        assert!(fan(2).is_empty());
+    }
+
+    #[test]
+    fn concave_polygon_is_clipped_without_overlap() {
+        // L-shape with a reflex corner at index 3; a fan from 0 would spill outside
+        let points = [
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(2.0, 1.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            Vec3::new(1.0, 2.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+        ];
+        let triangles = ear_clip(&points).unwrap();
+        assert_eq!(triangles.len(), 4);
+        assert!((area(&points, &triangles) - 3.0).abs() < 1e-5);
+        assert!(triangles.iter().all(|&t| winding(&points, t) > 0.0));
+    }
+
+    #[test]
+    fn clockwise_polygons_keep_their_winding() {
+        let points = [
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 2.0, 1.0),
+            Vec3::new(1.0, 1.0, 1.0),
+            Vec3::new(2.0, 2.0, 1.0),
+            Vec3::new(2.0, 0.0, 1.0),
+        ];
+        let triangles = ear_clip(&points).unwrap();
+        assert_eq!(triangles.len(), 3);
+        assert!((area(&points, &triangles) - 3.0).abs() < 1e-5);
+        assert!(triangles.iter().all(|&t| winding(&points, t) < 0.0));
+    }
+
+    #[test]
+    fn polygons_in_other_planes_are_projected() {
+        let points = [
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, 1.0),
+            Vec3::new(0.0, 0.5, 0.4),
+            Vec3::new(0.0, 0.0, 1.0),
+        ];
+        let triangles = ear_clip(&points).unwrap();
+        assert_eq!(triangles.len(), 3);
+    }
+
+    #[test]
+    fn degenerate_polygons_are_rejected() {
+        let collinear = [
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(3.0, 0.0, 0.0),
+        ];
+        assert!(ear_clip(&collinear).is_none());
+    }
+}
@@ -0,0 +1,381 @@
+use std::ops::{Add, AddAssign, Div, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vec2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vec4 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Vec2 {
+    pub const ZERO: Vec2 = Vec2 { x: 0.0, y: 0.0 };
+
+    pub const fn new(x: f32, y: f32) -> Self {
+        Vec2 { x, y }
+    }
+
+    pub fn dot(self, other: Vec2) -> f32 {
+        self.x * other.x + self.y * other.y
+    }
+
+    /// Z component of the 3D cross product, i.e. twice the signed triangle area.
+    pub fn perp_dot(self, other: Vec2) -> f32 {
+        self.x * other.y - self.y * other.x
+    }
+
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(self) -> Vec2 {
+        let length = self.length();
+        if length > 0.0 {
+            self / length
+        } else {
+            self
+        }
+    }
+
+    pub fn to_array(self) -> [f32; 2] {
+        [self.x, self.y]
+    }
+}
+
+impl Vec3 {
+    pub const ZERO: Vec3 = Vec3 {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    };
+    pub const X: Vec3 = Vec3 {
+        x: 1.0,
+        y: 0.0,
+        z: 0.0,
+    };
+    pub const Y: Vec3 = Vec3 {
+        x: 0.0,
+        y: 1.0,
+        z: 0.0,
+    };
+    pub const Z: Vec3 = Vec3 {
+        x: 0.0,
+        y: 0.0,
+        z: 1.0,
+    };
+
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Vec3 { x, y, z }
+    }
+
+    pub fn splat(value: f32) -> Self {
+        Vec3::new(value, value, value)
+    }
+
+    pub fn dot(self, other: Vec3) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(self, other: Vec3) -> Vec3 {
+        Vec3::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    pub fn length_squared(self) -> f32 {
+        self.dot(self)
+    }
+
+    pub fn length(self) -> f32 {
+        self.length_squared().sqrt()
+    }
+
+    /// Unit vector in the same direction; zero vectors are returned unchanged.
+    pub fn normalize(self) -> Vec3 {
+        let length = self.length();
+        if length > 0.0 {
+            self / length
+        } else {
+            self
+        }
+    }
+
+    pub fn min(self, other: Vec3) -> Vec3 {
+        Vec3::new(
+            self.x.min(other.x),
+            self.y.min(other.y),
+            self.z.min(other.z),
+        )
+    }
+
+    pub fn max(self, other: Vec3) -> Vec3 {
+        Vec3::new(
+            self.x.max(other.x),
+            self.y.max(other.y),
+            self.z.max(other.z),
+        )
+    }
+
+    pub fn lerp(self, other: Vec3, t: f32) -> Vec3 {
+        self + (other - self) * t
+    }
+
+    pub fn extend(self, w: f32) -> Vec4 {
+        Vec4::new(self.x, self.y, self.z, w)
+    }
+
+    pub fn to_array(self) -> [f32; 3] {
+        [self.x, self.y, self.z]
+    }
+}
+
+impl Vec4 {
+    pub const ZERO: Vec4 = Vec4 {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+        w: 0.0,
+    };
+
+    pub const fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Vec4 { x, y, z, w }
+    }
+
+    pub fn dot(self, other: Vec4) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn truncate(self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
+    }
+
+    pub fn to_array(self) -> [f32; 4] {
+        [self.x, self.y, self.z, self.w]
+    }
+}
+
+// Operators are generated per type since each one is a plain component-wise map
+macro_rules! impl_vector_ops {
+    ($name:ident { $($field:ident),+ }, $len:literal) => {
+        impl Add for $name {
+            type Output = $name;
+            fn add(self, rhs: $name) -> $name {
+                $name { $($field: self.$field + rhs.$field),+ }
+            }
+        }
+
+        impl Sub for $name {
+            type Output = $name;
+            fn sub(self, rhs: $name) -> $name {
+                $name { $($field: self.$field - rhs.$field),+ }
+            }
+        }
+
+        impl Mul<f32> for $name {
+            type Output = $name;
+            fn mul(self, rhs: f32) -> $name {
+                $name { $($field: self.$field * rhs),+ }
+            }
+        }
+
+        impl Mul<$name> for f32 {
+            type Output = $name;
+            fn mul(self, rhs: $name) -> $name {
+                rhs * self
+            }
+        }
+
+        /// Component-wise (Hadamard) product.
+        impl Mul for $name {
+            type Output = $name;
+            fn mul(self, rhs: $name) -> $name {
+                $name { $($field: self.$field * rhs.$field),+ }
+            }
+        }
+
+        impl Div<f32> for $name {
+            type Output = $name;
+            fn div(self, rhs: f32) -> $name {
+                $name { $($field: self.$field / rhs),+ }
+            }
+        }
+
+        impl Neg for $name {
+            type Output = $name;
+            fn neg(self) -> $name {
+                $name { $($field: -self.$field),+ }
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, rhs: $name) {
+                $(self.$field += rhs.$field;)+
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, rhs: $name) {
+                $(self.$field -= rhs.$field;)+
+            }
+        }
+
+        impl MulAssign<f32> for $name {
+            fn mul_assign(&mut self, rhs: f32) {
+                $(self.$field *= rhs;)+
+            }
+        }
+
+        impl Index<usize> for $name {
+            type Output = f32;
+            fn index(&self, index: usize) -> &f32 {
+                let fields = [$(&self.$field),+];
+                fields[index]
+            }
+        }
+
+        impl IndexMut<usize> for $name {
+            fn index_mut(&mut self, index: usize) -> &mut f32 {
+                let fields = [$(&mut self.$field),+];
+                fields.into_iter().nth(index).expect("vector index out of range")
+            }
+        }
+
+        impl From<[f32; $len]> for $name {
+            fn from(array: [f32; $len]) -> $name {
+                let [$($field),+] = array;
+                $name { $($field),+ }
+            }
+        }
+
+        impl From<$name> for [f32; $len] {
+            fn from(vector: $name) -> [f32; $len] {
+                [$(vector.$field),+]
+            }
+        }
+    };
+}
+
+impl_vector_ops!(Vec2 { x, y }, 2);
+impl_vector_ops!(Vec3 { x, y, z }, 3);
+impl_vector_ops!(Vec4 { x, y, z, w }, 4);
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub const EPSILON: f32 = 1e-4;
+
+    /// Deterministic xorshift generator so property tests are reproducible.
+    pub struct Rng(u32);
+
+    impl Rng {
+        pub fn new(seed: u32) -> Self {
+            Rng(seed.max(1))
+        }
+
+        /// Uniform value in `[-1, 1)`.
+        pub fn next_f32(&mut self) -> f32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            (self.0 as f32 / u32::MAX as f32) * 2.0 - 1.0
+        }
+
+        pub fn vec3(&mut self, scale: f32) -> Vec3 {
+            Vec3::new(self.next_f32(), self.next_f32(), self.next_f32()) * scale
+        }
+    }
+
+    pub fn assert_vec3_eq(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < EPSILON, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn arithmetic_is_component_wise() {
+        let a = Vec3::new(1.0, 2.0, 3.0);
+        let b = Vec3::new(4.0, -5.0, 6.0);
+        assert_eq!(a + b, Vec3::new(5.0, -3.0, 9.0));
+        assert_eq!(a - b, Vec3::new(-3.0, 7.0, -3.0));
+        assert_eq!(a * 2.0, Vec3::new(2.0, 4.0, 6.0));
+        assert_eq!(2.0 * a, a * 2.0);
+        assert_eq!(a * b, Vec3::new(4.0, -10.0, 18.0));
+        assert_eq!(-a, Vec3::new(-1.0, -2.0, -3.0));
+        assert_eq!(b / 2.0, Vec3::new(2.0, -2.5, 3.0));
+
+        let mut c = a;
+        c += b;
+        c -= a;
+        c *= 0.5;
+        assert_eq!(c, b * 0.5);
+    }
+
+    #[test]
+    fn indexing_and_conversions() {
+        let mut v = Vec4::new(1.0, 2.0, 3.0, 4.0);
+        assert_eq!(v[3], 4.0);
+        v[1] = 9.0;
+        assert_eq!(v.to_array(), [1.0, 9.0, 3.0, 4.0]);
+        assert_eq!(
+            Vec3::from([1.0, 2.0, 3.0]).extend(1.0).truncate(),
+            Vec3::new(1.0, 2.0, 3.0)
+        );
+        assert_eq!(<[f32; 2]>::from(Vec2::new(5.0, 6.0)), [5.0, 6.0]);
+    }
+
+    #[test]
+    fn cross_product_of_axes() {
+        assert_eq!(Vec3::X.cross(Vec3::Y), Vec3::Z);
+        assert_eq!(Vec3::Y.cross(Vec3::Z), Vec3::X);
+        assert_eq!(Vec3::Z.cross(Vec3::X), Vec3::Y);
+        assert_eq!(Vec2::new(1.0, 0.0).perp_dot(Vec2::new(0.0, 1.0)), 1.0);
+    }
+
+    #[test]
+    fn normalize_handles_zero() {
+        assert_eq!(Vec3::ZERO.normalize(), Vec3::ZERO);
+        assert!((Vec3::new(3.0, 4.0, 0.0).normalize().length() - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn cross_is_orthogonal_and_anticommutative() {
+        let mut rng = Rng::new(7);
+        for _ in 0..200 {
+            let (a, b) = (rng.vec3(10.0), rng.vec3(10.0));
+            let c = a.cross(b);
+            let tolerance = EPSILON * a.length() * b.length() * 10.0;
+            assert!(c.dot(a).abs() <= tolerance);
+            assert!(c.dot(b).abs() <= tolerance);
+            assert_vec3_eq(c, -b.cross(a));
+        }
+    }
+
+    #[test]
+    fn normalized_vectors_have_unit_length() {
+        let mut rng = Rng::new(42);
+        for _ in 0..200 {
+            let v = rng.vec3(100.0);
+            if v.length() > EPSILON {
+                assert!((v.normalize().length() - 1.0).abs() < EPSILON);
+            }
+        }
+    }
+}